@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+
+use {Error, ScanRequest, WirelessNetwork};
+
+/// A ScanBackend is whatever actually talks to the wireless hardware. `WifiScan`
+/// runs every scan through one of these, so swapping the backend changes where
+/// results come from without touching any code downstream of the scan.
+pub trait ScanBackend {
+    /// Trigger the scan `request` describes and return a handle to collect
+    /// the results from once it finishes, without waiting for it.
+    fn start_scan(&mut self, request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error>;
+
+    /// Run the scan `request` describes and return the networks it found,
+    /// blocking until the scan is done.
+    fn scan(&mut self, request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        let mut pending = self.start_scan(request)?;
+        loop {
+            if let Some(networks) = pending.poll_results()? {
+                return Ok(networks);
+            }
+            thread::sleep(pending.poll_interval());
+        }
+    }
+
+    /// The results the kernel kept from the last scan of the interface,
+    /// whoever ran it, without triggering a new one.
+    fn cached_results(&mut self, _request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        Err(Error::Unsupported {
+            call: "ScanBackend::cached_results",
+            message: "the backend can't read cached scan results",
+        })
+    }
+}
+
+/// A scan that has been triggered and may still be running.
+pub trait PendingScan: Send {
+    /// Check on the scan without blocking. Returns `Ok(None)` while it is still
+    /// running and the results once it has finished; after that, and after an
+    /// error, the scan is over and shouldn't be polled again.
+    fn poll_results(&mut self) -> Result<Option<Vec<WirelessNetwork>>, Error>;
+
+    /// How long to wait between calls to `poll_results`.
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(100)
+    }
+
+    /// Give up on the scan, asking the driver to stop it where possible.
+    fn cancel(&mut self) {}
+}
+
+impl<B: ScanBackend + ?Sized> ScanBackend for &mut B {
+    fn start_scan(&mut self, request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+        (**self).start_scan(request)
+    }
+
+    fn scan(&mut self, request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        (**self).scan(request)
+    }
+
+    fn cached_results(&mut self, request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        (**self).cached_results(request)
+    }
+}
+
+impl<B: ScanBackend + ?Sized> ScanBackend for Box<B> {
+    fn start_scan(&mut self, request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+        (**self).start_scan(request)
+    }
+
+    fn scan(&mut self, request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        (**self).scan(request)
+    }
+
+    fn cached_results(&mut self, request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        (**self).cached_results(request)
+    }
+}
+
+/// A scan whose results are already in hand, for backends that can't scan
+/// without blocking. The results are ready after `delay` more polls.
+pub(crate) struct FinishedScan {
+    networks: Option<Vec<WirelessNetwork>>,
+    delay: usize,
+}
+
+impl FinishedScan {
+    pub(crate) fn new(networks: Vec<WirelessNetwork>) -> FinishedScan {
+        FinishedScan {
+            networks: Some(networks),
+            delay: 0,
+        }
+    }
+}
+
+impl PendingScan for FinishedScan {
+    fn poll_results(&mut self) -> Result<Option<Vec<WirelessNetwork>>, Error> {
+        if self.delay > 0 {
+            self.delay -= 1;
+            return Ok(None);
+        }
+        match self.networks.take() {
+            Some(networks) => Ok(Some(networks)),
+            None => {
+                Err(Error::InvalidInput {
+                    call: "PendingScan::poll_results",
+                    message: "scan results were already collected",
+                })
+            }
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(1)
+    }
+}
+
+/// A ScanBackend that hands back canned network lists instead of touching any
+/// hardware, for testing code that consumes scan results.
+///
+/// Each scan returns the next queued list. Once the queue is down to its last
+/// list, that list is returned for every scan after it. A request for certain
+/// frequencies only gets the networks on them. Queued failures come out
+/// before any more lists, and the cached results are whatever the last
+/// successful scan returned.
+#[derive(Clone, Default)]
+pub struct FixtureBackend {
+    scans: VecDeque<Vec<WirelessNetwork>>,
+    scan_count: usize,
+    delay: usize,
+    last_request: Option<ScanRequest>,
+    failures: VecDeque<Error>,
+    cached: Vec<WirelessNetwork>,
+}
+
+impl FixtureBackend {
+    /// A backend that returns `networks` from every scan.
+    pub fn new(networks: Vec<WirelessNetwork>) -> FixtureBackend {
+        FixtureBackend::sequence(vec![networks])
+    }
+
+    /// A backend that returns each list in `scans` in turn, repeating the last one.
+    pub fn sequence(scans: Vec<Vec<WirelessNetwork>>) -> FixtureBackend {
+        FixtureBackend {
+            scans: scans.into_iter().collect(),
+            scan_count: 0,
+            delay: 0,
+            last_request: None,
+            failures: VecDeque::new(),
+            cached: Vec::new(),
+        }
+    }
+
+    /// Make every scan report that it's still running for `polls` polls
+    /// before handing over its results, like a real scan would.
+    pub fn delay(mut self, polls: usize) -> FixtureBackend {
+        self.delay = polls;
+        self
+    }
+
+    /// Queue up another list to be returned after the ones already queued.
+    pub fn push(&mut self, networks: Vec<WirelessNetwork>) {
+        self.scans.push_back(networks);
+    }
+
+    /// Make the next scan that isn't already set to fail fail with `err`.
+    pub fn fail(&mut self, err: Error) {
+        self.failures.push_back(err);
+    }
+
+    /// How many scans have been run against this backend.
+    pub fn scan_count(&self) -> usize {
+        self.scan_count
+    }
+
+    /// The request the most recent scan was started with.
+    pub fn last_request(&self) -> Option<&ScanRequest> {
+        self.last_request.as_ref()
+    }
+}
+
+impl ScanBackend for FixtureBackend {
+    fn start_scan(&mut self, request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+        request.validate()?;
+        self.scan_count += 1;
+        self.last_request = Some(request.clone());
+        if let Some(err) = self.failures.pop_front() {
+            return Err(err);
+        }
+        let mut networks = if self.scans.len() > 1 {
+            self.scans.pop_front().unwrap()
+        } else {
+            self.scans.front().cloned().unwrap_or_default()
+        };
+        if !request.frequencies.is_empty() {
+            networks.retain(|network| {
+                network.channel().is_some_and(|channel| request.frequencies.contains(&channel.mhz()))
+            });
+        }
+        self.cached = networks.clone();
+        let mut pending = FinishedScan::new(networks);
+        pending.delay = self.delay;
+        Ok(Box::new(pending))
+    }
+
+    fn cached_results(&mut self, _request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        Ok(self.cached.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Ssid, WifiScan};
+    use test_util::{essids, network};
+
+    #[test]
+    fn fixture_repeats_single_list() {
+        let mut backend = FixtureBackend::new(vec![network("home"), network("cafe")]);
+        for _ in 0..3 {
+            let scan = WifiScan::scan_with(&mut backend, "wlan0").unwrap();
+            assert_eq!(essids(&scan.networks), vec!["home", "cafe"]);
+        }
+        assert_eq!(backend.scan_count(), 3);
+    }
+
+    #[test]
+    fn fixture_plays_sequence_then_repeats_last() {
+        let mut backend = FixtureBackend::sequence(vec![vec![network("a")],
+                                                        vec![network("a"), network("b")]]);
+        backend.push(vec![network("b")]);
+        let seen: Vec<Vec<String>> = (0..4)
+            .map(|_| essids(&WifiScan::scan_with(&mut backend, "wlan0").unwrap().networks))
+            .collect();
+        assert_eq!(seen, vec![vec!["a"], vec!["a", "b"], vec!["b"], vec!["b"]]);
+    }
+
+    #[test]
+    fn empty_fixture_returns_no_networks() {
+        let mut backend = FixtureBackend::default();
+        assert!(backend.scan(&"wlan0".into()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fixture_honors_frequencies() {
+        let mut on_six = network("six");
+        on_six.freq = Some(2.437e9);
+        let mut backend = FixtureBackend::new(vec![network("unknown"), on_six]);
+        let request = ScanRequest::new("wlan0").frequency(2437);
+        let found = backend.scan(&request).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].essid, Ssid::new(b"six"));
+        assert_eq!(backend.last_request(), Some(&request));
+    }
+
+    #[test]
+    fn boxed_backend() {
+        let mut backend: Box<dyn ScanBackend> = Box::new(FixtureBackend::new(vec![network("x")]));
+        let scan = WifiScan::scan_with(&mut backend, "wlan0").unwrap();
+        assert_eq!(essids(&scan.networks), vec!["x"]);
+    }
+}
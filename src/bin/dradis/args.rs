@@ -0,0 +1,335 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use dradis::{Band, Protocol, WirelessNetwork};
+
+pub const USAGE: &str = "\
+usage: dradis <command> [options]
+
+commands:
+  scan [INTERFACE]          scan for networks and list them
+  watch [INTERFACE]         scan over and over and report what changes
+  interfaces                list the wireless interfaces
+  capabilities [INTERFACE]  show what an interface's driver supports
+  help                      show this message
+
+options for scan and watch:
+  -f, --format FORMAT       table (the default) or json; for scan, also csv
+                            or wigle, WiGLE's upload format
+  -s, --sort KEY            for scan, signal (the default), ssid, channel or
+                            bssid
+  -b, --band BAND           only networks on 2.4, 5, 6 or 60 GHz
+  -m, --min-signal DBM      only networks at least this strong, e.g. -70
+  -S, --security LIST       only networks using one of open, wep, wpa, wpa2,
+                            wpa3, separated by commas
+      --from-file PATH      read scans from a JSON file saved with
+                            `dradis scan -f json` instead of scanning
+
+options for watch:
+  -n, --interval SECONDS    time between scans, 10 by default
+  -c, --count SCANS         stop after this many scans
+
+The interface defaults to the first one that is up.";
+
+/// What the command line asked for.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Scan(ScanOptions),
+    Watch(ScanOptions, WatchOptions),
+    Interfaces,
+    Capabilities(Option<String>),
+    Help,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+    Wigle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Signal,
+    Ssid,
+    Channel,
+    Bssid,
+}
+
+/// Which networks to show.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub band: Option<Band>,
+    pub min_signal: Option<i32>,
+    /// Empty means any.
+    pub security: Vec<Protocol>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ScanOptions {
+    pub interface: Option<String>,
+    pub format: Format,
+    pub sort: SortKey,
+    pub filter: Filter,
+    pub from_file: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WatchOptions {
+    pub interval: Duration,
+    pub count: Option<usize>,
+}
+
+impl Filter {
+    pub fn matches(&self, network: &WirelessNetwork) -> bool {
+        if self.band.is_some() && network.band() != self.band {
+            return false;
+        }
+        if let Some(min) = self.min_signal {
+            match network.signal_dbm() {
+                Some(dbm) if dbm >= min => {}
+                _ => return false,
+            }
+        }
+        self.security.is_empty() || self.security.contains(&network.security.protocol)
+    }
+}
+
+/// Put `networks` in the order `key` asks for: strongest signal, or
+/// ascending otherwise. Networks missing the key go last.
+pub fn sort(networks: &mut [WirelessNetwork], key: SortKey) {
+    match key {
+        SortKey::Signal => networks.sort_by_key(|n| n.signal_dbm().map_or(i32::MAX, |dbm| -dbm)),
+        SortKey::Ssid => {
+            networks.sort_by_cached_key(|n| (n.essid.is_none(), n.essid.map(|ssid| ssid.as_bytes().to_vec())))
+        }
+        SortKey::Channel => networks.sort_by_key(|n| (n.channel().is_none(), n.channel())),
+        SortKey::Bssid => networks.sort_by_key(|n| (n.bssid.is_none(), n.bssid)),
+    }
+}
+
+/// Parse the arguments after the program name.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(Command::Help),
+    };
+    let mut scan = ScanOptions {
+        interface: None,
+        format: Format::Table,
+        sort: SortKey::Signal,
+        filter: Filter::default(),
+        from_file: None,
+    };
+    let mut watch = WatchOptions {
+        interval: Duration::from_secs(10),
+        count: None,
+    };
+    let takes_scan_options = command == "scan" || command == "watch";
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            if scan.interface.is_some() || command == "interfaces" {
+                return Err(format!("unexpected argument '{}'", arg));
+            }
+            scan.interface = Some(arg);
+            continue;
+        }
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        }
+        let (flag, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => {
+                (arg[..i].to_string(), Some(arg[i + 1..].to_string()))
+            }
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline.clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match flag.as_str() {
+            "-i" | "--interface" if command != "interfaces" => scan.interface = Some(value()?),
+            "-f" | "--format" if takes_scan_options => scan.format = parse_format(&value()?)?,
+            "-s" | "--sort" if command == "scan" => scan.sort = parse_sort(&value()?)?,
+            "-b" | "--band" if takes_scan_options => scan.filter.band = Some(parse_band(&value()?)?),
+            "-m" | "--min-signal" if takes_scan_options => {
+                let dbm = value()?;
+                let dbm = dbm.parse().map_err(|_| format!("bad signal level '{}'", dbm))?;
+                scan.filter.min_signal = Some(dbm);
+            }
+            "-S" | "--security" if takes_scan_options => {
+                for name in value()?.split(',') {
+                    scan.filter.security.push(parse_protocol(name)?);
+                }
+            }
+            "--from-file" if takes_scan_options => scan.from_file = Some(PathBuf::from(value()?)),
+            "-n" | "--interval" if command == "watch" => {
+                let seconds = value()?;
+                watch.interval = seconds.parse()
+                    .ok()
+                    .and_then(|s: f64| Duration::try_from_secs_f64(s).ok())
+                    .filter(|interval| !interval.is_zero())
+                    .ok_or_else(|| format!("bad interval '{}'", seconds))?;
+            }
+            "-c" | "--count" if command == "watch" => {
+                let count = value()?;
+                watch.count = Some(count.parse().map_err(|_| format!("bad count '{}'", count))?);
+            }
+            _ => return Err(format!("unknown option '{}' for {}", flag, command)),
+        }
+    }
+    match command.as_str() {
+        "scan" => Ok(Command::Scan(scan)),
+        "watch" if scan.format == Format::Csv || scan.format == Format::Wigle => {
+            Err("watch only writes tables and json".to_string())
+        }
+        "watch" => Ok(Command::Watch(scan, watch)),
+        "interfaces" => Ok(Command::Interfaces),
+        "capabilities" | "caps" => Ok(Command::Capabilities(scan.interface)),
+        "help" => Ok(Command::Help),
+        _ => Err(format!("unknown command '{}'", command)),
+    }
+}
+
+fn parse_format(format: &str) -> Result<Format, String> {
+    match format {
+        "table" => Ok(Format::Table),
+        "json" => Ok(Format::Json),
+        "csv" => Ok(Format::Csv),
+        "wigle" => Ok(Format::Wigle),
+        _ => Err(format!("unknown format '{}'", format)),
+    }
+}
+
+fn parse_sort(key: &str) -> Result<SortKey, String> {
+    match key {
+        "signal" => Ok(SortKey::Signal),
+        "ssid" => Ok(SortKey::Ssid),
+        "channel" => Ok(SortKey::Channel),
+        "bssid" => Ok(SortKey::Bssid),
+        _ => Err(format!("unknown sort key '{}'", key)),
+    }
+}
+
+fn parse_band(band: &str) -> Result<Band, String> {
+    match band.to_ascii_lowercase().trim_end_matches("ghz") {
+        "2.4" | "2" => Ok(Band::TwoGhz),
+        "5" => Ok(Band::FiveGhz),
+        "6" => Ok(Band::SixGhz),
+        "60" => Ok(Band::SixtyGhz),
+        _ => Err(format!("unknown band '{}'", band)),
+    }
+}
+
+fn parse_protocol(protocol: &str) -> Result<Protocol, String> {
+    match protocol.to_ascii_lowercase().as_str() {
+        "open" => Ok(Protocol::Open),
+        "wep" => Ok(Protocol::Wep),
+        "wpa" => Ok(Protocol::Wpa),
+        "wpa2" => Ok(Protocol::Wpa2),
+        "wpa3" => Ok(Protocol::Wpa3),
+        _ => Err(format!("unknown security '{}'", protocol)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dradis::{IwQuality, IwStats, Security, Ssid};
+
+    fn args(line: &str) -> Result<Command, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    fn network(essid: &str, mhz: u32, dbm: Option<i32>, protocol: Protocol) -> WirelessNetwork {
+        let quality = |dbm: i32| {
+            IwQuality::new(0, dbm as u8, 0, IwQuality::LEVEL_UPDATED | IwQuality::DBM)
+        };
+        WirelessNetwork {
+            stats: dbm.map(|dbm| IwStats::new(quality(dbm))),
+            freq: Some(mhz as f64 * 1e6),
+            essid: Ssid::new(essid.as_bytes()),
+            security: Security { protocol, ..Security::open() },
+            ..WirelessNetwork::default()
+        }
+    }
+
+    #[test]
+    fn parses_scan_options() {
+        let command = args("scan wlan1 --format=csv -s ssid -b 5GHz -m -70 -S wpa2,WPA3 --from-file x.json");
+        assert_eq!(command,
+                   Ok(Command::Scan(ScanOptions {
+                       interface: Some("wlan1".to_string()),
+                       format: Format::Csv,
+                       sort: SortKey::Ssid,
+                       filter: Filter {
+                           band: Some(Band::FiveGhz),
+                           min_signal: Some(-70),
+                           security: vec![Protocol::Wpa2, Protocol::Wpa3],
+                       },
+                       from_file: Some(PathBuf::from("x.json")),
+                   })));
+    }
+
+    #[test]
+    fn parses_other_commands() {
+        assert_eq!(args(""), Ok(Command::Help));
+        assert_eq!(args("interfaces"), Ok(Command::Interfaces));
+        assert_eq!(args("capabilities -i wlan0"),
+                   Ok(Command::Capabilities(Some("wlan0".to_string()))));
+        match args("watch -n 2.5 -c 3 -f json").unwrap() {
+            Command::Watch(scan, watch) => {
+                assert_eq!(scan.format, Format::Json);
+                assert_eq!(watch.interval, Duration::from_millis(2500));
+                assert_eq!(watch.count, Some(3));
+            }
+            command => panic!("{:?}", command),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(args("frobnicate").is_err());
+        assert!(args("scan --band 3").is_err());
+        assert!(args("scan --min-signal").unwrap_err().contains("needs a value"));
+        assert!(args("scan --interval 5").is_err());
+        assert!(args("scan wlan0 wlan1").is_err());
+        assert!(args("interfaces --sort ssid").is_err());
+        assert!(args("watch -f csv").is_err());
+        assert!(args("watch -f wigle").is_err());
+        assert!(args("watch -n -1").is_err());
+        assert!(args("watch -n 0").unwrap_err().contains("bad interval"));
+        assert!(args("watch -n 1e30").unwrap_err().contains("bad interval"));
+        assert!(args("watch -n NaN").is_err());
+    }
+
+    #[test]
+    fn filters_and_sorts() {
+        let mut networks = vec![network("b", 2412, Some(-80), Protocol::Wpa2),
+                                network("a", 5180, Some(-50), Protocol::Open),
+                                network("c", 5745, None, Protocol::Wpa3),
+                                network("d", 2437, Some(-60), Protocol::Wpa3)];
+        let filter = Filter {
+            min_signal: Some(-70),
+            ..Filter::default()
+        };
+        let strong: Vec<_> = networks.iter().filter(|n| filter.matches(n)).map(|n| n.essid).collect();
+        assert_eq!(strong, vec![Ssid::new(b"a"), Ssid::new(b"d")]);
+        let filter = Filter {
+            band: Some(Band::FiveGhz),
+            security: vec![Protocol::Wpa3],
+            ..Filter::default()
+        };
+        assert_eq!(networks.iter().filter(|n| filter.matches(n)).count(), 1);
+
+        sort(&mut networks, SortKey::Signal);
+        let order: Vec<_> = networks.iter().map(|n| n.essid.unwrap().to_string()).collect();
+        assert_eq!(order, vec!["a", "d", "b", "c"]);
+        sort(&mut networks, SortKey::Channel);
+        let order: Vec<_> = networks.iter().map(|n| n.essid.unwrap().to_string()).collect();
+        assert_eq!(order, vec!["b", "d", "a", "c"]);
+    }
+}
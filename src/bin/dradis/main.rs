@@ -0,0 +1,189 @@
+//! `dradis`, for scanning from a shell: list networks as a table, JSON or
+//! CSV, watch them come and go, and look at the interfaces doing the
+//! scanning. Run `dradis help` for the options.
+
+extern crate dradis;
+extern crate serde_json;
+
+mod args;
+mod output;
+
+use std::cmp;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use args::{Command, Format, ScanOptions, WatchOptions};
+use dradis::{FixtureBackend, InterfaceCapabilities, ScanBackend, ScanMonitor, ScanRequest, WextBackend,
+             WifiScan};
+use dradis::wigle::WigleWriter;
+
+fn main() {
+    let command = match args::parse(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("dradis: {}\nRun `dradis help` for usage.", message);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(command) {
+        // Output piped into something like `head` that stopped reading.
+        if err.downcast_ref::<io::Error>().is_some_and(|err| err.kind() == ErrorKind::BrokenPipe) {
+            return;
+        }
+        eprintln!("dradis: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match command {
+        Command::Help => writeln!(out, "{}", args::USAGE)?,
+        Command::Interfaces => output::interfaces(&mut out, &dradis::interfaces()?)?,
+        Command::Capabilities(interface) => {
+            let interface = interface_or_default(interface)?;
+            output::capabilities(&mut out, &interface, &InterfaceCapabilities::query(&interface)?)?;
+        }
+        Command::Scan(options) => scan(&mut out, options)?,
+        Command::Watch(options, watch_options) => watch(&mut out, options, watch_options)?,
+    }
+    Ok(())
+}
+
+fn scan<W: Write>(out: &mut W, options: ScanOptions) -> Result<(), Box<dyn Error>> {
+    let (mut backend, interface) = backend(&options)?;
+    // Without root, show what the kernel kept from the last scan.
+    let request = ScanRequest::new(&interface).cached_if_denied(true);
+    let mut scan = WifiScan::scan_request_with(&mut *backend, &request)?;
+    scan.networks.retain(|network| options.filter.matches(network));
+    args::sort(&mut scan.networks, options.sort);
+    match options.format {
+        Format::Table => {
+            if scan.stale {
+                eprintln!("dradis: not allowed to scan, showing the results of the last scan");
+            }
+            output::scan_table(out, &scan.networks)?;
+        }
+        Format::Json => output::scan_json(out, &scan)?,
+        Format::Csv => output::scan_csv(out, &scan.networks)?,
+        Format::Wigle => {
+            let mut writer = WigleWriter::new(out)?;
+            writer.write_scan(&scan, None, None)?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn watch<W: Write>(out: &mut W, options: ScanOptions, watch: WatchOptions) -> Result<(), Box<dyn Error>> {
+    let (backend, interface) = backend(&options)?;
+    // Don't lose networks just because scans are far apart.
+    let expiry = cmp::max(Duration::from_secs(30), watch.interval.saturating_mul(3));
+    let mut monitor = ScanMonitor::new(backend, &interface)
+        .interval(watch.interval)
+        .expiry(expiry);
+    let mut scans = 0;
+    loop {
+        // Scans start every interval, however long each one takes.
+        let due = Instant::now().checked_add(watch.interval);
+        // Like iterating over the monitor, a failed scan doesn't stop it.
+        match monitor.poll() {
+            Ok(events) => {
+                for event in events.iter().filter(|event| options.filter.matches(event.network())) {
+                    match options.format {
+                        Format::Json => output::event_json(out, event)?,
+                        _ => output::event_line(out, event)?,
+                    }
+                }
+                out.flush()?;
+            }
+            Err(err) => eprintln!("dradis: {}", err),
+        }
+        scans += 1;
+        if watch.count.is_some_and(|count| scans >= count) {
+            return Ok(());
+        }
+        match due {
+            Some(due) => thread::sleep(due.saturating_duration_since(Instant::now())),
+            None => thread::sleep(watch.interval),
+        }
+    }
+}
+
+/// The backend `options` asks for, and the interface to scan with it.
+fn backend(options: &ScanOptions) -> Result<(Box<dyn ScanBackend>, String), Box<dyn Error>> {
+    match options.from_file {
+        Some(ref path) => {
+            let scans = load(path)?;
+            let backend = FixtureBackend::sequence(scans.into_iter().map(|scan| scan.networks).collect());
+            let interface = options.interface.clone().unwrap_or_else(|| path.display().to_string());
+            Ok((Box::new(backend), interface))
+        }
+        None => Ok((Box::new(WextBackend::new()), interface_or_default(options.interface.clone())?)),
+    }
+}
+
+fn interface_or_default(interface: Option<String>) -> Result<String, Box<dyn Error>> {
+    if let Some(interface) = interface {
+        return Ok(interface);
+    }
+    let interfaces = dradis::interfaces()?;
+    match dradis::default_interface(&interfaces) {
+        Some(interface) => Ok(interface.name.clone()),
+        None => Err("no wireless interface found".into()),
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<WifiScan>, Box<dyn Error>> {
+    let json = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    parse_scans(&json).map_err(|err| format!("{}: {}", path.display(), err).into())
+}
+
+/// A scan saved by `dradis scan -f json`, or an array of them to replay one
+/// after another.
+fn parse_scans(json: &str) -> Result<Vec<WifiScan>, serde_json::Error> {
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    match value {
+        serde_json::Value::Array(scans) => scans.into_iter().map(serde_json::from_value).collect(),
+        scan => Ok(vec![serde_json::from_value(scan)?]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_one_scan_or_several() {
+        let scan = r#"{"version":1,"networks":[],"stale":false}"#;
+        assert_eq!(parse_scans(scan).unwrap().len(), 1);
+        assert_eq!(parse_scans(&format!("[{0},{0}]", scan)).unwrap().len(), 2);
+        assert!(parse_scans(r#"{"version":9,"networks":[],"stale":false}"#).is_err());
+        assert!(parse_scans("[1]").is_err());
+    }
+
+    #[test]
+    fn scans_from_a_file() {
+        let path = env::temp_dir().join(format!("dradis-cli-{}.json", process::id()));
+        fs::write(&path, r#"{"version":1,"networks":[],"stale":false}"#).unwrap();
+        let options = match args::parse(vec!["scan".to_string(),
+                                             "-f".to_string(),
+                                             "csv".to_string(),
+                                             "--from-file".to_string(),
+                                             path.display().to_string()]) {
+            Ok(Command::Scan(options)) => options,
+            other => panic!("{:?}", other),
+        };
+        let mut out = Vec::new();
+        scan(&mut out, options).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+    }
+}
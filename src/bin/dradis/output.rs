@@ -0,0 +1,253 @@
+use std::io::{self, Write};
+
+use dradis::csv;
+use dradis::{Interface, InterfaceCapabilities, ScanEvent, TxPower, WifiScan, WirelessNetwork};
+use serde_json;
+
+/// Columns of text padded to line up, the last one left ragged.
+struct Table {
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(header: &[&str]) -> Table {
+        Table { rows: vec![header.iter().map(|title| title.to_string()).collect()] }
+    }
+
+    fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut widths = vec![0; self.rows[0].len()];
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        for row in &self.rows {
+            let mut line = String::new();
+            for (cell, width) in row.iter().zip(&widths) {
+                line.push_str(&format!("{:1$}  ", cell, width));
+            }
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+fn ssid(network: &WirelessNetwork) -> String {
+    match network.essid {
+        Some(ssid) if !ssid.is_hidden() => ssid.to_string(),
+        _ => "<hidden>".to_string(),
+    }
+}
+
+fn rate_mbps(network: &WirelessNetwork) -> Option<f64> {
+    network.maxbitrate.map(|bps| bps as f64 / 1e6)
+}
+
+pub fn scan_table<W: Write>(out: &mut W, networks: &[WirelessNetwork]) -> io::Result<()> {
+    let mut table = Table::new(&["BSSID", "SSID", "CH", "BAND", "SIGNAL", "SECURITY", "RATE"]);
+    for network in networks {
+        table.row(vec![or_dash(network.bssid),
+                       ssid(network),
+                       or_dash(network.channel().map(|channel| channel.number())),
+                       or_dash(network.band()),
+                       signal(network),
+                       network.security.to_string(),
+                       or_dash(rate_mbps(network).map(|mbps| format!("{} Mb/s", mbps)))]);
+    }
+    table.write(out)
+}
+
+const CSV_COLUMNS: [&str; 9] = ["bssid", "ssid", "channel", "frequency_mhz", "signal_dbm", "quality",
+                                "security", "max_rate_mbps", "mode"];
+
+pub fn scan_csv<W: Write>(out: &mut W, networks: &[WirelessNetwork]) -> io::Result<()> {
+    csv::write_record(out, &CSV_COLUMNS)?;
+    for network in networks {
+        let fields = [network.bssid.map(|bssid| bssid.to_string()),
+                      network.essid.map(|ssid| ssid.to_string()),
+                      network.channel().map(|channel| channel.number().to_string()),
+                      network.channel().map(|channel| channel.mhz().to_string()),
+                      network.signal_dbm().map(|dbm| dbm.to_string()),
+                      network.signal_percent().map(|percent| percent.to_string()),
+                      Some(network.security.to_string()),
+                      rate_mbps(network).map(|mbps| mbps.to_string()),
+                      network.mode.map(|mode| format!("{:?}", mode))];
+        let fields: Vec<&str> = fields.iter()
+            .map(|field| field.as_ref().map_or("", String::as_str))
+            .collect();
+        csv::write_record(out, &fields)?;
+    }
+    Ok(())
+}
+
+pub fn scan_json<W: Write>(out: &mut W, scan: &WifiScan) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, scan)?;
+    writeln!(out)
+}
+
+pub fn interfaces<W: Write>(out: &mut W, interfaces: &[Interface]) -> io::Result<()> {
+    let mut table = Table::new(&["NAME", "STATE", "MAC", "DRIVER", "PHY"]);
+    for interface in interfaces {
+        table.row(vec![interface.name.clone(),
+                       format!("{:?}", interface.operstate).to_lowercase(),
+                       or_dash(interface.mac),
+                       or_dash(interface.driver.as_ref()),
+                       or_dash(interface.phy.as_ref())]);
+    }
+    table.write(out)
+}
+
+fn join<I: IntoIterator<Item = String>>(items: I) -> String {
+    let items: Vec<String> = items.into_iter().collect();
+    if items.is_empty() { "-".to_string() } else { items.join(" ") }
+}
+
+pub fn capabilities<W: Write>(out: &mut W,
+                               interface: &str,
+                               caps: &InterfaceCapabilities)
+                               -> io::Result<()> {
+    let txpower = caps.txpower.iter().map(|power| match *power {
+        TxPower::Dbm(dbm) => format!("{}dBm", dbm),
+        TxPower::Milliwatts(mw) => format!("{}mW", mw),
+        TxPower::Relative(level) => level.to_string(),
+    });
+    let ciphers = &caps.ciphers;
+    let ciphers = [(ciphers.wpa, "WPA"),
+                   (ciphers.wpa2, "WPA2"),
+                   (ciphers.tkip, "TKIP"),
+                   (ciphers.ccmp, "CCMP")];
+    let max = &caps.max_quality;
+    let mut table = Table::new(&[interface, ""]);
+    table.row(vec!["wireless extensions".to_string(),
+                   format!("{} (source {})", caps.we_version, caps.we_version_source)]);
+    table.row(vec!["channels".to_string(),
+                   join(caps.channels.iter().map(|channel| channel.number().to_string()))]);
+    table.row(vec!["bands".to_string(), {
+        let mut bands: Vec<_> = caps.channels.iter().map(|channel| channel.band()).collect();
+        bands.dedup();
+        join(bands.iter().map(|band| band.to_string()))
+    }]);
+    table.row(vec!["bitrates (Mb/s)".to_string(),
+                   join(caps.bitrates.iter().map(|bps| (*bps as f64 / 1e6).to_string()))]);
+    table.row(vec!["tx power".to_string(), join(txpower)]);
+    table.row(vec!["ciphers".to_string(),
+                   join(ciphers.iter().filter(|cipher| cipher.0).map(|cipher| cipher.1.to_string()))]);
+    table.row(vec!["wep key sizes".to_string(),
+                   join(caps.encoding_sizes.iter().map(|size| size.to_string()))]);
+    table.row(vec!["max quality".to_string(),
+                   format!("quality {} level {} noise {}", max.quality(), max.level(), max.noise())]);
+    table.write(out)
+}
+
+fn signal(network: &WirelessNetwork) -> String {
+    or_dash(network.signal_dbm().map(|dbm| format!("{} dBm", dbm)))
+}
+
+fn describe(network: &WirelessNetwork) -> String {
+    format!("{} {} ch {} {}",
+            ssid(network),
+            or_dash(network.bssid),
+            or_dash(network.channel().map(|channel| channel.number())),
+            network.security)
+}
+
+pub fn event_line<W: Write>(out: &mut W, event: &ScanEvent) -> io::Result<()> {
+    match *event {
+        ScanEvent::NetworkAppeared(ref network) => {
+            writeln!(out, "+ {} {}", describe(network), signal(network))
+        }
+        ScanEvent::NetworkLost(ref network) => writeln!(out, "- {}", describe(network)),
+        ScanEvent::SignalChanged { ref network, previous_dbm, dbm } => {
+            writeln!(out, "~ {} {} -> {} dBm", describe(network), previous_dbm, dbm)
+        }
+        ScanEvent::SecurityChanged { ref network, ref previous } => {
+            writeln!(out, "! {} was {}", describe(network), previous)
+        }
+    }
+}
+
+/// One event as a line of JSON, for piping into something else.
+pub fn event_json<W: Write>(out: &mut W, event: &ScanEvent) -> io::Result<()> {
+    let value = match *event {
+        ScanEvent::NetworkAppeared(ref network) => {
+            ::serde_json::json!({ "event": "appeared", "network": network })
+        }
+        ScanEvent::NetworkLost(ref network) => ::serde_json::json!({ "event": "lost", "network": network }),
+        ScanEvent::SignalChanged { ref network, previous_dbm, dbm } => ::serde_json::json!({
+            "event": "signal_changed",
+            "network": network,
+            "previous_dbm": previous_dbm,
+            "dbm": dbm,
+        }),
+        ScanEvent::SecurityChanged { ref network, ref previous } => ::serde_json::json!({
+            "event": "security_changed",
+            "network": network,
+            "previous": previous,
+        }),
+    };
+    serde_json::to_writer(&mut *out, &value)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dradis::{IwQuality, IwStats, MacAddress, Ssid};
+
+    fn network(essid: &[u8]) -> WirelessNetwork {
+        WirelessNetwork {
+            bssid: Some(MacAddress::new([0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc])),
+            stats: Some(IwStats::new(IwQuality::new(0, 201, 0, IwQuality::LEVEL_UPDATED | IwQuality::DBM))),
+            maxbitrate: Some(54000000),
+            freq: Some(2.437e9),
+            essid: Ssid::new(essid),
+            ..WirelessNetwork::default()
+        }
+    }
+
+    fn text<F: FnOnce(&mut Vec<u8>) -> io::Result<()>>(write: F) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn lines_up_the_table() {
+        let table = text(|out| scan_table(out, &[network(b"home"), network(b"")]));
+        assert_eq!(table,
+                   "BSSID              SSID      CH  BAND     SIGNAL   SECURITY  RATE\n\
+                    00:11:22:aa:bb:cc  home      6   2.4 GHz  -55 dBm  Open      54 Mb/s\n\
+                    00:11:22:aa:bb:cc  <hidden>  6   2.4 GHz  -55 dBm  Open      54 Mb/s\n");
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        let csv = text(|out| scan_csv(out, &[network(b"say \"hi\", ok")]));
+        let lines: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(lines[0], CSV_COLUMNS.join(","));
+        assert_eq!(lines[1], "00:11:22:aa:bb:cc,\"say \"\"hi\"\", ok\",6,2437,-55,90,Open,54,");
+    }
+
+    #[test]
+    fn events_as_lines_and_json() {
+        let event = ScanEvent::SignalChanged {
+            network: network(b"home"),
+            previous_dbm: -70,
+            dbm: -55,
+        };
+        assert_eq!(text(|out| event_line(out, &event)),
+                   "~ home 00:11:22:aa:bb:cc ch 6 Open -70 -> -55 dBm\n");
+        let json: serde_json::Value = serde_json::from_str(&text(|out| event_json(out, &event))).unwrap();
+        assert_eq!(json["event"], "signal_changed");
+        assert_eq!(json["network"]["essid"], "home");
+        assert_eq!(json["dbm"], -55);
+    }
+}
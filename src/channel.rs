@@ -0,0 +1,198 @@
+use std::fmt;
+
+/// A frequency band a channel can be in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Band {
+    /// 2.4 GHz, channels 1 to 14.
+    TwoGhz,
+    /// 5 GHz, including the 4.9 GHz public safety channels.
+    FiveGhz,
+    /// 6 GHz, 802.11ax and later.
+    SixGhz,
+    /// 60 GHz, 802.11ad and ay.
+    SixtyGhz,
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Band::TwoGhz => "2.4 GHz",
+            Band::FiveGhz => "5 GHz",
+            Band::SixGhz => "6 GHz",
+            Band::SixtyGhz => "60 GHz",
+        })
+    }
+}
+
+/// A 20 MHz (or 2.16 GHz at 60 GHz) channel: its band, number and center
+/// frequency.
+///
+/// ```
+/// use dradis::{Band, Channel};
+///
+/// let channel = Channel::from_mhz(5180).unwrap();
+/// assert_eq!(channel.band(), Band::FiveGhz);
+/// assert_eq!(channel.number(), 36);
+/// assert_eq!(channel.to_string(), "ch 36");
+/// assert_eq!(Channel::new(Band::SixGhz, 5).unwrap().mhz(), 5975);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Channel {
+    band: Band,
+    number: u16,
+    mhz: u32,
+}
+
+impl Channel {
+    /// The channel `number` in `band`, or `None` if the band has no such
+    /// channel.
+    pub fn new(band: Band, number: u16) -> Option<Channel> {
+        let mhz = match (band, number) {
+            (Band::TwoGhz, 14) => 2484,
+            (Band::TwoGhz, 1..=13) => 2407 + 5 * number as u32,
+            (Band::FiveGhz, 182..=196) => 4000 + 5 * number as u32,
+            (Band::FiveGhz, 1..=177) => 5000 + 5 * number as u32,
+            (Band::SixGhz, 2) => 5935,
+            (Band::SixGhz, 1..=233) => 5950 + 5 * number as u32,
+            (Band::SixtyGhz, 1..=6) => 56160 + 2160 * number as u32,
+            _ => return None,
+        };
+        Some(Channel { band, number, mhz })
+    }
+
+    /// The channel centered on `mhz`, or `None` if no 802.11 channel is.
+    pub fn from_mhz(mhz: u32) -> Option<Channel> {
+        let (band, number) = match mhz {
+            2484 => (Band::TwoGhz, 14),
+            2412..=2472 if (mhz - 2407) % 5 == 0 => (Band::TwoGhz, (mhz - 2407) / 5),
+            4910..=4980 if (mhz - 4000) % 5 == 0 => (Band::FiveGhz, (mhz - 4000) / 5),
+            5005..=5885 if (mhz - 5000) % 5 == 0 => (Band::FiveGhz, (mhz - 5000) / 5),
+            5935 => (Band::SixGhz, 2),
+            5955..=7115 if (mhz - 5950) % 5 == 0 => (Band::SixGhz, (mhz - 5950) / 5),
+            58320..=69120 if (mhz - 56160) % 2160 == 0 => {
+                (Band::SixtyGhz, (mhz - 56160) / 2160)
+            }
+            _ => return None,
+        };
+        Channel::new(band, number as u16)
+    }
+
+    /// The channel centered on `hz`, rounded to the nearest MHz.
+    pub fn from_hz(hz: f64) -> Option<Channel> {
+        if !(hz >= 1e6 && hz < u32::MAX as f64 * 1e6) {
+            return None;
+        }
+        Channel::from_mhz((hz / 1e6).round() as u32)
+    }
+
+    /// A bare channel number, as some drivers report instead of a frequency.
+    /// Without a band it can only be a 2.4 GHz channel if it is 14 or less and
+    /// a 5 GHz one otherwise.
+    pub fn from_number(number: u16) -> Option<Channel> {
+        if number <= 14 {
+            Channel::new(Band::TwoGhz, number)
+        } else {
+            Channel::new(Band::FiveGhz, number)
+        }
+    }
+
+    /// Decode a Wireless Extensions `struct iw_freq`. With an exponent of zero
+    /// and a mantissa under 1000 the driver reported a channel number;
+    /// otherwise the frequency is `m * 10^e` Hz.
+    pub fn from_wext(m: i32, e: i16) -> Option<Channel> {
+        if e == 0 && (0..1000).contains(&m) {
+            return Channel::from_number(m as u16);
+        }
+        if !(0..=9).contains(&e) {
+            return None;
+        }
+        Channel::from_hz(m as f64 * 10f64.powi(e as i32))
+    }
+
+    pub fn band(&self) -> Band {
+        self.band
+    }
+
+    pub fn number(&self) -> u16 {
+        self.number
+    }
+
+    /// The center frequency in MHz.
+    pub fn mhz(&self) -> u32 {
+        self.mhz
+    }
+
+    /// The center frequency in Hz, the unit `WirelessNetwork::freq` uses.
+    pub fn hz(&self) -> f64 {
+        self.mhz as f64 * 1e6
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ch {}", self.number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_channels() {
+        let cases = [(2412, Band::TwoGhz, 1),
+                     (2437, Band::TwoGhz, 6),
+                     (2472, Band::TwoGhz, 13),
+                     (2484, Band::TwoGhz, 14),
+                     (4920, Band::FiveGhz, 184),
+                     (5180, Band::FiveGhz, 36),
+                     (5825, Band::FiveGhz, 165),
+                     (5935, Band::SixGhz, 2),
+                     (5955, Band::SixGhz, 1),
+                     (6115, Band::SixGhz, 33),
+                     (7115, Band::SixGhz, 233),
+                     (58320, Band::SixtyGhz, 1),
+                     (69120, Band::SixtyGhz, 6)];
+        for &(mhz, band, number) in &cases {
+            let channel = Channel::from_mhz(mhz).unwrap();
+            assert_eq!((channel.band(), channel.number()), (band, number), "{}", mhz);
+            assert_eq!(Channel::new(band, number), Some(channel));
+        }
+    }
+
+    #[test]
+    fn rejects_off_grid_frequencies() {
+        for &mhz in &[0, 2411, 2477, 2500, 5001, 5902, 5950, 7120, 58321, 71280] {
+            assert_eq!(Channel::from_mhz(mhz), None, "{}", mhz);
+        }
+        assert_eq!(Channel::new(Band::TwoGhz, 0), None);
+        assert_eq!(Channel::new(Band::TwoGhz, 15), None);
+        assert_eq!(Channel::new(Band::SixtyGhz, 7), None);
+    }
+
+    #[test]
+    fn decodes_wext_frequencies() {
+        assert_eq!(Channel::from_wext(2437, 6).unwrap().number(), 6);
+        assert_eq!(Channel::from_wext(518, 7).unwrap().number(), 36);
+        assert_eq!(Channel::from_wext(241200000, 1).unwrap().number(), 1);
+        assert_eq!(Channel::from_wext(11, 0).unwrap().mhz(), 2462);
+        assert_eq!(Channel::from_wext(149, 0).unwrap().band(), Band::FiveGhz);
+        assert_eq!(Channel::from_wext(2437, -3), None);
+        assert_eq!(Channel::from_wext(i32::MAX, 9), None);
+        assert_eq!(Channel::from_wext(-5, 0), None);
+    }
+
+    #[test]
+    fn hz_round_trips() {
+        let channel = Channel::from_hz(5.18e9).unwrap();
+        assert_eq!(channel.hz(), 5.18e9);
+        assert_eq!(Channel::from_hz(f64::NAN), None);
+        assert_eq!(Channel::from_hz(-1.0), None);
+    }
+
+    #[test]
+    fn bands_display() {
+        assert_eq!(Band::TwoGhz.to_string(), "2.4 GHz");
+        assert_eq!(Channel::from_mhz(2412).unwrap().to_string(), "ch 1");
+    }
+}
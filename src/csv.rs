@@ -0,0 +1,86 @@
+//! Just enough CSV, RFC 4180 style, for the survey formats and anything
+//! else that wants its lines ending the same way.
+
+use std::io::{self, Write};
+
+/// Write one record, quoting the fields that need it, and end it with CRLF.
+pub fn write_record<W: Write>(out: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            out.write_all(field.as_bytes())?;
+        }
+    }
+    out.write_all(b"\r\n")
+}
+
+/// The records in `text`, each with the line it starts on. Quoted fields may
+/// span lines; blank lines are skipped.
+pub(crate) fn records(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(field.split_off(0)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                if !record.is_empty() || !field.is_empty() {
+                    record.push(field.split_off(0));
+                    records.push((start, record.split_off(0)));
+                }
+                line += 1;
+                start = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !record.is_empty() || !field.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_what_needs_quoting() {
+        let mut out = Vec::new();
+        let fields = ["plain", "a,b", "say \"hi\"", "two\nlines", ""];
+        write_record(&mut out, &fields).unwrap();
+        assert_eq!(out, b"plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n".to_vec());
+        let text = String::from_utf8(out).unwrap();
+        let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+        assert_eq!(records(&text), vec![(1, fields)]);
+    }
+
+    #[test]
+    fn counts_lines() {
+        let parsed = records("a,b\n\n\"c\nd\",e\r\nf");
+        let lines: Vec<usize> = parsed.iter().map(|&(line, _)| line).collect();
+        assert_eq!(lines, vec![1, 3, 5]);
+        assert_eq!(parsed[1].1, vec!["c\nd", "e"]);
+        assert_eq!(parsed[2].1, vec!["f"]);
+    }
+}
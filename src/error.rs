@@ -0,0 +1,210 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use libc;
+
+/// Why a scan or query failed.
+///
+/// The variants sort failures by what a caller can do about them; each one
+/// that came from the kernel keeps the errno and names the call that failed,
+/// such as `SIOCSIWSCAN` or `NL80211_CMD_TRIGGER_SCAN`.
+///
+/// ```no_run
+/// use dradis::{Error, WifiScan};
+///
+/// match WifiScan::scan("wlan0".to_string()) {
+///     Ok(scan) => println!("{} networks", scan.networks.len()),
+///     Err(Error::PermissionDenied { .. }) => println!("run me with CAP_NET_ADMIN"),
+///     Err(Error::Busy { .. }) => println!("try again in a moment"),
+///     Err(err) => println!("scan failed: {}", err),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Error {
+    /// EPERM or EACCES: scanning needs CAP_NET_ADMIN.
+    PermissionDenied { call: &'static str, errno: Option<i32> },
+    /// ENODEV or ENXIO: there is no interface by that name, or no wireless
+    /// interface at all.
+    NoSuchInterface { call: &'static str, errno: Option<i32> },
+    /// EOPNOTSUPP: the interface exists but isn't wireless.
+    NotWireless { call: &'static str, errno: Option<i32> },
+    /// EBUSY or EAGAIN: the driver is busy, usually with another scan.
+    Busy { call: &'static str, errno: Option<i32> },
+    /// ENETDOWN: the interface has to be brought up first.
+    InterfaceDown { call: &'static str, errno: Option<i32> },
+    /// The scan didn't finish in time.
+    Timeout { call: &'static str, errno: Option<i32> },
+    /// The kernel or a file said something that couldn't be decoded.
+    Parse { call: &'static str, message: String },
+    /// The backend can't do what was asked of it.
+    Unsupported { call: &'static str, message: &'static str },
+    /// The caller asked for something that makes no sense.
+    InvalidInput { call: &'static str, message: &'static str },
+    /// The scan was cancelled.
+    Cancelled,
+    /// Anything else the backend ran into.
+    Backend {
+        call: &'static str,
+        errno: Option<i32>,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Classify the errno `call` failed with.
+    pub fn from_errno(call: &'static str, code: i32) -> Error {
+        let errno = Some(code);
+        match code {
+            libc::EPERM | libc::EACCES => Error::PermissionDenied { call, errno },
+            libc::ENODEV | libc::ENXIO => Error::NoSuchInterface { call, errno },
+            libc::EOPNOTSUPP => Error::NotWireless { call, errno },
+            libc::EBUSY | libc::EAGAIN => Error::Busy { call, errno },
+            libc::ENETDOWN => Error::InterfaceDown { call, errno },
+            libc::ETIMEDOUT => Error::Timeout { call, errno },
+            _ => {
+                Error::Backend {
+                    call,
+                    errno,
+                    message: io::Error::from_raw_os_error(code).to_string(),
+                }
+            }
+        }
+    }
+
+    /// Classify the errno the last system call, `call`, left behind.
+    pub(crate) fn last_os_error(call: &'static str) -> Error {
+        Error::from_io(call, io::Error::last_os_error())
+    }
+
+    /// Classify an I/O error from `call`, by its errno if it has one.
+    pub(crate) fn from_io(call: &'static str, err: io::Error) -> Error {
+        match err.raw_os_error() {
+            Some(errno) => Error::from_errno(call, errno),
+            None if err.kind() == io::ErrorKind::TimedOut => Error::Timeout { call, errno: None },
+            None => {
+                Error::Backend {
+                    call,
+                    errno: None,
+                    message: err.to_string(),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn parse<S: Into<String>>(call: &'static str, message: S) -> Error {
+        Error::Parse {
+            call,
+            message: message.into(),
+        }
+    }
+
+    /// The errno behind the error, if it came from the kernel.
+    pub fn errno(&self) -> Option<i32> {
+        match *self {
+            Error::PermissionDenied { errno, .. } |
+            Error::NoSuchInterface { errno, .. } |
+            Error::NotWireless { errno, .. } |
+            Error::Busy { errno, .. } |
+            Error::InterfaceDown { errno, .. } |
+            Error::Timeout { errno, .. } |
+            Error::Backend { errno, .. } => errno,
+            _ => None,
+        }
+    }
+
+    /// The call or request that failed.
+    pub fn call(&self) -> Option<&'static str> {
+        match *self {
+            Error::PermissionDenied { call, .. } |
+            Error::NoSuchInterface { call, .. } |
+            Error::NotWireless { call, .. } |
+            Error::Busy { call, .. } |
+            Error::InterfaceDown { call, .. } |
+            Error::Timeout { call, .. } |
+            Error::Parse { call, .. } |
+            Error::Unsupported { call, .. } |
+            Error::InvalidInput { call, .. } |
+            Error::Backend { call, .. } => Some(call),
+            Error::Cancelled => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::PermissionDenied { .. } => f.write_str("permission denied")?,
+            Error::NoSuchInterface { .. } => f.write_str("no such wireless interface")?,
+            Error::NotWireless { .. } => f.write_str("not a wireless interface")?,
+            Error::Busy { .. } => f.write_str("device busy")?,
+            Error::InterfaceDown { .. } => f.write_str("interface is down")?,
+            Error::Timeout { .. } => f.write_str("timed out")?,
+            Error::Parse { ref message, .. } | Error::Backend { ref message, .. } => {
+                f.write_str(message)?
+            }
+            Error::Unsupported { message, .. } |
+            Error::InvalidInput { message, .. } => f.write_str(message)?,
+            Error::Cancelled => return f.write_str("scan cancelled"),
+        }
+        if let Some(call) = self.call() {
+            write!(f, " in {}", call)?;
+        }
+        if let Some(errno) = self.errno() {
+            write!(f, " (errno {})", errno)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        let kind = match err {
+            Error::PermissionDenied { .. } => io::ErrorKind::PermissionDenied,
+            Error::NoSuchInterface { .. } => io::ErrorKind::NotFound,
+            Error::Busy { .. } => io::ErrorKind::WouldBlock,
+            Error::Timeout { .. } => io::ErrorKind::TimedOut,
+            Error::Parse { .. } => io::ErrorKind::InvalidData,
+            Error::Unsupported { .. } | Error::NotWireless { .. } => io::ErrorKind::Unsupported,
+            Error::InvalidInput { .. } => io::ErrorKind::InvalidInput,
+            Error::Cancelled => io::ErrorKind::Interrupted,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_errnos() {
+        let classify = |errno| Error::from_errno("SIOCSIWSCAN", errno);
+        assert!(matches!(classify(libc::EPERM), Error::PermissionDenied { .. }));
+        assert!(matches!(classify(libc::EACCES), Error::PermissionDenied { .. }));
+        assert!(matches!(classify(libc::ENODEV), Error::NoSuchInterface { .. }));
+        assert!(matches!(classify(libc::EOPNOTSUPP), Error::NotWireless { .. }));
+        assert!(matches!(classify(libc::EBUSY), Error::Busy { .. }));
+        assert!(matches!(classify(libc::EAGAIN), Error::Busy { .. }));
+        assert!(matches!(classify(libc::ENETDOWN), Error::InterfaceDown { .. }));
+        assert!(matches!(classify(libc::ETIMEDOUT), Error::Timeout { .. }));
+        let other = classify(libc::EIO);
+        assert!(matches!(other, Error::Backend { .. }));
+        assert_eq!((other.call(), other.errno()), (Some("SIOCSIWSCAN"), Some(libc::EIO)));
+    }
+
+    #[test]
+    fn keeps_errno_through_io_errors() {
+        let err = Error::from_io("socket", io::Error::from_raw_os_error(libc::EPERM));
+        assert_eq!(err.errno(), Some(libc::EPERM));
+        assert_eq!(err.to_string(), format!("permission denied in socket (errno {})", libc::EPERM));
+        let timeout = Error::from_io("recv", io::Error::new(io::ErrorKind::TimedOut, "slow"));
+        assert!(matches!(timeout, Error::Timeout { errno: None, .. }));
+        assert_eq!(io::Error::from(timeout).kind(), io::ErrorKind::TimedOut);
+        assert_eq!(Error::Cancelled.to_string(), "scan cancelled");
+    }
+}
@@ -0,0 +1,955 @@
+//! Parsing for 802.11 information elements, the tagged fields that make up the
+//! body of beacons and probe responses.
+//!
+//! Every element is a one byte ID, a one byte length and that many bytes of
+//! contents. `Elements` walks a run of them and decodes the ones it knows
+//! about; anything else, including known elements too short to decode, comes
+//! back as `Element::Unknown` with its bytes untouched. Parsing never panics, no
+//! matter how the input is cut off: a truncated final element just ends the
+//! walk.
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const SSID: u8 = 0;
+const SUPPORTED_RATES: u8 = 1;
+const DS_PARAMETER: u8 = 3;
+const TIM: u8 = 5;
+const COUNTRY: u8 = 7;
+const HT_CAPABILITIES: u8 = 45;
+const RSN: u8 = 48;
+const EXTENDED_SUPPORTED_RATES: u8 = 50;
+const HT_OPERATION: u8 = 61;
+const EXTENDED_CAPABILITIES: u8 = 127;
+const VHT_CAPABILITIES: u8 = 191;
+const VHT_OPERATION: u8 = 192;
+const VENDOR_SPECIFIC: u8 = 221;
+const EXTENSION: u8 = 255;
+
+const EXT_HE_CAPABILITIES: u8 = 35;
+const EXT_HE_OPERATION: u8 = 36;
+
+/// The OUI that the 802.11 standard's own cipher and AKM suites live under.
+pub const IEEE_OUI: [u8; 3] = [0x00, 0x0f, 0xac];
+
+/// A single decoded information element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    /// The raw SSID, up to 32 bytes, not necessarily UTF-8.
+    Ssid(Vec<u8>),
+    SupportedRates(Vec<Rate>),
+    ExtendedSupportedRates(Vec<Rate>),
+    /// The channel the network is on, from the DS Parameter Set.
+    DsParameter(u8),
+    Tim(Tim),
+    Country(Country),
+    Rsn(Rsn),
+    HtCapabilities(HtCapabilities),
+    HtOperation(HtOperation),
+    VhtCapabilities(VhtCapabilities),
+    VhtOperation(VhtOperation),
+    HeCapabilities(HeCapabilities),
+    HeOperation(HeOperation),
+    ExtendedCapabilities(ExtendedCapabilities),
+    /// A vendor specific element. `data` is everything after the OUI, starting
+    /// with the vendor's type byte.
+    VendorSpecific {
+        oui: [u8; 3],
+        data: Vec<u8>,
+    },
+    /// Any element this module doesn't decode. For element ID extension
+    /// elements (ID 255) `data` starts with the extension ID.
+    Unknown {
+        id: u8,
+        data: Vec<u8>,
+    },
+}
+
+/// A rate from the Supported Rates or Extended Supported Rates element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    /// Stations must support basic rates to join the network.
+    pub basic: bool,
+    pub kbps: u32,
+}
+
+/// The Traffic Indication Map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tim {
+    pub dtim_count: u8,
+    pub dtim_period: u8,
+    pub bitmap_control: u8,
+    pub partial_virtual_bitmap: Vec<u8>,
+}
+
+/// The regulatory domain the access point says it is operating in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Country {
+    /// ISO 3166-1 alpha-2 code, e.g. `*b"US"`.
+    pub code: [u8; 2],
+    /// b' ' for any environment, b'O' for outdoor, b'I' for indoor.
+    pub environment: u8,
+    pub triplets: Vec<CountryTriplet>,
+}
+
+/// A subband from the Country element. When `first_channel` is 201 or more
+/// this is an operating extension triplet instead and the fields are the
+/// operating extension ID, operating class and coverage class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountryTriplet {
+    pub first_channel: u8,
+    pub num_channels: u8,
+    pub max_tx_power_dbm: i8,
+}
+
+/// A cipher suite selector from an RSN or WPA element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CipherSuite {
+    /// Pairwise traffic uses the group cipher.
+    UseGroup,
+    Wep40,
+    Tkip,
+    Ccmp128,
+    Wep104,
+    BipCmac128,
+    /// Group addressed traffic is not allowed.
+    GroupNotAllowed,
+    Gcmp128,
+    Gcmp256,
+    Ccmp256,
+    BipGmac128,
+    BipGmac256,
+    BipCmac256,
+    /// A vendor suite or one this crate doesn't know, as OUI and type.
+    Other([u8; 3], u8),
+}
+
+impl CipherSuite {
+    /// Decode a suite selector under the given standard OUI, which is
+    /// `IEEE_OUI` for RSN and 00:50:F2 for WPA.
+    pub fn from_selector(oui: [u8; 3], kind: u8, standard: [u8; 3]) -> CipherSuite {
+        if oui != standard {
+            return CipherSuite::Other(oui, kind);
+        }
+        match kind {
+            0 => CipherSuite::UseGroup,
+            1 => CipherSuite::Wep40,
+            2 => CipherSuite::Tkip,
+            4 => CipherSuite::Ccmp128,
+            5 => CipherSuite::Wep104,
+            6 => CipherSuite::BipCmac128,
+            7 => CipherSuite::GroupNotAllowed,
+            8 => CipherSuite::Gcmp128,
+            9 => CipherSuite::Gcmp256,
+            10 => CipherSuite::Ccmp256,
+            11 => CipherSuite::BipGmac128,
+            12 => CipherSuite::BipGmac256,
+            13 => CipherSuite::BipCmac256,
+            _ => CipherSuite::Other(oui, kind),
+        }
+    }
+}
+
+/// An authentication and key management suite selector from an RSN or WPA
+/// element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AkmSuite {
+    Ieee8021x,
+    Psk,
+    FtIeee8021x,
+    FtPsk,
+    Ieee8021xSha256,
+    PskSha256,
+    Tdls,
+    Sae,
+    FtSae,
+    ApPeerKey,
+    SuiteB,
+    SuiteB192,
+    FtIeee8021xSha384,
+    FilsSha256,
+    FilsSha384,
+    FtFilsSha256,
+    FtFilsSha384,
+    Owe,
+    FtPskSha384,
+    PskSha384,
+    SaeExt,
+    FtSaeExt,
+    /// A vendor suite or one this crate doesn't know, as OUI and type.
+    Other([u8; 3], u8),
+}
+
+impl AkmSuite {
+    /// Decode a suite selector under the given standard OUI, which is
+    /// `IEEE_OUI` for RSN and 00:50:F2 for WPA.
+    pub fn from_selector(oui: [u8; 3], kind: u8, standard: [u8; 3]) -> AkmSuite {
+        if oui != standard {
+            return AkmSuite::Other(oui, kind);
+        }
+        match kind {
+            1 => AkmSuite::Ieee8021x,
+            2 => AkmSuite::Psk,
+            3 => AkmSuite::FtIeee8021x,
+            4 => AkmSuite::FtPsk,
+            5 => AkmSuite::Ieee8021xSha256,
+            6 => AkmSuite::PskSha256,
+            7 => AkmSuite::Tdls,
+            8 => AkmSuite::Sae,
+            9 => AkmSuite::FtSae,
+            10 => AkmSuite::ApPeerKey,
+            11 => AkmSuite::SuiteB,
+            12 => AkmSuite::SuiteB192,
+            13 => AkmSuite::FtIeee8021xSha384,
+            14 => AkmSuite::FilsSha256,
+            15 => AkmSuite::FilsSha384,
+            16 => AkmSuite::FtFilsSha256,
+            17 => AkmSuite::FtFilsSha384,
+            18 => AkmSuite::Owe,
+            19 => AkmSuite::FtPskSha384,
+            20 => AkmSuite::PskSha384,
+            24 => AkmSuite::SaeExt,
+            25 => AkmSuite::FtSaeExt,
+            _ => AkmSuite::Other(oui, kind),
+        }
+    }
+}
+
+/// The Robust Security Network element. Every field after the version is
+/// optional on the air; missing ones are `None` or empty here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rsn {
+    pub version: u16,
+    pub group_cipher: Option<CipherSuite>,
+    pub pairwise_ciphers: Vec<CipherSuite>,
+    pub akm_suites: Vec<AkmSuite>,
+    pub capabilities: Option<u16>,
+    pub pmkids: Vec<[u8; 16]>,
+    pub group_management_cipher: Option<CipherSuite>,
+}
+
+impl Rsn {
+    /// Management frame protection is required.
+    pub const CAPABILITY_MFP_REQUIRED: u16 = 1 << 6;
+    /// Management frame protection is supported.
+    pub const CAPABILITY_MFP_CAPABLE: u16 = 1 << 7;
+
+    /// Parse the body of an RSN element, or the body of a WPA vendor element
+    /// after its OUI and type, with suites under the `standard` OUI.
+    pub fn parse(body: &[u8], standard: [u8; 3]) -> Option<Rsn> {
+        let mut reader = Reader::new(body);
+        let mut rsn = Rsn {
+            version: reader.u16()?,
+            group_cipher: None,
+            pairwise_ciphers: Vec::new(),
+            akm_suites: Vec::new(),
+            capabilities: None,
+            pmkids: Vec::new(),
+            group_management_cipher: None,
+        };
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        let (oui, kind) = reader.suite()?;
+        rsn.group_cipher = Some(CipherSuite::from_selector(oui, kind, standard));
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        for _ in 0..reader.u16()? {
+            let (oui, kind) = reader.suite()?;
+            rsn.pairwise_ciphers.push(CipherSuite::from_selector(oui, kind, standard));
+        }
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        for _ in 0..reader.u16()? {
+            let (oui, kind) = reader.suite()?;
+            rsn.akm_suites.push(AkmSuite::from_selector(oui, kind, standard));
+        }
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        rsn.capabilities = Some(reader.u16()?);
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        for _ in 0..reader.u16()? {
+            let mut pmkid = [0u8; 16];
+            pmkid.copy_from_slice(reader.take(16)?);
+            rsn.pmkids.push(pmkid);
+        }
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        let (oui, kind) = reader.suite()?;
+        rsn.group_management_cipher = Some(CipherSuite::from_selector(oui, kind, standard));
+        Some(rsn)
+    }
+
+    pub fn mfp_required(&self) -> bool {
+        self.capabilities.is_some_and(|caps| caps & Rsn::CAPABILITY_MFP_REQUIRED != 0)
+    }
+
+    pub fn mfp_capable(&self) -> bool {
+        self.capabilities.is_some_and(|caps| caps & Rsn::CAPABILITY_MFP_CAPABLE != 0)
+    }
+}
+
+/// The HT (802.11n) Capabilities element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtCapabilities {
+    pub info: u16,
+    pub ampdu_parameters: u8,
+    pub mcs_set: [u8; 16],
+    pub extended_capabilities: u16,
+    pub transmit_beamforming: u32,
+    pub antenna_selection: u8,
+}
+
+impl HtCapabilities {
+    pub fn supports_40mhz(&self) -> bool {
+        self.info & (1 << 1) != 0
+    }
+
+    pub fn short_gi_20mhz(&self) -> bool {
+        self.info & (1 << 5) != 0
+    }
+
+    pub fn short_gi_40mhz(&self) -> bool {
+        self.info & (1 << 6) != 0
+    }
+
+    /// The number of spatial streams the station can receive, from how many of
+    /// the first four bytes of the MCS bitmask are in use.
+    pub fn rx_spatial_streams(&self) -> u8 {
+        self.mcs_set[..4].iter().filter(|&&mcs| mcs != 0).count() as u8
+    }
+}
+
+/// The HT Operation element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtOperation {
+    pub primary_channel: u8,
+    pub info: [u8; 5],
+    pub basic_mcs_set: [u8; 16],
+}
+
+impl HtOperation {
+    /// Where the secondary 20 MHz channel sits: 1 above the primary, -1 below
+    /// it, or 0 if there is none.
+    pub fn secondary_channel_offset(&self) -> i8 {
+        match self.info[0] & 0x03 {
+            1 => 1,
+            3 => -1,
+            _ => 0,
+        }
+    }
+
+    /// The network allows 40 MHz wide channels.
+    pub fn any_channel_width(&self) -> bool {
+        self.info[0] & 0x04 != 0
+    }
+}
+
+/// The VHT (802.11ac) Capabilities element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VhtCapabilities {
+    pub info: u32,
+    pub rx_mcs_map: u16,
+    pub rx_highest_rate: u16,
+    pub tx_mcs_map: u16,
+    pub tx_highest_rate: u16,
+}
+
+impl VhtCapabilities {
+    /// The supported channel width set field: 0 for 80 MHz only, 1 for 160 MHz,
+    /// 2 for 160 MHz and 80+80 MHz.
+    pub fn supported_channel_width(&self) -> u8 {
+        ((self.info >> 2) & 0x03) as u8
+    }
+
+    /// The number of spatial streams the station can receive.
+    pub fn rx_spatial_streams(&self) -> u8 {
+        mcs_map_streams(self.rx_mcs_map)
+    }
+}
+
+/// The VHT Operation element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VhtOperation {
+    /// 0 for 20 or 40 MHz, 1 for 80, 160 or 80+80 MHz; 2 and 3 are the
+    /// deprecated 160 and 80+80 MHz values.
+    pub channel_width: u8,
+    pub center_freq_seg0: u8,
+    pub center_freq_seg1: u8,
+    pub basic_mcs_map: u16,
+}
+
+/// The HE (802.11ax) Capabilities element. The variable length MCS and NSS
+/// sets and PPE thresholds that follow the fixed fields are left raw.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeCapabilities {
+    pub mac_capabilities: [u8; 6],
+    pub phy_capabilities: [u8; 11],
+    pub mcs_nss_and_ppe: Vec<u8>,
+}
+
+impl HeCapabilities {
+    /// The number of spatial streams the station can receive at 80 MHz or less.
+    pub fn rx_spatial_streams(&self) -> u8 {
+        if self.mcs_nss_and_ppe.len() < 2 {
+            return 0;
+        }
+        mcs_map_streams(u16::from_le_bytes([self.mcs_nss_and_ppe[0], self.mcs_nss_and_ppe[1]]))
+    }
+}
+
+/// The HE Operation element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeOperation {
+    /// The 24 bit HE Operation Parameters field.
+    pub parameters: u32,
+    pub bss_color_info: u8,
+    pub basic_mcs_nss: u16,
+    /// Present when the network runs in the 6 GHz band.
+    pub six_ghz: Option<HeSixGhzOperation>,
+}
+
+impl HeOperation {
+    const VHT_OPERATION_PRESENT: u32 = 1 << 14;
+    const CO_HOSTED_BSS: u32 = 1 << 15;
+    const SIX_GHZ_OPERATION_PRESENT: u32 = 1 << 17;
+
+    pub fn bss_color(&self) -> u8 {
+        self.bss_color_info & 0x3f
+    }
+}
+
+/// The 6 GHz Operation Information inside an HE Operation element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeSixGhzOperation {
+    pub primary_channel: u8,
+    pub control: u8,
+    pub center_freq_seg0: u8,
+    pub center_freq_seg1: u8,
+    pub minimum_rate: u8,
+}
+
+impl HeSixGhzOperation {
+    /// 0 for 20 MHz, 1 for 40, 2 for 80 and 3 for 160 or 80+80 MHz.
+    pub fn channel_width(&self) -> u8 {
+        self.control & 0x03
+    }
+}
+
+/// The Extended Capabilities element, a little endian bit field.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtendedCapabilities(pub Vec<u8>);
+
+impl ExtendedCapabilities {
+    pub const BSS_TRANSITION: usize = 19;
+    pub const INTERWORKING: usize = 31;
+    pub const UTF8_SSID: usize = 48;
+
+    /// Whether capability bit `bit` is set. Bits past the end of the element
+    /// are unset.
+    pub fn has(&self, bit: usize) -> bool {
+        self.0.get(bit / 8).is_some_and(|byte| byte & (1 << (bit % 8)) != 0)
+    }
+}
+
+impl fmt::Debug for ExtendedCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExtendedCapabilities(")?;
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Walks a run of information elements, decoding each one.
+#[derive(Debug, Clone)]
+pub struct Elements<'a> {
+    raw: RawElements<'a>,
+}
+
+impl<'a> Elements<'a> {
+    pub fn new(buf: &'a [u8]) -> Elements<'a> {
+        Elements { raw: RawElements::new(buf) }
+    }
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = Element;
+
+    fn next(&mut self) -> Option<Element> {
+        self.raw.next().map(|(id, data)| Element::parse(id, data))
+    }
+}
+
+/// Parse every element in `buf`.
+pub fn parse(buf: &[u8]) -> Vec<Element> {
+    Elements::new(buf).collect()
+}
+
+impl Element {
+    /// Decode one element from its ID and contents.
+    pub fn parse(id: u8, data: &[u8]) -> Element {
+        Element::decode(id, data).unwrap_or_else(|| {
+            Element::Unknown {
+                id,
+                data: data.to_vec(),
+            }
+        })
+    }
+
+    fn decode(id: u8, data: &[u8]) -> Option<Element> {
+        let mut reader = Reader::new(data);
+        let element = match id {
+            SSID => Element::Ssid(data.to_vec()),
+            SUPPORTED_RATES => Element::SupportedRates(rates(data)),
+            EXTENDED_SUPPORTED_RATES => Element::ExtendedSupportedRates(rates(data)),
+            DS_PARAMETER => Element::DsParameter(reader.u8()?),
+            TIM => {
+                Element::Tim(Tim {
+                    dtim_count: reader.u8()?,
+                    dtim_period: reader.u8()?,
+                    bitmap_control: reader.u8()?,
+                    partial_virtual_bitmap: reader.rest().to_vec(),
+                })
+            }
+            COUNTRY => {
+                let code = reader.take(2)?;
+                let environment = reader.u8()?;
+                let triplets = reader.rest()
+                    .chunks(3)
+                    .filter(|triplet| triplet.len() == 3)
+                    .map(|triplet| {
+                        CountryTriplet {
+                            first_channel: triplet[0],
+                            num_channels: triplet[1],
+                            max_tx_power_dbm: triplet[2] as i8,
+                        }
+                    })
+                    .collect();
+                Element::Country(Country {
+                    code: [code[0], code[1]],
+                    environment,
+                    triplets,
+                })
+            }
+            RSN => Element::Rsn(Rsn::parse(data, IEEE_OUI)?),
+            HT_CAPABILITIES => {
+                let info = reader.u16()?;
+                let ampdu_parameters = reader.u8()?;
+                let mut mcs_set = [0u8; 16];
+                mcs_set.copy_from_slice(reader.take(16)?);
+                Element::HtCapabilities(HtCapabilities {
+                    info,
+                    ampdu_parameters,
+                    mcs_set,
+                    extended_capabilities: reader.u16()?,
+                    transmit_beamforming: reader.u32()?,
+                    antenna_selection: reader.u8()?,
+                })
+            }
+            HT_OPERATION => {
+                let primary_channel = reader.u8()?;
+                let mut info = [0u8; 5];
+                info.copy_from_slice(reader.take(5)?);
+                let mut basic_mcs_set = [0u8; 16];
+                basic_mcs_set.copy_from_slice(reader.take(16)?);
+                Element::HtOperation(HtOperation {
+                    primary_channel,
+                    info,
+                    basic_mcs_set,
+                })
+            }
+            EXTENDED_CAPABILITIES => Element::ExtendedCapabilities(ExtendedCapabilities(data.to_vec())),
+            VHT_CAPABILITIES => {
+                Element::VhtCapabilities(VhtCapabilities {
+                    info: reader.u32()?,
+                    rx_mcs_map: reader.u16()?,
+                    rx_highest_rate: reader.u16()?,
+                    tx_mcs_map: reader.u16()?,
+                    tx_highest_rate: reader.u16()?,
+                })
+            }
+            VHT_OPERATION => {
+                Element::VhtOperation(VhtOperation {
+                    channel_width: reader.u8()?,
+                    center_freq_seg0: reader.u8()?,
+                    center_freq_seg1: reader.u8()?,
+                    basic_mcs_map: reader.u16()?,
+                })
+            }
+            VENDOR_SPECIFIC => {
+                let oui = reader.take(3)?;
+                Element::VendorSpecific {
+                    oui: [oui[0], oui[1], oui[2]],
+                    data: reader.rest().to_vec(),
+                }
+            }
+            EXTENSION => {
+                match reader.u8()? {
+                    EXT_HE_CAPABILITIES => {
+                        let mut mac_capabilities = [0u8; 6];
+                        mac_capabilities.copy_from_slice(reader.take(6)?);
+                        let mut phy_capabilities = [0u8; 11];
+                        phy_capabilities.copy_from_slice(reader.take(11)?);
+                        Element::HeCapabilities(HeCapabilities {
+                            mac_capabilities,
+                            phy_capabilities,
+                            mcs_nss_and_ppe: reader.rest().to_vec(),
+                        })
+                    }
+                    EXT_HE_OPERATION => Element::HeOperation(he_operation(&mut reader)?),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(element)
+    }
+}
+
+fn he_operation(reader: &mut Reader) -> Option<HeOperation> {
+    let params = reader.take(3)?;
+    let parameters = u32::from_le_bytes([params[0], params[1], params[2], 0]);
+    let bss_color_info = reader.u8()?;
+    let basic_mcs_nss = reader.u16()?;
+    if parameters & HeOperation::VHT_OPERATION_PRESENT != 0 {
+        reader.take(3)?;
+    }
+    if parameters & HeOperation::CO_HOSTED_BSS != 0 {
+        reader.take(1)?;
+    }
+    let six_ghz = if parameters & HeOperation::SIX_GHZ_OPERATION_PRESENT != 0 {
+        Some(HeSixGhzOperation {
+            primary_channel: reader.u8()?,
+            control: reader.u8()?,
+            center_freq_seg0: reader.u8()?,
+            center_freq_seg1: reader.u8()?,
+            minimum_rate: reader.u8()?,
+        })
+    } else {
+        None
+    };
+    Some(HeOperation {
+        parameters,
+        bss_color_info,
+        basic_mcs_nss,
+        six_ghz,
+    })
+}
+
+fn rates(data: &[u8]) -> Vec<Rate> {
+    data.iter()
+        .map(|&rate| {
+            Rate {
+                basic: rate & 0x80 != 0,
+                kbps: (rate & 0x7f) as u32 * 500,
+            }
+        })
+        .collect()
+}
+
+/// Counts the spatial streams in a VHT or HE MCS map, where each stream gets
+/// two bits and 3 means unsupported.
+fn mcs_map_streams(map: u16) -> u8 {
+    (0..8).filter(|nss| (map >> (nss * 2)) & 0x03 != 0x03).count() as u8
+}
+
+/// Walks a run of information elements without decoding them, yielding each
+/// element ID and its contents.
+#[derive(Debug, Clone)]
+pub struct RawElements<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> RawElements<'a> {
+    pub fn new(buf: &'a [u8]) -> RawElements<'a> {
+        RawElements { buf }
+    }
+}
+
+impl<'a> Iterator for RawElements<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        if self.buf.len() < 2 || self.buf.len() < 2 + self.buf[1] as usize {
+            self.buf = &[];
+            return None;
+        }
+        let (element, rest) = self.buf.split_at(2 + self.buf[1] as usize);
+        self.buf = rest;
+        Some((element[0], &element[2..]))
+    }
+}
+
+/// Bounds checked little endian reads from an element body.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Reader<'a> {
+        Reader { buf }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(taken)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = self.buf;
+        self.buf = &[];
+        rest
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn suite(&mut self) -> Option<([u8; 3], u8)> {
+        self.take(4).map(|b| ([b[0], b[1], b[2]], b[3]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The elements from a WPA2/WPA3 transition mode beacon on channel 36.
+    const BEACON: &[u8] = &[
+        0x00, 0x06, b'd', b'r', b'a', b'd', b'i', b's',
+        0x01, 0x08, 0x8c, 0x12, 0x98, 0x24, 0xb0, 0x48, 0x60, 0x6c,
+        0x03, 0x01, 0x24,
+        0x05, 0x04, 0x00, 0x03, 0x00, 0x00,
+        0x07, 0x06, b'U', b'S', b' ', 0x24, 0x04, 0x17,
+        0x30, 0x18, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04,
+        0x02, 0x00, 0x00, 0x0f, 0xac, 0x02, 0x00, 0x0f, 0xac, 0x08, 0x80, 0x00,
+        0x2d, 0x1a, 0xef, 0x01, 0x1b, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x3d, 0x16, 0x24, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x7f, 0x08, 0x04, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0x40,
+        0xbf, 0x0c, 0xb2, 0x79, 0x91, 0x33, 0xfa, 0xff, 0x0c, 0x03, 0xfa, 0xff, 0x0c, 0x03,
+        0xc0, 0x05, 0x01, 0x2a, 0x00, 0xfc, 0xff,
+        0xff, 0x1a, 0x23, 0x01, 0x78, 0x10, 0x1a, 0x00, 0x00, 0x40, 0x20, 0x0e, 0x09, 0x80,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfa, 0xff, 0xfa, 0xff, 0x61, 0x1c, 0xc7, 0x71,
+        0xff, 0x07, 0x24, 0x04, 0x00, 0x00, 0x05, 0xfc, 0xff,
+        0xff, 0x02, 0x25, 0x01,
+        0xdd, 0x18, 0x00, 0x50, 0xf2, 0x02, 0x01, 0x01, 0x80, 0x00, 0x03, 0xa4, 0x00, 0x00,
+        0x27, 0xa4, 0x00, 0x00, 0x42, 0x43, 0x5e, 0x00, 0x62, 0x32, 0x2f, 0x00,
+        0x46, 0x05, 0x72, 0x08, 0x01, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn parses_beacon() {
+        let elements = parse(BEACON);
+        assert_eq!(elements.len(), 16);
+        assert_eq!(elements[0], Element::Ssid(b"dradis".to_vec()));
+        match elements[1] {
+            Element::SupportedRates(ref rates) => {
+                assert_eq!(rates[0],
+                           Rate {
+                               basic: true,
+                               kbps: 6000,
+                           });
+                assert_eq!(rates[7],
+                           Rate {
+                               basic: false,
+                               kbps: 54000,
+                           });
+            }
+            ref other => panic!("{:?}", other),
+        }
+        assert_eq!(elements[2], Element::DsParameter(36));
+        assert_eq!(elements[3],
+                   Element::Tim(Tim {
+                       dtim_count: 0,
+                       dtim_period: 3,
+                       bitmap_control: 0,
+                       partial_virtual_bitmap: vec![0],
+                   }));
+        assert_eq!(elements[4],
+                   Element::Country(Country {
+                       code: *b"US",
+                       environment: b' ',
+                       triplets: vec![CountryTriplet {
+                                          first_channel: 36,
+                                          num_channels: 4,
+                                          max_tx_power_dbm: 23,
+                                      }],
+                   }));
+        assert_eq!(elements[5],
+                   Element::Rsn(Rsn {
+                       version: 1,
+                       group_cipher: Some(CipherSuite::Ccmp128),
+                       pairwise_ciphers: vec![CipherSuite::Ccmp128],
+                       akm_suites: vec![AkmSuite::Psk, AkmSuite::Sae],
+                       capabilities: Some(0x0080),
+                       pmkids: vec![],
+                       group_management_cipher: None,
+                   }));
+        match elements[6] {
+            Element::HtCapabilities(ht) => {
+                assert!(ht.supports_40mhz() && ht.short_gi_20mhz() && ht.short_gi_40mhz());
+                assert_eq!(ht.rx_spatial_streams(), 2);
+            }
+            ref other => panic!("{:?}", other),
+        }
+        match elements[7] {
+            Element::HtOperation(op) => {
+                assert_eq!(op.primary_channel, 36);
+                assert_eq!(op.secondary_channel_offset(), 1);
+                assert!(op.any_channel_width());
+            }
+            ref other => panic!("{:?}", other),
+        }
+        match elements[8] {
+            Element::ExtendedCapabilities(ref caps) => {
+                assert!(caps.has(ExtendedCapabilities::BSS_TRANSITION));
+                assert!(caps.has(ExtendedCapabilities::UTF8_SSID));
+                assert!(!caps.has(ExtendedCapabilities::INTERWORKING));
+                assert!(!caps.has(1000));
+            }
+            ref other => panic!("{:?}", other),
+        }
+        match elements[9] {
+            Element::VhtCapabilities(vht) => assert_eq!(vht.rx_spatial_streams(), 2),
+            ref other => panic!("{:?}", other),
+        }
+        assert_eq!(elements[10],
+                   Element::VhtOperation(VhtOperation {
+                       channel_width: 1,
+                       center_freq_seg0: 42,
+                       center_freq_seg1: 0,
+                       basic_mcs_map: 0xfffc,
+                   }));
+        match elements[11] {
+            Element::HeCapabilities(ref he) => assert_eq!(he.rx_spatial_streams(), 2),
+            ref other => panic!("{:?}", other),
+        }
+        match elements[12] {
+            Element::HeOperation(ref he) => {
+                assert_eq!(he.bss_color(), 5);
+                assert_eq!(he.six_ghz, None);
+            }
+            ref other => panic!("{:?}", other),
+        }
+        assert_eq!(elements[13],
+                   Element::Unknown {
+                       id: EXTENSION,
+                       data: vec![0x25, 0x01],
+                   });
+        match elements[14] {
+            Element::VendorSpecific { oui, ref data } => {
+                assert_eq!(oui, [0x00, 0x50, 0xf2]);
+                assert_eq!(data[0], 0x02);
+            }
+            ref other => panic!("{:?}", other),
+        }
+        assert_eq!(elements[15],
+                   Element::Unknown {
+                       id: 0x46,
+                       data: vec![0x72, 0x08, 0x01, 0x00, 0x00],
+                   });
+    }
+
+    #[test]
+    fn rsn_fields_are_optional() {
+        let rsn = Rsn::parse(&[1, 0], IEEE_OUI).unwrap();
+        assert_eq!(rsn.group_cipher, None);
+        assert!(rsn.akm_suites.is_empty());
+        let rsn = Rsn::parse(&[1, 0, 0x00, 0x0f, 0xac, 0x02], IEEE_OUI).unwrap();
+        assert_eq!(rsn.group_cipher, Some(CipherSuite::Tkip));
+        // A pairwise count with nothing behind it is malformed.
+        assert_eq!(Rsn::parse(&[1, 0, 0x00, 0x0f, 0xac, 0x02, 0x05, 0x00], IEEE_OUI), None);
+    }
+
+    #[test]
+    fn rsn_mfp_and_group_management() {
+        let body = [1, 0, 0x00, 0x0f, 0xac, 0x04, 1, 0, 0x00, 0x0f, 0xac, 0x04, 1, 0, 0x00,
+                    0x0f, 0xac, 0x08, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x0f, 0xac, 0x06];
+        let rsn = Rsn::parse(&body, IEEE_OUI).unwrap();
+        assert!(rsn.mfp_required() && rsn.mfp_capable());
+        assert_eq!(rsn.group_management_cipher, Some(CipherSuite::BipCmac128));
+    }
+
+    #[test]
+    fn six_ghz_he_operation() {
+        let element = Element::parse(EXTENSION,
+                                     &[EXT_HE_OPERATION, 0x00, 0x00, 0x02, 0x01, 0xfc, 0xff,
+                                       0x25, 0x02, 0x27, 0x00, 0x01]);
+        match element {
+            Element::HeOperation(he) => {
+                let six_ghz = he.six_ghz.unwrap();
+                assert_eq!(six_ghz.primary_channel, 37);
+                assert_eq!(six_ghz.channel_width(), 2);
+                assert_eq!(six_ghz.center_freq_seg0, 39);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn short_known_elements_stay_raw() {
+        assert_eq!(Element::parse(DS_PARAMETER, &[]),
+                   Element::Unknown {
+                       id: DS_PARAMETER,
+                       data: vec![],
+                   });
+        assert_eq!(Element::parse(HT_CAPABILITIES, &[0xef, 0x01]),
+                   Element::Unknown {
+                       id: HT_CAPABILITIES,
+                       data: vec![0xef, 0x01],
+                   });
+    }
+
+    #[test]
+    fn never_panics_on_truncated_or_garbage_input() {
+        for len in 0..BEACON.len() {
+            let elements = parse(&BEACON[..len]);
+            assert!(elements.len() <= 16);
+        }
+        // Feed every element type garbage of every length up to 40 bytes.
+        let mut state: u32 = 0x2545_f491;
+        for id in 0..=255u8 {
+            for len in 0..40 {
+                let data: Vec<u8> = (0..len)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        state as u8
+                    })
+                    .collect();
+                let _ = Element::parse(id, &data);
+            }
+        }
+    }
+}
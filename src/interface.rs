@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use {Error, MacAddress};
+
+/// The RFC 2863 operational state of an interface, from
+/// `/sys/class/net/<name>/operstate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperState {
+    Unknown,
+    NotPresent,
+    Down,
+    LowerLayerDown,
+    Testing,
+    /// Up but waiting on something, such as a wireless interface that isn't
+    /// associated yet.
+    Dormant,
+    Up,
+}
+
+impl OperState {
+    fn parse(state: &str) -> OperState {
+        match state {
+            "notpresent" => OperState::NotPresent,
+            "down" => OperState::Down,
+            "lowerlayerdown" => OperState::LowerLayerDown,
+            "testing" => OperState::Testing,
+            "dormant" => OperState::Dormant,
+            "up" => OperState::Up,
+            _ => OperState::Unknown,
+        }
+    }
+}
+
+/// A wireless network interface on the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    pub index: Option<u32>,
+    pub mac: Option<MacAddress>,
+    pub operstate: OperState,
+    /// The kernel driver bound to the device, e.g. `iwlwifi`.
+    pub driver: Option<String>,
+    /// The cfg80211 wiphy, e.g. `phy0`. `None` for drivers that only speak
+    /// Wireless Extensions.
+    pub phy: Option<String>,
+}
+
+/// List the wireless interfaces on this host, sorted by name.
+///
+/// ```no_run
+/// for interface in dradis::interfaces().unwrap() {
+///     println!("{} {:?}", interface.name, interface.operstate);
+/// }
+/// ```
+pub fn interfaces() -> Result<Vec<Interface>, Error> {
+    interfaces_in(Path::new("/"))
+}
+
+/// List the wireless interfaces described by the `sys` and `proc` trees under
+/// `root` instead of `/`. An interface counts as wireless if it has a
+/// `wireless` directory or `phy80211` link in sysfs, or a line in
+/// `/proc/net/wireless`.
+pub fn interfaces_in(root: &Path) -> Result<Vec<Interface>, Error> {
+    let class = root.join("sys/class/net");
+    let mut names = BTreeSet::new();
+    match fs::read_dir(&class) {
+        Ok(entries) => {
+            for entry in entries {
+                let path = entry.map_err(|err| Error::from_io("read_dir", err))?.path();
+                if path.join("wireless").is_dir() || fs::symlink_metadata(path.join("phy80211")).is_ok() {
+                    if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                        names.insert(name.to_string());
+                    }
+                }
+            }
+        }
+        Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(Error::from_io("read_dir", err)),
+    }
+    match fs::read_to_string(root.join("proc/net/wireless")) {
+        Ok(table) => names.extend(proc_net_wireless(&table)),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(Error::from_io("read /proc/net/wireless", err)),
+    }
+    Ok(names.into_iter().map(|name| interface(&class, name)).collect())
+}
+
+/// Pick the interface a scan should use when the caller doesn't say: the first
+/// one that is up, then the first that is dormant, then the first at all.
+pub fn default_interface(interfaces: &[Interface]) -> Option<&Interface> {
+    [OperState::Up, OperState::Dormant]
+        .iter()
+        .filter_map(|&state| interfaces.iter().find(|i| i.operstate == state))
+        .next()
+        .or_else(|| interfaces.first())
+}
+
+/// The interface names in `/proc/net/wireless`, which has two header lines
+/// and then one `name: status link level noise ...` line per interface.
+fn proc_net_wireless(table: &str) -> Vec<String> {
+    table.lines()
+        .skip(2)
+        .filter_map(|line| line.split(':').next())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn interface(class: &Path, name: String) -> Interface {
+    let dir = class.join(&name);
+    Interface {
+        index: read_trimmed(&dir.join("ifindex")).and_then(|index| index.parse().ok()),
+        mac: read_trimmed(&dir.join("address")).and_then(|mac| mac.parse().ok()),
+        operstate: read_trimmed(&dir.join("operstate"))
+            .map(|state| OperState::parse(&state))
+            .unwrap_or(OperState::Unknown),
+        driver: link_name(&dir.join("device/driver")),
+        phy: link_name(&dir.join("phy80211")),
+        name,
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|contents| contents.trim().to_string())
+}
+
+/// The last component of where the symlink at `path` points.
+fn link_name(path: &Path) -> Option<String> {
+    let target = fs::read_link(path).ok()?;
+    target.file_name().and_then(|name| name.to_str()).map(|name| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+    use std::os::unix::fs::symlink;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static ROOTS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A scratch root directory that is removed when dropped.
+    struct FakeRoot(PathBuf);
+
+    impl FakeRoot {
+        fn new() -> FakeRoot {
+            let n = ROOTS.fetch_add(1, Ordering::SeqCst);
+            let path = env::temp_dir().join(format!("dradis-interface-{}-{}", process::id(), n));
+            fs::create_dir_all(&path).unwrap();
+            FakeRoot(path)
+        }
+
+        fn net_device(&self, name: &str, index: u32, mac: &str, state: &str) -> PathBuf {
+            let dir = self.0.join("sys/class/net").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("ifindex"), format!("{}\n", index)).unwrap();
+            fs::write(dir.join("address"), format!("{}\n", mac)).unwrap();
+            fs::write(dir.join("operstate"), format!("{}\n", state)).unwrap();
+            dir
+        }
+
+        fn proc_net_wireless(&self, names: &[&str]) {
+            let dir = self.0.join("proc/net");
+            fs::create_dir_all(&dir).unwrap();
+            let mut table = "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE\n \
+                             face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22\n"
+                .to_string();
+            for name in names {
+                table.push_str(&format!("{:>6}: 0000   70.  -40.  -256        0      0      0      0      0        0\n",
+                                        name));
+            }
+            fs::write(dir.join("wireless"), table).unwrap();
+        }
+    }
+
+    impl Drop for FakeRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn finds_wireless_interfaces() {
+        let root = FakeRoot::new();
+        let wlan = root.net_device("wlp58s0", 3, "00:11:22:aa:bb:cc", "up");
+        fs::create_dir(wlan.join("wireless")).unwrap();
+        symlink("../../../../devices/virtual/ieee80211/phy0", wlan.join("phy80211")).unwrap();
+        fs::create_dir(wlan.join("device")).unwrap();
+        symlink("../../../../bus/pci/drivers/iwlwifi", wlan.join("device/driver")).unwrap();
+        // A WE-only driver that is only in /proc/net/wireless.
+        root.net_device("eth1", 4, "00:11:22:aa:bb:dd", "dormant");
+        root.proc_net_wireless(&["wlp58s0", "eth1"]);
+        // Wired interfaces are left out.
+        root.net_device("eth0", 2, "00:11:22:aa:bb:ee", "up");
+        root.net_device("lo", 1, "00:00:00:00:00:00", "unknown");
+
+        let found = interfaces_in(&root.0).unwrap();
+        assert_eq!(found,
+                   vec![Interface {
+                            name: "eth1".to_string(),
+                            index: Some(4),
+                            mac: "00:11:22:aa:bb:dd".parse().ok(),
+                            operstate: OperState::Dormant,
+                            driver: None,
+                            phy: None,
+                        },
+                        Interface {
+                            name: "wlp58s0".to_string(),
+                            index: Some(3),
+                            mac: "00:11:22:aa:bb:cc".parse().ok(),
+                            operstate: OperState::Up,
+                            driver: Some("iwlwifi".to_string()),
+                            phy: Some("phy0".to_string()),
+                        }]);
+        assert_eq!(default_interface(&found).unwrap().name, "wlp58s0");
+    }
+
+    #[test]
+    fn phy80211_alone_is_enough() {
+        let root = FakeRoot::new();
+        let wlan = root.net_device("wlan0", 5, "02:00:00:00:01:00", "down");
+        symlink("../../../../devices/virtual/ieee80211/phy1", wlan.join("phy80211")).unwrap();
+        let found = interfaces_in(&root.0).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].phy, Some("phy1".to_string()));
+        assert_eq!(found[0].operstate, OperState::Down);
+    }
+
+    #[test]
+    fn missing_trees_mean_no_interfaces() {
+        let root = FakeRoot::new();
+        assert_eq!(interfaces_in(&root.0).unwrap(), vec![]);
+        assert_eq!(default_interface(&[]), None);
+    }
+
+    #[test]
+    fn default_prefers_up_then_dormant() {
+        let interface = |name: &str, operstate| {
+            Interface {
+                name: name.to_string(),
+                index: None,
+                mac: None,
+                operstate,
+                driver: None,
+                phy: None,
+            }
+        };
+        let list = vec![interface("wlan0", OperState::Down),
+                        interface("wlan1", OperState::Dormant),
+                        interface("wlan2", OperState::Down)];
+        assert_eq!(default_interface(&list).unwrap().name, "wlan1");
+        assert_eq!(default_interface(&list[..1]).unwrap().name, "wlan0");
+    }
+
+    #[test]
+    fn parses_proc_net_wireless_without_sysfs() {
+        let root = FakeRoot::new();
+        root.proc_net_wireless(&["wlan0"]);
+        let found = interfaces_in(&root.0).unwrap();
+        assert_eq!(found[0].name, "wlan0");
+        assert_eq!((found[0].index, found[0].operstate), (None, OperState::Unknown));
+    }
+}
@@ -0,0 +1,512 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr;
+use std::os::raw::c_char;
+use libc::*;
+
+use {Channel, Error, IwParam, IwStats, MacAddress, PendingScan, ScanBackend, ScanRequest,
+     Security, Ssid, WirelessKey, WirelessMode, WirelessNetwork};
+use backend::FinishedScan;
+use wext::iw_range;
+
+const IW_ESSID_MAX_SIZE: usize = 32;
+const IW_ENCODING_TOKEN_MAX: usize = 64;
+const IFNAMSIZ: usize = 16; // Defined in /include/uapi/linux/if.h but easier to just redefine here
+
+#[repr(C)]
+struct WirelessScanHead {
+    result: *mut WirelessScan,
+    retry: c_int,
+}
+
+#[repr(C)]
+struct WirelessScan {
+    next: *mut WirelessScan,
+    has_ap_addr: c_int,
+    ap_addr: sockaddr,
+    b: WirelessConfig,
+    stats: IwStats,
+    has_stats: c_int,
+    maxbitrate: IwParam,
+    has_maxbitrate: c_int,
+}
+
+impl fmt::Debug for WirelessScan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "WirelessScan {{next: {:?},
+    has_ap_addr: {:?},
+    ap_addr: sockaddr,
+    b: \
+                {:?},
+    stats: {:?},
+    has_stats: {:?},
+    maxbitrate: {:?},
+    \
+                has_maxbitrate: {:?}
+ }}",
+               self.next,
+               self.has_ap_addr,
+               self.b,
+               self.stats,
+               self.has_stats,
+               self.maxbitrate,
+               self.has_maxbitrate)
+    }
+}
+
+#[repr(C)]
+struct WirelessConfig {
+    name: [c_char; IFNAMSIZ + 1], /* Wireless/protocol name */
+    has_nwid: c_int,
+    nwid: IwParam, /* Network ID */
+    has_freq: c_int,
+    freq: f64, /* Frequency/channel */
+    freq_flags: c_int,
+    has_key: c_int,
+    key: [c_uchar; IW_ENCODING_TOKEN_MAX], /* Encoding key used */
+    key_size: c_int, /* Number of bytes */
+    key_flags: c_int, /* Various flags */
+    has_essid: c_int,
+    essid_on: c_int,
+    essid: [c_char; IW_ESSID_MAX_SIZE + 2], // ESSID (extended network)
+    essid_len: c_int,
+    has_mode: c_int,
+    mode: c_int, /* Operation mode */
+}
+
+impl fmt::Debug for WirelessConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut better_essid = "[".to_string();
+        for &byte in self.essid.iter() {
+            better_essid.push_str(&format!("{:02X}, ", byte))
+        }
+        better_essid.push(']');
+        let mut better_key = "[".to_string();
+        for &byte in self.key.iter() {
+            better_key.push_str(&format!("{:02X}, ", byte))
+        }
+        better_key.push(']');
+
+        write!(f,
+               "WirelessConfig {{name: {:?},
+                has_nwid: {:?},
+                \
+                nwid: {:?},
+                has_freq: {:?},
+                freq: {:?},
+                \
+                freq_flags: {:?},
+                has_key: {:?},
+                key: {:?},
+                \
+                key_size: {:?},
+                key_flags: {:?},
+                has_essid: {:?},
+                \
+                essid_on: {:?},
+                essid: {:?},
+                essid_len: {:?},
+                has_mode: {:?},
+                \
+                mode: {:?},
+                }}",
+               self.name,
+               self.has_nwid,
+               self.nwid,
+               self.has_freq,
+               self.freq,
+               self.freq_flags,
+               self.has_key,
+               better_key,
+               self.key_size,
+               self.key_flags,
+               self.has_essid,
+               self.essid_on,
+               better_essid,
+               self.essid_len,
+               self.has_mode,
+               self.mode)
+    }
+}
+
+#[link(name="iw")]
+extern "C" {
+    fn iw_sockets_open() -> c_int;
+    fn iw_get_range_info(socket: c_int, interface: *const c_char, range: *mut iw_range) -> c_int;
+    fn iw_scan(socket: c_int,
+               interface: *mut c_char,
+               version: c_int,
+               head: *mut WirelessScanHead)
+               -> c_int;
+}
+
+/// The libiw calls a `WirelessContext` makes, so tests can stand in for the
+/// library.
+trait IwApi {
+    unsafe fn sockets_open(&self) -> c_int;
+    unsafe fn get_range_info(&self, socket: c_int, interface: &CStr, range: *mut iw_range) -> c_int;
+    unsafe fn scan(&self,
+                   socket: c_int,
+                   interface: &CStr,
+                   version: c_int,
+                   head: *mut WirelessScanHead)
+                   -> c_int;
+    unsafe fn close(&self, socket: c_int);
+    /// Free one node of a scan result list; libiw mallocs each of them.
+    unsafe fn free(&self, node: *mut WirelessScan);
+}
+
+/// The real libiw.
+#[derive(Debug, Default)]
+struct Libiw;
+
+impl IwApi for Libiw {
+    unsafe fn sockets_open(&self) -> c_int {
+        iw_sockets_open()
+    }
+
+    unsafe fn get_range_info(&self, socket: c_int, interface: &CStr, range: *mut iw_range) -> c_int {
+        iw_get_range_info(socket, interface.as_ptr(), range)
+    }
+
+    unsafe fn scan(&self,
+                   socket: c_int,
+                   interface: &CStr,
+                   version: c_int,
+                   head: *mut WirelessScanHead)
+                   -> c_int {
+        iw_scan(socket, interface.as_ptr() as *mut c_char, version, head)
+    }
+
+    // iw_sockets_close is an inline function in iwlib.h, not an export.
+    unsafe fn close(&self, socket: c_int) {
+        close(socket);
+    }
+
+    unsafe fn free(&self, node: *mut WirelessScan) {
+        free(node as *mut c_void);
+    }
+}
+
+/// A socket from `iw_sockets_open`, closed on drop, and the scans run on it.
+/// Every scan's result list is freed before `scan` returns.
+struct WirelessContext<L: IwApi = Libiw> {
+    lib: L,
+    socket: c_int,
+}
+
+impl WirelessContext<Libiw> {
+    fn open() -> Result<WirelessContext<Libiw>, Error> {
+        WirelessContext::open_with(Libiw)
+    }
+}
+
+impl<L: IwApi> WirelessContext<L> {
+    fn open_with(lib: L) -> Result<WirelessContext<L>, Error> {
+        let socket = unsafe { lib.sockets_open() };
+        if socket < 0 {
+            return Err(Error::last_os_error("iw_sockets_open"));
+        }
+        Ok(WirelessContext { lib, socket })
+    }
+
+    /// Run a scan of `interface` and copy the networks out of libiw's list.
+    fn scan(&self, interface: &str) -> Result<Vec<WirelessNetwork>, Error> {
+        let name = CString::new(interface).map_err(|_| {
+            Error::InvalidInput {
+                call: "iw_scan",
+                message: "interface name contains a NUL byte",
+            }
+        })?;
+        // We have to make this call in order to get the version of the library on the computer
+        let mut range: iw_range = Default::default();
+        if unsafe { self.lib.get_range_info(self.socket, &name, &mut range) } < 0 {
+            return Err(Error::last_os_error("iw_get_range_info"));
+        }
+        // This is the actual scan call that fills in the head with a list of
+        // the visible networks. The list is ours to free, even if the scan
+        // fails partway through.
+        let mut list = ScanList {
+            lib: &self.lib,
+            head: WirelessScanHead {
+                result: ptr::null_mut(),
+                retry: 0,
+            },
+        };
+        if unsafe { self.lib.scan(self.socket, &name, range.we_version() as c_int, &mut list.head) } < 0 {
+            return Err(Error::last_os_error("iw_scan"));
+        }
+        Ok(list.iter().map(|result| network(result, &range)).collect())
+    }
+}
+
+impl<L: IwApi> Drop for WirelessContext<L> {
+    fn drop(&mut self) {
+        unsafe {
+            self.lib.close(self.socket);
+        }
+    }
+}
+
+/// The linked list of results `iw_scan` allocated, freed on drop.
+struct ScanList<'a, L: IwApi + 'a> {
+    lib: &'a L,
+    head: WirelessScanHead,
+}
+
+impl<'a, L: IwApi> ScanList<'a, L> {
+    fn iter(&self) -> ScanIter<'_> {
+        ScanIter {
+            next: self.head.result,
+            _list: PhantomData,
+        }
+    }
+}
+
+impl<'a, L: IwApi> Drop for ScanList<'a, L> {
+    fn drop(&mut self) {
+        let mut node = self.head.result;
+        self.head.result = ptr::null_mut();
+        while !node.is_null() {
+            unsafe {
+                let next = (*node).next;
+                self.lib.free(node);
+                node = next;
+            }
+        }
+    }
+}
+
+struct ScanIter<'a> {
+    next: *const WirelessScan,
+    _list: PhantomData<&'a WirelessScan>,
+}
+
+impl<'a> Iterator for ScanIter<'a> {
+    type Item = &'a WirelessScan;
+
+    fn next(&mut self) -> Option<&'a WirelessScan> {
+        if self.next.is_null() {
+            return None;
+        }
+        let node = unsafe { &*self.next };
+        self.next = node.next;
+        Some(node)
+    }
+}
+
+/// Scan backend that drives the wireless tools library (`libiw`), the same code
+/// `iwlist` uses. Only available with the `libiw` feature.
+#[derive(Debug, Default)]
+pub struct IwlibBackend;
+
+impl IwlibBackend {
+    pub fn new() -> IwlibBackend {
+        IwlibBackend
+    }
+}
+
+/// The ESSID libiw copied out of the scan. Its scan code doesn't always fill
+/// in `essid_len`, and without it the SSID can only run up to the first NUL.
+fn essid(config: &WirelessConfig) -> Ssid {
+    let bytes: Vec<u8> = config.essid.iter().map(|&byte| byte as u8).collect();
+    let len = match config.essid_len {
+        len @ 1..=32 => len as usize,
+        _ => bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len()),
+    };
+    Ssid::truncated(&bytes[..len])
+}
+
+/// Copy one of libiw's results into a network.
+fn network(result: &WirelessScan, range: &iw_range) -> WirelessNetwork {
+    let bssid = if result.has_ap_addr == 1 {
+        let sa_data: Vec<u8> = result.ap_addr.sa_data.iter().map(|&b| b as u8).collect();
+        MacAddress::from_slice(&sa_data)
+    } else {
+        None
+    };
+    let network_name = if result.b.has_essid == 1 {
+        Some(essid(&result.b))
+    } else {
+        None
+    };
+    // libiw hands back a bare channel number as a small frequency.
+    let freq = match result.b.freq {
+        _ if result.b.has_freq == 0 => None,
+        freq if freq < 1e3 => Channel::from_number(freq as u16).map(|c| c.hz()),
+        freq => Some(freq),
+    };
+    let key = if result.b.has_key != 0 {
+        let size = (result.b.key_size.max(0) as usize).min(IW_ENCODING_TOKEN_MAX);
+        Some(WirelessKey::new(&result.b.key[..size], result.b.key_flags as u16))
+    } else {
+        None
+    };
+    // libiw doesn't keep the IEs, so protected networks all look like WEP here.
+    let privacy = key.as_ref().is_some_and(WirelessKey::is_enabled);
+    WirelessNetwork {
+        bssid,
+        maxbitrate: if result.has_maxbitrate != 0 { Some(result.maxbitrate.value) } else { None },
+        freq,
+        key,
+        mode: if result.b.has_mode != 0 { WirelessMode::from_iw(result.b.mode) } else { None },
+        essid: network_name,
+        security: Security::from_ies(&[], privacy),
+        stats: if result.has_stats != 0 { Some(result.stats) } else { None },
+        max_quality: Some(range.max_quality()),
+        ies: Vec::new(),
+    }
+}
+
+impl ScanBackend for IwlibBackend {
+    fn start_scan(&mut self, request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+        // iw_scan always runs the driver's default scan and has its own
+        // timeout, so only the interface can be honored.
+        if request.is_targeted() {
+            return Err(Error::Unsupported {
+                call: "iw_scan",
+                message: "libiw can only run default scans",
+            });
+        }
+        // iw_scan blocks until the results are in, so there is nothing left
+        // to wait for by the time it returns.
+        let context = WirelessContext::open()?;
+        let list = context.scan(&request.interface)?;
+        Ok(Box::new(FinishedScan::new(list)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::mem;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Log {
+        closed: Vec<c_int>,
+        allocated: Vec<usize>,
+        freed: Vec<usize>,
+    }
+
+    /// Stands in for libiw, mallocing a result list like `iw_scan` does and
+    /// recording what gets closed and freed.
+    struct Stub {
+        log: Rc<RefCell<Log>>,
+        socket: c_int,
+        essids: Vec<&'static str>,
+        fail_scan: bool,
+    }
+
+    impl Stub {
+        fn new(essids: Vec<&'static str>) -> (Stub, Rc<RefCell<Log>>) {
+            let log = Rc::new(RefCell::new(Log::default()));
+            let stub = Stub {
+                log: log.clone(),
+                socket: 7,
+                essids,
+                fail_scan: false,
+            };
+            (stub, log)
+        }
+    }
+
+    impl IwApi for Stub {
+        unsafe fn sockets_open(&self) -> c_int {
+            self.socket
+        }
+
+        unsafe fn get_range_info(&self, _socket: c_int, _interface: &CStr, _range: *mut iw_range) -> c_int {
+            0
+        }
+
+        unsafe fn scan(&self,
+                       _socket: c_int,
+                       _interface: &CStr,
+                       _version: c_int,
+                       head: *mut WirelessScanHead)
+                       -> c_int {
+            for essid in self.essids.iter().rev() {
+                let node = calloc(1, mem::size_of::<WirelessScan>()) as *mut WirelessScan;
+                (*node).b.has_essid = 1;
+                (*node).b.essid_len = essid.len() as c_int;
+                for (dst, &src) in (*node).b.essid.iter_mut().zip(essid.as_bytes()) {
+                    *dst = src as c_char;
+                }
+                (*node).b.has_mode = 1;
+                (*node).b.mode = 3;
+                (*node).has_maxbitrate = 1;
+                (*node).maxbitrate.value = 54000000;
+                (*node).next = (*head).result;
+                (*head).result = node;
+                self.log.borrow_mut().allocated.push(node as usize);
+            }
+            if self.fail_scan { -1 } else { 0 }
+        }
+
+        unsafe fn close(&self, socket: c_int) {
+            self.log.borrow_mut().closed.push(socket);
+        }
+
+        unsafe fn free(&self, node: *mut WirelessScan) {
+            self.log.borrow_mut().freed.push(node as usize);
+            free(node as *mut c_void);
+        }
+    }
+
+    fn assert_all_freed(log: &Log) {
+        let mut allocated = log.allocated.clone();
+        let mut freed = log.freed.clone();
+        allocated.sort();
+        freed.sort();
+        assert_eq!(allocated, freed);
+    }
+
+    #[test]
+    fn frees_every_node_and_closes_the_socket() {
+        let (stub, log) = Stub::new(vec!["home", "cafe", "guest"]);
+        let context = WirelessContext::open_with(stub).unwrap();
+        for _ in 0..2 {
+            let networks = context.scan("wlan0").unwrap();
+            let essids: Vec<String> = networks.iter().map(|n| n.essid.unwrap().to_string()).collect();
+            assert_eq!(essids, vec!["home", "cafe", "guest"]);
+            assert_eq!(networks[0].mode, Some(WirelessMode::Master));
+            assert_eq!(networks[0].maxbitrate, Some(54000000));
+            assert_eq!((networks[0].key.clone(), networks[0].stats), (None, None));
+        }
+        assert_eq!(log.borrow().allocated.len(), 6);
+        assert_all_freed(&log.borrow());
+        assert!(log.borrow().closed.is_empty());
+        drop(context);
+        assert_eq!(log.borrow().closed, vec![7]);
+    }
+
+    #[test]
+    fn frees_the_list_when_the_scan_fails() {
+        let (mut stub, log) = Stub::new(vec!["home", "cafe"]);
+        stub.fail_scan = true;
+        let context = WirelessContext::open_with(stub).unwrap();
+        let err = context.scan("wlan0").unwrap_err();
+        assert_eq!(err.call(), Some("iw_scan"));
+        assert_eq!(log.borrow().allocated.len(), 2);
+        assert_all_freed(&log.borrow());
+    }
+
+    #[test]
+    fn nothing_to_close_when_open_fails() {
+        let (mut stub, log) = Stub::new(Vec::new());
+        stub.socket = -1;
+        let err = WirelessContext::open_with(stub).map(|_| ()).unwrap_err();
+        assert_eq!(err.call(), Some("iw_sockets_open"));
+        assert!(log.borrow().closed.is_empty());
+    }
+
+    #[test]
+    fn stats_match_iw_statistics() {
+        // struct iw_statistics: status, qual, discard (5 x u32), miss.
+        assert_eq!(mem::size_of::<IwStats>(), 32);
+    }
+}
@@ -0,0 +1,153 @@
+//! Kismet's SQLite logs. Each device Kismet saw is a row in the `devices`
+//! table, with everything it knows about it as a JSON blob.
+
+use std::fs::File;
+use std::path::Path;
+use std::time::SystemTime;
+
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+
+use {Channel, Error, IwQuality, IwStats, MacAddress, Ssid, WirelessMode, WirelessNetwork};
+use date;
+use super::{parse_crypt, KismetRecord};
+
+const CALL: &str = "kismetdb";
+
+const QUERY: &str = "SELECT device FROM devices \
+                     WHERE phyname = 'IEEE802.11' AND type IN ('Wi-Fi AP', 'Wi-Fi Ad-Hoc') \
+                     ORDER BY first_time";
+
+fn sql_error(err: rusqlite::Error) -> Error {
+    Error::parse(CALL, err.to_string())
+}
+
+/// Read the access points and ad-hoc networks out of a kismetdb log, in the
+/// order they were first seen. The file is only opened for reading, so it's
+/// safe to read one Kismet is still writing.
+pub fn read_kismetdb<P: AsRef<Path>>(path: P) -> Result<Vec<KismetRecord>, Error> {
+    // SQLite only says it couldn't open the file; opening it ourselves first
+    // keeps the errno, so a missing or unreadable log isn't a parse error.
+    File::open(&path).map_err(|err| Error::from_io(CALL, err))?;
+    let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(sql_error)?;
+    let mut statement = db.prepare(QUERY).map_err(sql_error)?;
+    let mut rows = statement.query([]).map_err(sql_error)?;
+    let mut records = Vec::new();
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        // Kismet stores the JSON as a blob, but take text just as well.
+        let json = row.get_ref(0).map_err(sql_error)?.as_bytes().map_err(|err| sql_error(err.into()))?;
+        let device: Value = serde_json::from_slice(json)
+            .map_err(|err| Error::parse(CALL, format!("device {}: {}", records.len() + 1, err)))?;
+        records.push(record(&device)?);
+    }
+    Ok(records)
+}
+
+fn time(device: &Value, field: &str) -> Option<SystemTime> {
+    device[field].as_i64().filter(|&secs| secs != 0).and_then(date::from_unix_seconds)
+}
+
+/// A signal level, with the 0 Kismet leaves in fields it never filled in as
+/// `None`.
+fn dbm(signal: &Value, field: &str) -> Option<i32> {
+    signal[field].as_i64().filter(|&dbm| dbm != 0).map(|dbm| dbm as i32)
+}
+
+/// The SSID record from the last beacon, or failing that the first one
+/// advertised, depending on how new the Kismet that wrote the log is.
+fn ssid_record(dot11: &Value) -> Option<&Value> {
+    let last = &dot11["dot11.device.last_beaconed_ssid_record"];
+    if last.is_object() {
+        return Some(last);
+    }
+    match dot11["dot11.device.advertised_ssid_map"] {
+        Value::Array(ref ssids) => ssids.first(),
+        Value::Object(ref ssids) => ssids.values().next(),
+        _ => None,
+    }
+}
+
+fn record(device: &Value) -> Result<KismetRecord, Error> {
+    let mac = device["kismet.device.base.macaddr"].as_str().unwrap_or("");
+    let bssid: MacAddress = mac.parse().map_err(|_| Error::parse(CALL, format!("bad MAC '{}'", mac)))?;
+    let ssid = ssid_record(&device["dot11.device"]);
+    let essid = ssid.and_then(|ssid| ssid["dot11.advertisedssid.ssid"].as_str())
+        .map(|ssid| Ssid::truncated(ssid.as_bytes()));
+    let maxbitrate = ssid.and_then(|ssid| ssid["dot11.advertisedssid.maxrate"].as_f64())
+        .filter(|&mbps| mbps > 0.0)
+        .map(|mbps| (mbps * 1e6).round() as i32);
+    // The frequency is in kHz.
+    let freq = match device["kismet.device.base.frequency"].as_f64() {
+        Some(khz) if khz > 0.0 => Some(khz * 1e3),
+        _ => {
+            let channel = device["kismet.device.base.channel"].as_str();
+            let channel = channel.and_then(|number| number.parse().ok());
+            channel.and_then(Channel::from_number).map(|channel| channel.hz())
+        }
+    };
+    let signal = &device["kismet.device.base.signal"];
+    let crypt = device["kismet.device.base.crypt"].as_str().unwrap_or("");
+    let mode = match device["kismet.device.base.type"].as_str() {
+        Some("Wi-Fi Ad-Hoc") => WirelessMode::AdHoc,
+        _ => WirelessMode::Master,
+    };
+    Ok(KismetRecord {
+        network: WirelessNetwork {
+            bssid: Some(bssid),
+            stats: dbm(signal, "kismet.common.signal.last_signal")
+                .map(|dbm| IwStats::new(IwQuality::from_dbm(dbm))),
+            max_quality: None,
+            maxbitrate,
+            freq,
+            key: None,
+            essid,
+            mode: Some(mode),
+            security: parse_crypt(crypt.split_whitespace()),
+            ies: Vec::new(),
+        },
+        first_seen: time(device, "kismet.device.base.first_time"),
+        last_seen: time(device, "kismet.device.base.last_time"),
+        min_signal_dbm: dbm(signal, "kismet.common.signal.min_signal"),
+        max_signal_dbm: dbm(signal, "kismet.common.signal.max_signal"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use {Band, Protocol};
+    use ie::{AkmSuite, CipherSuite};
+
+    #[test]
+    fn reads_kismetdb_logs() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/kismet/sample.kismet");
+        let records = read_kismetdb(path).unwrap();
+        // The client is left out.
+        assert_eq!(records.len(), 2);
+        let cafe = &records[0];
+        assert_eq!(cafe.network.bssid, "a4:2b:b0:11:22:33".parse().ok());
+        assert_eq!(cafe.network.essid, Ssid::new(b"CoffeeShop"));
+        assert_eq!(cafe.network.channel().map(|channel| channel.number()), Some(6));
+        assert_eq!(cafe.network.signal_dbm(), Some(-58));
+        assert_eq!(cafe.network.maxbitrate, Some(54000000));
+        assert_eq!(cafe.network.security.protocol, Protocol::Wpa2);
+        assert_eq!(cafe.network.security.pairwise_ciphers, vec![CipherSuite::Ccmp128]);
+        assert_eq!(cafe.first_seen, Some(UNIX_EPOCH + Duration::from_secs(1564680007)));
+        assert_eq!((cafe.min_signal_dbm, cafe.max_signal_dbm), (Some(-71), Some(-49)));
+
+        let hidden = &records[1].network;
+        assert!(hidden.essid.unwrap().is_hidden());
+        assert_eq!(hidden.band(), Some(Band::FiveGhz));
+        assert_eq!(hidden.security.protocol, Protocol::Wpa3);
+        assert_eq!(hidden.security.akm_suites, vec![AkmSuite::Psk, AkmSuite::Sae]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let netxml = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/kismet/sample.netxml");
+        assert!(matches!(read_kismetdb(netxml), Err(Error::Parse { .. })));
+        let missing = read_kismetdb("/nonexistent/dradis.kismet").unwrap_err();
+        assert!(matches!(missing, Error::Backend { errno: Some(::libc::ENOENT), .. }), "{:?}", missing);
+    }
+}
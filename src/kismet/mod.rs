@@ -0,0 +1,242 @@
+//! Surveys in Kismet's formats, so they can go back and forth between
+//! dradis and Kismet.
+//!
+//! A `Session` gathers scans taken over a run into one record per BSSID,
+//! the way Kismet tracks devices, and writes them as a netxml detection run.
+//! `read_netxml` reads the networks back out of netxml written by dradis,
+//! Kismet itself or `kismetdb_to_netxml`, and with the `kismetdb` feature
+//! `read_kismetdb` reads them straight out of the SQLite logs newer Kismet
+//! writes. Clients and probe requests are skipped on the way in.
+//!
+//! Kismet only keeps a summary of a network's security: the protocol, AKM
+//! suites and pairwise ciphers come back, the group cipher and management
+//! frame protection don't.
+//!
+//! ```
+//! use std::time::SystemTime;
+//! use dradis::{FixtureBackend, WifiScan};
+//! use dradis::kismet::{self, Session};
+//!
+//! let scan = WifiScan::scan_with(&mut FixtureBackend::new(Vec::new()), "wlan0").unwrap();
+//! let mut session = Session::new(SystemTime::now());
+//! session.add_scan(&scan, SystemTime::now());
+//! let mut netxml = Vec::new();
+//! session.write_netxml(&mut netxml).unwrap();
+//! assert!(kismet::read_netxml(&netxml[..]).unwrap().is_empty());
+//! ```
+
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+use {Protocol, Security, WifiScan, WirelessNetwork};
+use ie::{AkmSuite, CipherSuite};
+
+#[cfg(feature = "kismetdb")]
+mod kismetdb;
+mod netxml;
+
+#[cfg(feature = "kismetdb")]
+pub use self::kismetdb::read_kismetdb;
+pub use self::netxml::{read_netxml, write_netxml};
+
+/// A network as Kismet tracks it: how it looked last, when it was first
+/// and last seen and how its signal varied in between.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KismetRecord {
+    pub network: WirelessNetwork,
+    pub first_seen: Option<SystemTime>,
+    pub last_seen: Option<SystemTime>,
+    /// The weakest signal it was seen with, in dBm.
+    pub min_signal_dbm: Option<i32>,
+    /// The strongest signal it was seen with, in dBm.
+    pub max_signal_dbm: Option<i32>,
+}
+
+impl KismetRecord {
+    fn new(network: &WirelessNetwork, time: SystemTime) -> KismetRecord {
+        KismetRecord {
+            network: network.clone(),
+            first_seen: Some(time),
+            last_seen: Some(time),
+            min_signal_dbm: network.signal_dbm(),
+            max_signal_dbm: network.signal_dbm(),
+        }
+    }
+
+    /// Update the record with another sighting of its network.
+    fn seen(&mut self, network: &WirelessNetwork, time: SystemTime) {
+        self.network = network.clone();
+        self.first_seen = Some(self.first_seen.map_or(time, |first| first.min(time)));
+        self.last_seen = Some(self.last_seen.map_or(time, |last| last.max(time)));
+        if let Some(dbm) = network.signal_dbm() {
+            self.min_signal_dbm = Some(self.min_signal_dbm.map_or(dbm, |min| min.min(dbm)));
+            self.max_signal_dbm = Some(self.max_signal_dbm.map_or(dbm, |max| max.max(dbm)));
+        }
+    }
+}
+
+/// Scans taken over a run, merged into one record per BSSID.
+#[derive(Debug, Clone)]
+pub struct Session {
+    start: SystemTime,
+    records: Vec<KismetRecord>,
+}
+
+impl Session {
+    /// Start a session that began at `start`.
+    pub fn new(start: SystemTime) -> Session {
+        Session {
+            start,
+            records: Vec::new(),
+        }
+    }
+
+    /// Add the networks in a scan taken at `time`. Networks without a BSSID
+    /// can't be told apart from each other and are left out.
+    pub fn add_scan(&mut self, scan: &WifiScan, time: SystemTime) {
+        for network in scan.networks.iter().filter(|network| network.bssid.is_some()) {
+            match self.records.iter_mut().find(|record| record.network.bssid == network.bssid) {
+                Some(record) => record.seen(network, time),
+                None => self.records.push(KismetRecord::new(network, time)),
+            }
+        }
+    }
+
+    pub fn start(&self) -> SystemTime {
+        self.start
+    }
+
+    /// The networks seen so far, in the order they were first seen.
+    pub fn records(&self) -> &[KismetRecord] {
+        &self.records
+    }
+
+    /// Write the session as a netxml detection run.
+    pub fn write_netxml<W: Write>(&self, out: W) -> io::Result<()> {
+        write_netxml(out, self.start, &self.records)
+    }
+}
+
+const AES_MODES: [&str; 5] = ["CCM", "CCMP", "GCM", "GCMP", "OCB"];
+
+fn push<T: PartialEq>(items: &mut Vec<T>, item: T) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+/// The security Kismet's encryption names describe, either netxml's
+/// `<encryption>` elements, like `WPA+PSK` and `WPA+AES-CCM`, or the words
+/// of a kismetdb crypt string, like `WPA2-PSK AES-CCMP`.
+///
+/// netxml doesn't say which version of WPA a network uses, so without one
+/// it's worked out from the suites: SAE or OWE means WPA3, CCMP or GCMP
+/// WPA2 and TKIP alone the original WPA.
+pub(crate) fn parse_crypt<'a, I: IntoIterator<Item = &'a str>>(names: I) -> Security {
+    let mut security = Security::open();
+    let mut wpa = false;
+    let mut version = None;
+    for name in names {
+        let name = name.to_uppercase();
+        let mut words = name.split(['+', '-', '/', ' ']).peekable();
+        while let Some(word) = words.next() {
+            match word {
+                "WEP" | "WEP40" | "WEP104" | "WEP128" => {
+                    security.protocol = security.protocol.max(Protocol::Wep)
+                }
+                "WPA" => wpa = true,
+                "WPA2" => version = version.max(Some(Protocol::Wpa2)),
+                "WPA3" => version = version.max(Some(Protocol::Wpa3)),
+                "PSK" => push(&mut security.akm_suites, AkmSuite::Psk),
+                "SAE" => push(&mut security.akm_suites, AkmSuite::Sae),
+                "OWE" => push(&mut security.akm_suites, AkmSuite::Owe),
+                "EAP" | "MGT" | "LEAP" | "PEAP" | "TTLS" | "TLS" => {
+                    push(&mut security.akm_suites, AkmSuite::Ieee8021x)
+                }
+                "TKIP" => push(&mut security.pairwise_ciphers, CipherSuite::Tkip),
+                // AES on its own means CCMP, otherwise the mode that follows says.
+                "AES" if !words.peek().is_some_and(|mode| AES_MODES.contains(mode)) => {
+                    push(&mut security.pairwise_ciphers, CipherSuite::Ccmp128)
+                }
+                "CCMP" | "CCM" | "GCMP" | "GCM" => {
+                    let wide = words.next_if_eq(&"256").is_some();
+                    let cipher = match (word.starts_with('G'), wide) {
+                        (false, false) => CipherSuite::Ccmp128,
+                        (false, true) => CipherSuite::Ccmp256,
+                        (true, false) => CipherSuite::Gcmp128,
+                        (true, true) => CipherSuite::Gcmp256,
+                    };
+                    push(&mut security.pairwise_ciphers, cipher);
+                }
+                _ => {}
+            }
+        }
+    }
+    if wpa || version.is_some() || !security.akm_suites.is_empty() || !security.pairwise_ciphers.is_empty() {
+        let wpa3 = security.akm_suites.iter().any(|&akm| akm == AkmSuite::Sae || akm == AkmSuite::Owe);
+        let protocol = if wpa3 {
+            Protocol::Wpa3
+        } else if let Some(version) = version {
+            version
+        } else if security.pairwise_ciphers.iter().any(|&cipher| cipher != CipherSuite::Tkip) {
+            Protocol::Wpa2
+        } else {
+            Protocol::Wpa
+        };
+        security.protocol = security.protocol.max(protocol);
+    }
+    security
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use {IwQuality, IwStats, MacAddress, Ssid};
+
+    fn network(last_octet: u8, dbm: i32) -> WirelessNetwork {
+        WirelessNetwork {
+            bssid: Some(MacAddress::new([0x00, 0x11, 0x22, 0xaa, 0xbb, last_octet])),
+            stats: Some(IwStats::new(IwQuality::from_dbm(dbm))),
+            freq: Some(2.412e9),
+            essid: Ssid::new(b"home"),
+            ..WirelessNetwork::default()
+        }
+    }
+
+    #[test]
+    fn merges_scans_by_bssid() {
+        let start = UNIX_EPOCH + Duration::from_secs(1564680000);
+        let later = start + Duration::from_secs(30);
+        let mut session = Session::new(start);
+        let no_bssid = WirelessNetwork { bssid: None, ..network(1, -50) };
+        session.add_scan(&WifiScan { networks: vec![network(1, -60), no_bssid], stale: false }, start);
+        session.add_scan(&WifiScan { networks: vec![network(2, -80), network(1, -45)], stale: false }, later);
+        let records = session.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].network, network(1, -45));
+        assert_eq!((records[0].first_seen, records[0].last_seen), (Some(start), Some(later)));
+        assert_eq!((records[0].min_signal_dbm, records[0].max_signal_dbm), (Some(-60), Some(-45)));
+        assert_eq!(records[1].first_seen, Some(later));
+        assert_eq!(session.start(), start);
+    }
+
+    fn check(names: &[&str], protocol: Protocol, akms: &[AkmSuite], ciphers: &[CipherSuite]) {
+        let security = parse_crypt(names.iter().cloned());
+        assert_eq!(security.protocol, protocol, "{:?}", names);
+        assert_eq!(security.akm_suites, akms, "{:?}", names);
+        assert_eq!(security.pairwise_ciphers, ciphers, "{:?}", names);
+    }
+
+    #[test]
+    fn parses_crypt_names() {
+        check(&["None"], Protocol::Open, &[], &[]);
+        check(&["WEP"], Protocol::Wep, &[], &[]);
+        check(&["WPA+PSK", "WPA+TKIP"], Protocol::Wpa, &[AkmSuite::Psk], &[CipherSuite::Tkip]);
+        check(&["WPA", "WPA+PSK", "WPA+AES-CCM"], Protocol::Wpa2, &[AkmSuite::Psk], &[CipherSuite::Ccmp128]);
+        check(&["WPA+PEAP", "WPA+AES-OCB"], Protocol::Wpa, &[AkmSuite::Ieee8021x], &[]);
+        check(&["WPA+SAE", "WPA+AES-CCM"], Protocol::Wpa3, &[AkmSuite::Sae], &[CipherSuite::Ccmp128]);
+        check(&["WPA2-PSK", "AES-CCMP"], Protocol::Wpa2, &[AkmSuite::Psk], &[CipherSuite::Ccmp128]);
+        check(&["WPA3-EAP", "GCMP-256"], Protocol::Wpa3, &[AkmSuite::Ieee8021x], &[CipherSuite::Gcmp256]);
+    }
+}
@@ -0,0 +1,480 @@
+//! Kismet's netxml: a `<detection-run>` with a `<wireless-network>` element
+//! for each device it saw.
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::time::SystemTime;
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use {Channel, Error, IwQuality, IwStats, MacAddress, Protocol, Security, Ssid, WirelessMode,
+     WirelessNetwork};
+use date;
+use ie::{AkmSuite, CipherSuite};
+use super::{parse_crypt, KismetRecord};
+
+const CALL: &str = "Kismet netxml";
+
+const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov",
+                            "Dec"];
+
+/// Write `records` as a detection run that started at `start`. Records for
+/// networks without a BSSID are left out.
+pub fn write_netxml<W: Write>(mut out: W, start: SystemTime, records: &[KismetRecord]) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<!DOCTYPE detection-run SYSTEM \"http://kismetwireless.net/kismet-3.1.0.dtd\">")?;
+    writeln!(out,
+             "<detection-run kismet-version=\"dradis-{}\" start-time=\"{}\">",
+             env!("CARGO_PKG_VERSION"),
+             format_time(start))?;
+    let records = records.iter().filter(|record| record.network.bssid.is_some());
+    for (number, record) in records.enumerate() {
+        write_network(&mut out, number + 1, record)?;
+    }
+    writeln!(out, "</detection-run>")
+}
+
+fn write_network<W: Write>(out: &mut W, number: usize, record: &KismetRecord) -> io::Result<()> {
+    let network = &record.network;
+    let kind = match network.mode {
+        Some(WirelessMode::AdHoc) => "ad-hoc",
+        _ => "infrastructure",
+    };
+    let times = match (record.first_seen, record.last_seen) {
+        (Some(first), Some(last)) => {
+            format!(" first-time=\"{}\" last-time=\"{}\"", format_time(first), format_time(last))
+        }
+        (Some(time), None) | (None, Some(time)) => {
+            format!(" first-time=\"{0}\" last-time=\"{0}\"", format_time(time))
+        }
+        (None, None) => String::new(),
+    };
+    writeln!(out, "  <wireless-network number=\"{}\" type=\"{}\"{}>", number, kind, times)?;
+    writeln!(out, "    <SSID{}>", times)?;
+    writeln!(out, "      <type>Beacon</type>")?;
+    if let Some(bps) = network.maxbitrate {
+        writeln!(out, "      <max-rate>{:.6}</max-rate>", bps as f64 / 1e6)?;
+    }
+    for name in encryption(&network.security) {
+        writeln!(out, "      <encryption>{}</encryption>", name)?;
+    }
+    match network.essid {
+        Some(ssid) if !ssid.is_hidden() => {
+            writeln!(out, "      <essid cloaked=\"false\">{}</essid>", escape(ssid_text(&ssid)))?
+        }
+        _ => writeln!(out, "      <essid cloaked=\"true\"></essid>")?,
+    }
+    writeln!(out, "    </SSID>")?;
+    if let Some(bssid) = network.bssid {
+        writeln!(out, "    <BSSID>{}</BSSID>", bssid.to_string().to_uppercase())?;
+    }
+    if let Some(channel) = network.channel() {
+        writeln!(out, "    <channel>{}</channel>", channel.number())?;
+        writeln!(out, "    <freqmhz>{}</freqmhz>", channel.mhz())?;
+    }
+    if let Some(bps) = network.maxbitrate {
+        writeln!(out, "    <maxseenrate>{}</maxseenrate>", bps / 1000)?;
+    }
+    if let Some(dbm) = network.signal_dbm() {
+        writeln!(out, "    <snr-info>")?;
+        writeln!(out, "      <last_signal_dbm>{}</last_signal_dbm>", dbm)?;
+        writeln!(out, "      <min_signal_dbm>{}</min_signal_dbm>", record.min_signal_dbm.unwrap_or(dbm))?;
+        writeln!(out, "      <max_signal_dbm>{}</max_signal_dbm>", record.max_signal_dbm.unwrap_or(dbm))?;
+        writeln!(out, "    </snr-info>")?;
+    }
+    writeln!(out, "  </wireless-network>")
+}
+
+/// The SSID as text. XML can't carry control characters, so SSIDs with
+/// those, or that aren't UTF-8, are written escaped.
+fn ssid_text(ssid: &Ssid) -> Cow<'_, str> {
+    match ssid.as_str() {
+        Some(text) if !text.chars().any(char::is_control) => Cow::Borrowed(text),
+        _ => Cow::Owned(ssid.to_string()),
+    }
+}
+
+/// The `<encryption>` names Kismet uses for `security`.
+fn encryption(security: &Security) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    match security.protocol {
+        Protocol::Open => names.push("None"),
+        Protocol::Wep => names.push("WEP"),
+        Protocol::Wpa | Protocol::Wpa2 | Protocol::Wpa3 => {
+            names.push("WPA");
+            for &akm in &security.akm_suites {
+                let name = match akm {
+                    AkmSuite::Psk | AkmSuite::FtPsk | AkmSuite::PskSha256 | AkmSuite::PskSha384 |
+                    AkmSuite::FtPskSha384 => "WPA+PSK",
+                    AkmSuite::Sae | AkmSuite::FtSae | AkmSuite::SaeExt | AkmSuite::FtSaeExt => "WPA+SAE",
+                    AkmSuite::Owe => "WPA+OWE",
+                    AkmSuite::Tdls | AkmSuite::ApPeerKey | AkmSuite::Other(..) => continue,
+                    _ => "WPA+EAP",
+                };
+                super::push(&mut names, name);
+            }
+            for &cipher in &security.pairwise_ciphers {
+                let name = match cipher {
+                    CipherSuite::Tkip => "WPA+TKIP",
+                    CipherSuite::Ccmp128 | CipherSuite::Ccmp256 => "WPA+AES-CCM",
+                    CipherSuite::Gcmp128 | CipherSuite::Gcmp256 => "WPA+AES-GCM",
+                    _ => continue,
+                };
+                super::push(&mut names, name);
+            }
+        }
+    }
+    names
+}
+
+/// What's been read of a `<wireless-network>` so far.
+#[derive(Default)]
+struct Partial {
+    kind: String,
+    first_seen: Option<SystemTime>,
+    last_seen: Option<SystemTime>,
+    /// The `<SSID>` elements started so far; only the first, the beacon, counts.
+    ssids: usize,
+    essid: Option<Ssid>,
+    encryption: Vec<String>,
+    max_rate: Option<f64>,
+    bssid: Option<MacAddress>,
+    channel: Option<u16>,
+    mhz: Option<u32>,
+    max_seen_rate: Option<i32>,
+    signal: Option<i32>,
+    min_signal: Option<i32>,
+    max_signal: Option<i32>,
+}
+
+impl Partial {
+    /// The record, if this was an access point or ad-hoc network with a BSSID.
+    fn finish(self) -> Option<KismetRecord> {
+        let mode = match self.kind.as_str() {
+            "infrastructure" => WirelessMode::Master,
+            "ad-hoc" => WirelessMode::AdHoc,
+            _ => return None,
+        };
+        let freq = match (self.mhz, self.channel) {
+            (Some(mhz), _) => Some(mhz as f64 * 1e6),
+            (None, Some(number)) => Channel::from_number(number).map(|channel| channel.hz()),
+            (None, None) => None,
+        };
+        // In b/s an i32 tops out a little over 2 Gb/s, short of the fastest HE rates.
+        let maxbitrate = match (self.max_seen_rate, self.max_rate) {
+            (Some(kbps), _) => Some(kbps.checked_mul(1000).unwrap_or(i32::MAX)),
+            (None, Some(mbps)) => Some((mbps * 1e6).round() as i32),
+            (None, None) => None,
+        };
+        Some(KismetRecord {
+            network: WirelessNetwork {
+                bssid: Some(self.bssid?),
+                stats: self.signal.map(|dbm| IwStats::new(IwQuality::from_dbm(dbm))),
+                max_quality: None,
+                maxbitrate,
+                freq,
+                key: None,
+                essid: self.essid,
+                mode: Some(mode),
+                security: parse_crypt(self.encryption.iter().map(String::as_str)),
+                ies: Vec::new(),
+            },
+            first_seen: self.first_seen,
+            last_seen: self.last_seen,
+            min_signal_dbm: self.min_signal,
+            max_signal_dbm: self.max_signal,
+        })
+    }
+}
+
+fn xml_error<E: Display>(err: E) -> Error {
+    Error::parse(CALL, err.to_string())
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, Error> {
+    match element.try_get_attribute(name).map_err(xml_error)? {
+        Some(attribute) => Ok(Some(attribute.unescape_value().map_err(xml_error)?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+fn time_attribute(element: &BytesStart, name: &str) -> Result<Option<SystemTime>, Error> {
+    match attribute(element, name)? {
+        Some(text) => {
+            let bad = || Error::parse(CALL, format!("bad {} '{}'", name, text));
+            Ok(Some(parse_time(&text).ok_or_else(bad)?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// `text` as a number, with 0, which Kismet writes for "don't know", as
+/// `None`.
+fn number<T: ::std::str::FromStr + Default + PartialEq>(what: &str, text: &str) -> Result<Option<T>, Error> {
+    let value: T = text.parse().map_err(|_| Error::parse(CALL, format!("bad {} '{}'", what, text)))?;
+    Ok(if value == T::default() { None } else { Some(value) })
+}
+
+/// Read the access points and ad-hoc networks out of a netxml detection run.
+pub fn read_netxml<R: BufRead>(input: R) -> Result<Vec<KismetRecord>, Error> {
+    let mut reader = Reader::from_reader(input);
+    reader.config_mut().trim_text(true);
+    let mut buf = Vec::new();
+    let mut records = Vec::new();
+    let mut network: Option<Partial> = None;
+    // The elements open inside the current <wireless-network>.
+    let mut path: Vec<String> = Vec::new();
+    loop {
+        let (element, empty) = match reader.read_event_into(&mut buf).map_err(xml_error)? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::Text(text) => {
+                if let Some(ref mut network) = network {
+                    text_in(network, &path, &text.unescape().map_err(xml_error)?)?;
+                }
+                buf.clear();
+                continue;
+            }
+            Event::CData(data) => {
+                if let Some(ref mut network) = network {
+                    text_in(network, &path, &String::from_utf8_lossy(&data))?;
+                }
+                buf.clear();
+                continue;
+            }
+            Event::End(_) => {
+                if path.pop().is_none() {
+                    if let Some(record) = network.take().and_then(Partial::finish) {
+                        records.push(record);
+                    }
+                }
+                buf.clear();
+                continue;
+            }
+            Event::Eof => break,
+            _ => {
+                buf.clear();
+                continue;
+            }
+        };
+        let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+        match network {
+            None if name == "wireless-network" => {
+                let partial = Partial {
+                    kind: attribute(&element, "type")?.unwrap_or_default(),
+                    first_seen: time_attribute(&element, "first-time")?,
+                    last_seen: time_attribute(&element, "last-time")?,
+                    ..Partial::default()
+                };
+                if empty {
+                    records.extend(partial.finish());
+                } else {
+                    network = Some(partial);
+                }
+            }
+            None => {}
+            Some(ref mut network) => {
+                if path.is_empty() && name == "SSID" {
+                    network.ssids += 1;
+                }
+                if network.ssids == 1 && path.len() == 1 && path[0] == "SSID" && name == "essid" {
+                    // A cloaked network's <essid> has no text to go with it.
+                    network.essid = Some(Ssid::truncated(b""));
+                }
+                if !empty {
+                    path.push(name);
+                }
+            }
+        }
+        buf.clear();
+    }
+    Ok(records)
+}
+
+/// Take in the text of the element at `path` inside a `<wireless-network>`.
+fn text_in(network: &mut Partial, path: &[String], text: &str) -> Result<(), Error> {
+    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+    match path[..] {
+        ["SSID", element] if network.ssids == 1 => match element {
+            "essid" => network.essid = Some(Ssid::truncated(text.as_bytes())),
+            "encryption" => network.encryption.push(text.to_string()),
+            "max-rate" => network.max_rate = number("max-rate", text)?,
+            _ => {}
+        },
+        ["BSSID"] => {
+            let bssid = text.parse().map_err(|_| Error::parse(CALL, format!("bad BSSID '{}'", text)))?;
+            network.bssid = Some(bssid);
+        }
+        ["channel"] => network.channel = number("channel", text)?,
+        // The frequency, then how many packets were seen on it.
+        ["freqmhz"] => network.mhz = number("freqmhz", text.split_whitespace().next().unwrap_or(""))?,
+        ["maxseenrate"] => network.max_seen_rate = number("maxseenrate", text)?,
+        ["snr-info", "last_signal_dbm"] => network.signal = number("last_signal_dbm", text)?,
+        ["snr-info", "min_signal_dbm"] => network.min_signal = number("min_signal_dbm", text)?,
+        ["snr-info", "max_signal_dbm"] => network.max_signal = number("max_signal_dbm", text)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// `time` the way Kismet writes it, ctime style: `Wed May  1 13:45:00 2024`,
+/// in UTC.
+fn format_time(time: SystemTime) -> String {
+    let secs = date::unix_seconds(time);
+    let days = secs.div_euclid(86400);
+    let (year, month, day) = date::civil_from_days(days);
+    let secs = secs.rem_euclid(86400);
+    format!("{} {} {:2} {:02}:{:02}:{:02} {}",
+            DAYS[(days + 4).rem_euclid(7) as usize],
+            MONTHS[month as usize - 1],
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            year)
+}
+
+fn parse_time(text: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    if parts.len() != 5 {
+        return None;
+    }
+    let month = MONTHS.iter().position(|&month| month == parts[1])? as i64 + 1;
+    let day: i64 = parts[2].parse().ok()?;
+    let year: i64 = parts[4].parse().ok()?;
+    let clock: Vec<i64> = parts[3].split(':').map(str::parse).collect::<Result<_, _>>().ok()?;
+    match clock[..] {
+        [hour, minute, second] => date::from_civil(year, month, day, hour, minute, second),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::time::{Duration, UNIX_EPOCH};
+    use {Band, WifiScan};
+    use super::super::Session;
+
+    fn sample() -> Vec<KismetRecord> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/kismet/sample.netxml");
+        read_netxml(BufReader::new(File::open(path).unwrap())).unwrap()
+    }
+
+    fn network(essid: &[u8], security: Security) -> WirelessNetwork {
+        WirelessNetwork {
+            bssid: Some(MacAddress::new([0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc])),
+            stats: Some(IwStats::new(IwQuality::from_dbm(-61))),
+            maxbitrate: Some(54000000),
+            freq: Some(5.18e9),
+            essid: Ssid::new(essid),
+            mode: Some(WirelessMode::Master),
+            security,
+            ..WirelessNetwork::default()
+        }
+    }
+
+    #[test]
+    fn reads_kismets_netxml() {
+        let records = sample();
+        assert_eq!(records.len(), 3);
+        let cafe = &records[0];
+        assert_eq!(cafe.network.bssid, "a4:2b:b0:11:22:33".parse().ok());
+        assert_eq!(cafe.network.essid, Ssid::new("Caf\u{e9} & Bar".as_bytes()));
+        // The client's channel and signal aren't the network's.
+        assert_eq!(cafe.network.channel().map(|channel| channel.number()), Some(6));
+        assert_eq!(cafe.network.signal_dbm(), Some(-58));
+        assert_eq!((cafe.min_signal_dbm, cafe.max_signal_dbm), (Some(-71), Some(-49)));
+        assert_eq!(cafe.network.maxbitrate, Some(54000000));
+        // Only the beacon's encryption counts, not the probe response's.
+        assert_eq!(cafe.network.security.protocol, Protocol::Wpa2);
+        assert_eq!(cafe.network.security.pairwise_ciphers, vec![CipherSuite::Ccmp128]);
+        assert_eq!(cafe.first_seen, Some(UNIX_EPOCH + Duration::from_secs(1564680007)));
+        assert_eq!(cafe.last_seen, Some(UNIX_EPOCH + Duration::from_secs(1564680704)));
+
+        let hidden = &records[1].network;
+        assert!(hidden.essid.unwrap().is_hidden());
+        assert_eq!(hidden.band(), Some(Band::FiveGhz));
+        assert_eq!(hidden.stats, None);
+        assert_eq!(hidden.security, Security::open());
+
+        let adhoc = &records[2].network;
+        assert_eq!(adhoc.essid, Ssid::new(b"printer-setup"));
+        assert_eq!(adhoc.mode, Some(WirelessMode::AdHoc));
+        assert_eq!(adhoc.security.protocol, Protocol::Wep);
+    }
+
+    #[test]
+    fn round_trips_a_session() {
+        let start = UNIX_EPOCH + Duration::from_secs(1714571100);
+        let wpa2 = Security {
+            protocol: Protocol::Wpa2,
+            group_cipher: None,
+            pairwise_ciphers: vec![CipherSuite::Ccmp128],
+            akm_suites: vec![AkmSuite::Psk],
+            pmf_required: false,
+            pmf_capable: false,
+            wpa_compatible: false,
+        };
+        let mut session = Session::new(start);
+        let hidden = WirelessNetwork {
+            bssid: "00:11:22:33:44:55".parse().ok(),
+            ..network(b"", Security::open())
+        };
+        let networks = vec![network(b"<caf\xc3\xa9> & \"bar\"", wpa2), hidden];
+        session.add_scan(&WifiScan { networks, stale: false }, start + Duration::from_secs(5));
+        let mut netxml = Vec::new();
+        session.write_netxml(&mut netxml).unwrap();
+        let text = String::from_utf8(netxml.clone()).unwrap();
+        assert!(text.contains("start-time=\"Wed May  1 13:45:00 2024\""));
+        assert!(text.contains("<BSSID>00:11:22:AA:BB:CC</BSSID>"));
+        assert!(text.contains("&lt;caf\u{e9}&gt; &amp; &quot;bar&quot;"));
+
+        let records = read_netxml(&netxml[..]).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], session.records()[0]);
+        assert!(records[1].network.essid.unwrap().is_hidden());
+    }
+
+    #[test]
+    fn converts_ctime_dates() {
+        for &secs in &[0u64, 951782400, 1564680007, 4107542399] {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            assert_eq!(parse_time(&format_time(time)), Some(time), "{}", format_time(time));
+        }
+        assert_eq!(format_time(UNIX_EPOCH), "Thu Jan  1 00:00:00 1970");
+        assert_eq!(format_time(UNIX_EPOCH + Duration::from_secs(951782400)), "Tue Feb 29 00:00:00 2000");
+        assert_eq!(parse_time("Thu Aug 1 17:20:07"), None);
+        assert_eq!(parse_time("Thu Aux  1 17:20:07 2019"), None);
+        assert_eq!(parse_time("Thu Feb 29 17:20:07 2019"), None);
+        assert_eq!(parse_time("Thu Jun 31 17:20:07 2019"), None);
+    }
+
+    #[test]
+    fn saturates_rates_past_an_i32() {
+        let netxml = "<detection-run><wireless-network type=\"infrastructure\">\
+                      <BSSID>00:11:22:AA:BB:CC</BSSID><maxseenrate>2402000</maxseenrate>\
+                      </wireless-network></detection-run>";
+        let records = read_netxml(netxml.as_bytes()).unwrap();
+        assert_eq!(records[0].network.maxbitrate, Some(i32::MAX));
+    }
+
+    #[test]
+    fn rejects_malformed_netxml() {
+        let wrap = |body: &str| format!("<detection-run>{}</detection-run>", body);
+        let bad_bssid = wrap("<wireless-network type=\"ad-hoc\"><BSSID>nope</BSSID></wireless-network>");
+        assert!(matches!(read_netxml(bad_bssid.as_bytes()), Err(Error::Parse { .. })));
+        let bad_time = wrap("<wireless-network type=\"infrastructure\" first-time=\"yesterday\"/>");
+        assert!(read_netxml(bad_time.as_bytes()).is_err());
+        assert!(read_netxml(wrap("<wireless-network></SSID>").as_bytes()).is_err());
+        let no_bssid = wrap("<wireless-network type=\"ad-hoc\"><channel>6</channel></wireless-network>");
+        assert!(read_netxml(no_bssid.as_bytes()).unwrap().is_empty());
+    }
+}
@@ -1,25 +1,66 @@
 extern crate libc;
-
-use std::net::{SocketAddrV4, SocketAddrV6};
-use std::ffi::{CString, CStr};
-use std::os::raw::c_char;
-use std::mem;
-use std::io::{Error, ErrorKind};
-use std::str;
-use std::fmt;
-use libc::*;
-
-const IW_AUTH_WPA_VERSION_DISABLED: u8 = 0x00000001;
-const IW_AUTH_WPA_VERSION_WPA: u8 = 0x00000002;
-const IW_AUTH_WPA_VERSION_WPA2: u8 = 0x00000004;
-const IW_MAX_BITRATES: usize = 32;
-const IW_MAX_ENCODING_SIZES: usize = 8;
-const IW_MAX_FREQUENCIES: usize = 32;
-const IW_MAX_TXPOWER: usize = 8;
-const IW_ESSID_MAX_SIZE: usize = 32;
-const IW_ENCODING_TOKEN_MAX: usize = 64;
-const IFNAMSIZ: usize = 16; // Defined in /include/uapi/linux/if.h but easier to just redefine here
-
+#[cfg(feature = "kismet")]
+extern crate quick_xml;
+#[cfg(feature = "kismetdb")]
+extern crate rusqlite;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(any(feature = "kismetdb", all(test, feature = "serde")))]
+extern crate serde_json;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+
+mod backend;
+mod channel;
+pub mod csv;
+mod date;
+mod error;
+pub mod ie;
+mod interface;
+#[cfg(feature = "libiw")]
+mod iwlib;
+#[cfg(feature = "kismet")]
+pub mod kismet;
+mod mac_address;
+mod monitor;
+pub mod nl80211;
+mod request;
+mod retry;
+mod scan_future;
+#[cfg(feature = "serde")]
+pub mod schema;
+mod security;
+mod ssid;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod wext;
+pub mod wigle;
+
+pub use backend::{FixtureBackend, PendingScan, ScanBackend};
+pub use channel::{Band, Channel};
+pub use error::Error;
+pub use interface::{default_interface, interfaces, interfaces_in, Interface, OperState};
+pub use mac_address::{MacAddress, ParseMacAddressError};
+pub use monitor::{Clock, ManualClock, MonitorEvents, ScanEvent, ScanMonitor, SystemClock};
+#[cfg(feature = "libiw")]
+pub use iwlib::IwlibBackend;
+pub use nl80211::Nl80211Backend;
+pub use request::ScanRequest;
+pub use retry::RetryPolicy;
+pub use scan_future::{CancelHandle, ScanFuture};
+pub use security::{Protocol, Security, WPA_OUI};
+pub use ssid::Ssid;
+pub use wext::WextBackend;
+pub use wext::capabilities::{CipherCapabilities, InterfaceCapabilities, PowerManagement,
+                             TxPower};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The operating mode of a network or interface. In scan results an access
+/// point shows up as `Master` and a peer in an ad-hoc cell as `AdHoc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum WirelessMode {
     Auto, /* Let the driver decide */
     AdHoc, /* Single cell network */
@@ -28,484 +69,603 @@ pub enum WirelessMode {
     Repeat, /* Wireless Repeater (forwarder) */
     Second, /* Secondary master/repeater (backup) */
     Monitor, /* Passive monitor (listen only) */
+    Mesh, /* Mesh (IEEE 802.11s) network */
+}
+
+impl WirelessMode {
+    /// The mode for one of the IW_MODE_* values, or `None` if it isn't one.
+    ///
+    /// ```
+    /// use dradis::WirelessMode;
+    ///
+    /// assert_eq!(WirelessMode::from_iw(1), Some(WirelessMode::AdHoc));
+    /// assert_eq!(WirelessMode::from_iw(3), Some(WirelessMode::Master));
+    /// assert_eq!(WirelessMode::from_iw(42), None);
+    /// ```
+    pub fn from_iw(mode: i32) -> Option<WirelessMode> {
+        match mode {
+            0 => Some(WirelessMode::Auto),
+            1 => Some(WirelessMode::AdHoc),
+            2 => Some(WirelessMode::Infra),
+            3 => Some(WirelessMode::Master),
+            4 => Some(WirelessMode::Repeat),
+            5 => Some(WirelessMode::Second),
+            6 => Some(WirelessMode::Monitor),
+            7 => Some(WirelessMode::Mesh),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Link quality as Wireless Extensions report it, a `struct iw_quality`.
+///
+/// What the raw bytes mean depends on the `updated` flags and on the
+/// interface's best values, `max_qual` from its `iw_range`: the level and noise
+/// can be relative to `max_qual`, dBm stored as an unsigned byte, or 802.11k
+/// RCPI. The `*_dbm` and `percent` methods sort this out given `max_qual`.
+///
+/// ```
+/// use dradis::IwQuality;
+///
+/// // A driver that reports dBm: -55 dBm signal and -95 dBm noise.
+/// let max = IwQuality::new(70, 0, 0, IwQuality::DBM);
+/// let quality = IwQuality::new(55, 201, 161, IwQuality::ALL_UPDATED | IwQuality::DBM);
+/// assert_eq!(quality.level_dbm(&max), Some(-55));
+/// assert_eq!(quality.noise_dbm(&max), Some(-95));
+/// assert_eq!(quality.snr(&max), Some(40));
+/// assert_eq!(quality.percent(&max), Some(78));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(C)]
 pub struct IwQuality {
     quality: u8,
     level: u8,
     noise: u8,
+    updated: u8,
 }
 
-#[derive(Debug, Clone, Copy)]
-#[repr(C)]
-pub struct IwStats {
-    status: uint16_t,
-    quality: IwQuality,
-}
+impl IwQuality {
+    pub const QUAL_UPDATED: u8 = 0x01;
+    pub const LEVEL_UPDATED: u8 = 0x02;
+    pub const NOISE_UPDATED: u8 = 0x04;
+    pub const ALL_UPDATED: u8 = 0x07;
+    /// Level and noise are in dBm.
+    pub const DBM: u8 = 0x08;
+    pub const QUAL_INVALID: u8 = 0x10;
+    pub const LEVEL_INVALID: u8 = 0x20;
+    pub const NOISE_INVALID: u8 = 0x40;
+    /// Level and noise are 802.11k RCPI, half dB steps from -110 dBm.
+    pub const RCPI: u8 = 0x80;
+    pub const ALL_INVALID: u8 = 0x70;
+
+    pub fn new(quality: u8, level: u8, noise: u8, updated: u8) -> IwQuality {
+        IwQuality {
+            quality,
+            level,
+            noise,
+            updated,
+        }
+    }
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct IwParam {
-    value: int32_t, /* The value of the parameter itself */
-    fixed: uint8_t, /* Hardware should not use auto select */
-    disabled: uint8_t, /* Disable the feature */
-    flags: uint16_t, /* Various specifc flags (if any) */
-}
+    /// A signal level of `dbm` as Wireless Extensions would report it, for
+    /// networks read back from files that only keep the level. The byte only
+    /// holds -192 to 63 dBm, so levels outside that are clamped to it.
+    pub(crate) fn from_dbm(dbm: i32) -> IwQuality {
+        let flags = IwQuality::LEVEL_UPDATED | IwQuality::DBM | IwQuality::QUAL_INVALID |
+                    IwQuality::NOISE_INVALID;
+        IwQuality::new(0, dbm.clamp(-192, 63) as u8, 0, flags)
+    }
 
-#[repr(C)]
-pub struct WirelessKey<'a> {
-    key: &'a [u8],
-    size: u32,
-    flags: i32,
-}
+    pub fn quality(&self) -> u8 {
+        self.quality
+    }
 
-/// The WirelessNetwork struct holds details about a single network,
-/// including ssid, encryption type, bitrate, and signal strength.
-#[repr(C)]
-pub struct WirelessNetwork<'a> {
-    pub ap_addr4: Option<SocketAddrV4>,
-    pub ap_addr6: Option<SocketAddrV6>,
-    pub stats: Option<IwStats>,
-    pub maxbitrate: Option<i32>,
-    pub freq: Option<f64>,
-    pub key: Option<WirelessKey<'a>>,
-    pub essid: Option<String>,
-    pub mode: Option<WirelessMode>,
-    pub encryption: String,
-}
+    pub fn level(&self) -> u8 {
+        self.level
+    }
 
-#[repr(C)]
-struct WirelessScanHead {
-    result: *const WirelessScan,
-    retry: c_int,
-}
+    pub fn noise(&self) -> u8 {
+        self.noise
+    }
 
-#[repr(C)]
-struct WirelessScan {
-    next: *const WirelessScan,
-    has_ap_addr: c_int,
-    ap_addr: sockaddr,
-    b: WirelessConfig,
-    stats: IwStats,
-    has_stats: c_int,
-    maxbitrate: IwParam,
-    has_maxbitrate: c_int,
-}
+    /// The IW_QUAL_* flags, the associated constants of this type.
+    pub fn updated(&self) -> u8 {
+        self.updated
+    }
 
-impl fmt::Debug for WirelessScan {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,
-               "WirelessScan {{next: {:?},
-    has_ap_addr: {:?},
-    ap_addr: sockaddr,
-    b: \
-                {:?},
-    stats: {:?},
-    has_stats: {:?},
-    maxbitrate: {:?},
-    \
-                has_maxbitrate: {:?}
- }}",
-               self.next,
-               self.has_ap_addr,
-               self.b,
-               self.stats,
-               self.has_stats,
-               self.maxbitrate,
-               self.has_maxbitrate)
+    /// The signal level in dBm, or `None` if it's invalid or only relative.
+    pub fn level_dbm(&self, max: &IwQuality) -> Option<i32> {
+        if self.updated & IwQuality::LEVEL_INVALID != 0 {
+            return None;
+        }
+        self.dbm(self.level, max.level)
     }
-}
 
-#[repr(C)]
-struct WirelessConfig {
-    name: [c_char; IFNAMSIZ + 1], /* Wireless/protocol name */
-    has_nwid: c_int,
-    nwid: IwParam, /* Network ID */
-    has_freq: c_int,
-    freq: f64, /* Frequency/channel */
-    freq_flags: c_int,
-    has_key: c_int,
-    key: [c_uchar; IW_ENCODING_TOKEN_MAX], /* Encoding key used */
-    key_size: c_int, /* Number of bytes */
-    key_flags: c_int, /* Various flags */
-    has_essid: c_int,
-    essid_on: c_int,
-    essid: [c_char; IW_ESSID_MAX_SIZE + 2], // ESSID (extended network)
-    essid_len: c_int,
-    has_mode: c_int,
-    mode: c_int, /* Operation mode */
-}
+    /// The noise level in dBm, or `None` if it's invalid or only relative.
+    pub fn noise_dbm(&self, max: &IwQuality) -> Option<i32> {
+        if self.updated & IwQuality::NOISE_INVALID != 0 {
+            return None;
+        }
+        self.dbm(self.noise, max.noise)
+    }
 
-impl fmt::Debug for WirelessConfig {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut better_essid = "[".to_string();
-        for &byte in self.essid.iter() {
-            better_essid.push_str(&format!("{:02X}, ", byte))
+    /// The signal to noise ratio in dB. When both are relative to `max` the
+    /// difference is still a usable margin, just not in dB.
+    pub fn snr(&self, max: &IwQuality) -> Option<i32> {
+        match (self.level_dbm(max), self.noise_dbm(max)) {
+            (Some(level), Some(noise)) => Some(level - noise),
+            _ => None,
         }
-        better_essid.push_str("]");
-        let mut better_key = "[".to_string();
-        for &byte in self.key.iter() {
-            better_key.push_str(&format!("{:02X}, ", byte))
+    }
+
+    /// The quality as a percentage, for display. Uses the quality byte scaled
+    /// by `max.quality` when there is one, otherwise the signal level: dBm is
+    /// mapped linearly from -100 (0%) to -50 (100%), relative levels are scaled
+    /// by `max.level`.
+    pub fn percent(&self, max: &IwQuality) -> Option<u8> {
+        if self.updated & IwQuality::QUAL_INVALID == 0 && max.quality > 0 {
+            return Some(scale(self.quality as i32, max.quality as i32));
+        }
+        if let Some(dbm) = self.level_dbm(max) {
+            return Some(scale(dbm + 100, 50));
         }
-        better_key.push_str("]");
-
-        write!(f,
-               "WirelessConfig {{name: {:?},
-                has_nwid: {:?},
-                \
-                nwid: {:?},
-                has_freq: {:?},
-                freq: {:?},
-                \
-                freq_flags: {:?},
-                has_key: {:?},
-                key: {:?},
-                \
-                key_size: {:?},
-                key_flags: {:?},
-                has_essid: {:?},
-                \
-                essid_on: {:?},
-                essid: {:?},
-                essid_len: {:?},
-                has_mode: {:?},
-                \
-                mode: {:?},
-                }}",
-               self.name,
-               self.has_nwid,
-               self.nwid,
-               self.has_freq,
-               self.freq,
-               self.freq_flags,
-               self.has_key,
-               better_key,
-               self.key_size,
-               self.key_flags,
-               self.has_essid,
-               self.essid_on,
-               better_essid,
-               self.essid_len,
-               self.has_mode,
-               self.mode)
+        if self.updated & IwQuality::LEVEL_INVALID == 0 && max.level > 0 {
+            return Some(scale(self.level as i32, max.level as i32));
+        }
+        None
     }
+
+    fn dbm(&self, value: u8, max: u8) -> Option<i32> {
+        if self.updated & IwQuality::RCPI != 0 {
+            return Some(value as i32 / 2 - 110);
+        }
+        // Like libiw, treat a level above the relative maximum as dBm even
+        // when the driver didn't set IW_QUAL_DBM.
+        if self.updated & IwQuality::DBM != 0 || value > max {
+            // dBm travel as (256 + dBm); anything from 64 up is negative.
+            return Some(if value >= 64 { value as i32 - 0x100 } else { value as i32 });
+        }
+        None
+    }
+}
+
+fn scale(value: i32, max: i32) -> u8 {
+    (value * 100 / max).clamp(0, 100) as u8
 }
 
+/// Interface statistics, laid out like the kernel's `struct iw_statistics`
+/// so libiw's scan results can be read in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(C)]
-struct priv_iw_quality {
-    qual: uint8_t,
-    level: uint8_t,
-    noise: uint8_t,
-    updated: uint8_t,
+pub struct IwStats {
+    status: u16,
+    quality: IwQuality,
+    discard: [u32; 5], /* Packets discarded: nwid, code, fragment, retries, misc */
+    miss: u32, /* Missed beacons */
 }
 
-impl Default for priv_iw_quality {
-    fn default() -> priv_iw_quality {
-        priv_iw_quality {
-            qual: 0,
-            level: 0,
-            noise: 0,
-            updated: 0,
+impl IwStats {
+    pub fn new(quality: IwQuality) -> IwStats {
+        IwStats {
+            status: 0,
+            quality,
+            discard: [0; 5],
+            miss: 0,
         }
     }
+
+    pub fn quality(&self) -> IwQuality {
+        self.quality
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
-struct priv_iw_freq {
-    m: int32_t,
-    e: int16_t,
-    i: uint8_t,
-    flags: uint8_t,
+pub struct IwParam {
+    value: i32, /* The value of the parameter itself */
+    fixed: u8, /* Hardware should not use auto select */
+    disabled: u8, /* Disable the feature */
+    flags: u16, /* Various specifc flags (if any) */
+}
+
+/// What the driver said about a network's encryption key: its IW_ENCODE_*
+/// flags and the key itself, which drivers only hand over for networks
+/// they're configured for, and usually not even then.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WirelessKey {
+    #[cfg_attr(feature = "serde", serde(with = "schema::hex"))]
+    key: Vec<u8>,
+    flags: u16,
 }
 
-impl Default for priv_iw_freq {
-    fn default() -> priv_iw_freq {
-        priv_iw_freq {
-            m: 0,
-            e: 0,
-            i: 0,
-            flags: 0,
+impl WirelessKey {
+    pub fn new(key: &[u8], flags: u16) -> WirelessKey {
+        WirelessKey {
+            key: key.to_vec(),
+            flags,
         }
     }
+
+    /// The key, empty if the driver didn't give it out.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// The IW_ENCODE_* flags.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// True if the network uses encryption at all.
+    pub fn is_enabled(&self) -> bool {
+        wext::privacy_from_key_flags(self.flags)
+    }
 }
 
-#[repr(C)]
-struct iw_range {
-    /* Informative stuff (to choose between different interface) */
-    throughput: uint32_t, /* To give an idea... */
-    /* In theory this value should be the maximum benchmarked
-     * TCP/IP throughput, because with most of these devices the
-     * bit rate is meaningless (overhead an co) to estimate how
-     * fast the connection will go and pick the fastest one.
-     * I suggest people to play with Netperf or any benchmark...
-     */
-
-    /* NWID (or domain id) */
-    min_nwid: uint32_t, /* Minimal NWID we are able to set */
-    max_nwid: uint32_t, /* Maximal NWID we are able to set */
-
-    /* Old Frequency (backward compat - moved lower ) */
-    old_num_channels: uint16_t,
-    old_num_frequency: uint8_t,
-
-    /* Wireless event capability bitmasks */
-    event_capa: [uint32_t; 6],
-
-    /* signal level threshold range */
-    sensitivity: int32_t,
-
-    /* Quality of link & SNR stuff */
-    /* Quality range (link, level, noise)
-     * If the quality is absolute, it will be in the range [0 , max_qual],
-     * if the quality is dBm, it will be in the range [max_qual , 0].
-     * Don't forget that we use 8 bit arithmetics... */
-    max_qual: priv_iw_quality, /* Quality of the link */
-    /* This should contain the average/typical values of the quality
-     * indicator. This should be the threshold between a "good" and
-     * a "bad" link (example : monitor going from green to orange).
-     * Currently, user space apps like quality monitors don't have any
-     * way to calibrate the measurement. With this, they can split
-     * the range between 0 and max_qual in different quality level
-     * (using a geometric subdivision centered on the average).
-     * I expect that people doing the user space apps will feedback
-     * us on which value we need to put in each driver... */
-    avg_qual: priv_iw_quality, /* Quality of the link */
-
-    /* Rates */
-    num_bitrates: uint8_t, /* Number of entries in the list */
-    bitrate: [int32_t; IW_MAX_BITRATES], /* list, in bps */
-
-    /* RTS threshold */
-    min_rts: int32_t, /* Minimal RTS threshold */
-    max_rts: int32_t, /* Maximal RTS threshold */
-
-    /* Frag threshold */
-    min_frag: int32_t, /* Minimal frag threshold */
-    max_frag: int32_t, /* Maximal frag threshold */
-
-    /* Power Management duration & timeout */
-    min_pmp: int32_t, /* Minimal PM period */
-    max_pmp: int32_t, /* Maximal PM period */
-    min_pmt: int32_t, /* Minimal PM timeout */
-    max_pmt: int32_t, /* Maximal PM timeout */
-    pmp_flags: uint16_t, /* How to decode max/min PM period */
-    pmt_flags: uint16_t, /* How to decode max/min PM timeout */
-    pm_capa: uint16_t, /* What PM options are supported */
-
-    /* Encoder stuff */
-    encoding_size: [uint16_t; IW_MAX_ENCODING_SIZES], /* Different token sizes */
-    num_encoding_sizes: uint8_t, /* Number of entry in the list */
-    max_encoding_tokens: uint8_t, /* Max number of tokens */
-    /* For drivers that need a "login/passwd" form */
-    encoding_login_index: uint8_t, /* token index for login token */
-
-    /* Transmit power */
-    txpower_capa: uint16_t, /* What options are supported */
-    num_txpower: uint8_t, /* Number of entries in the list */
-    txpower: [int32_t; IW_MAX_TXPOWER], /* list, in bps */
-
-    /* Wireless Extension version info */
-    we_version_compiled: uint8_t, /* Must be WIRELESS_EXT */
-    we_version_source: uint8_t, /* Last update of source */
-
-    /* Retry limits and lifetime */
-    retry_capa: uint16_t, /* What retry options are supported */
-    retry_flags: uint16_t, /* How to decode max/min retry limit */
-    r_time_flags: uint16_t, /* How to decode max/min retry life */
-    min_retry: int32_t, /* Minimal number of retries */
-    max_retry: int32_t, /* Maximal number of retries */
-    min_r_time: int32_t, /* Minimal retry lifetime */
-    max_r_time: int32_t, /* Maximal retry lifetime */
-
-    /* Frequency */
-    num_channels: uint16_t, /* Number of channels [0, num - 1] */
-    num_frequency: uint8_t, /* Number of entry in the list */
-    freq: [priv_iw_freq; IW_MAX_FREQUENCIES], /* list */
-    /* Note : this frequency list doesn't need to fit channel numbers,
-     * because each entry contain its channel index */
-    enc_capa: uint32_t, /* IW_ENC_CAPA_* bit field */
-
-    /* More power management stuff */
-    min_pms: int32_t, /* Minimal PM saving */
-    max_pms: int32_t, /* Maximal PM saving */
-    pms_flags: int16_t, /* How to decode max/min PM saving */
-
-    /* All available modulations for driver (hw may support less) */
-    modul_capa: uint32_t, /* IW_MODUL_* bit field */
-
-    /* More bitrate stuff */
-    bitrate_capa: uint32_t, /* Types of bitrates supported */
+/// The WirelessNetwork struct holds details about a single network,
+/// including ssid, security, bitrate, and signal strength. It owns all of its
+/// data, so networks can be kept, sent to other threads and compared. The
+/// default is an open network nothing else is known about.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WirelessNetwork {
+    pub bssid: Option<MacAddress>,
+    /// The signal as the driver reported it, `None` if it didn't.
+    pub stats: Option<IwStats>,
+    /// The `max_qual` of the interface that saw the network, which `stats` is
+    /// relative to. `None` means the backend didn't know it.
+    pub max_quality: Option<IwQuality>,
+    /// The fastest bitrate the network offers, in bits per second. Backends
+    /// only know the legacy 802.11a/b/g rates, so this tops out at 54 Mb/s.
+    pub maxbitrate: Option<i32>,
+    /// The center frequency in Hz.
+    pub freq: Option<f64>,
+    /// The encryption key details Wireless Extensions reported. nl80211 has
+    /// no equivalent; see `security` instead.
+    pub key: Option<WirelessKey>,
+    /// The network name as broadcast, which may be hidden.
+    pub essid: Option<Ssid>,
+    pub mode: Option<WirelessMode>,
+    pub security: Security,
+    /// The raw information elements from the network's beacon or probe
+    /// response, empty if the backend didn't report any.
+    #[cfg_attr(feature = "serde", serde(with = "schema::hex"))]
+    pub ies: Vec<u8>,
 }
 
-impl Default for iw_range {
-    fn default() -> iw_range {
-        iw_range {
-            throughput: 0,
-            min_nwid: 0,
-            max_nwid: 0,
-            old_num_channels: 0,
-            old_num_frequency: 0,
-            event_capa: [0; 6],
-            sensitivity: 0,
-            max_qual: Default::default(), /* Quality of the link */
-            avg_qual: Default::default(), /* Quality of the link */
-            num_bitrates: 0, /* Number of entries in the list */
-            bitrate: [0; IW_MAX_BITRATES], /* list, in bps */
-            min_rts: 0, /* Minimal RTS threshold */
-            max_rts: 0, /* Maximal RTS threshold */
-            min_frag: 0, /* Minimal frag threshold */
-            max_frag: 0, /* Maximal frag threshold */
-            min_pmp: 0, /* Minimal PM period */
-            max_pmp: 0, /* Maximal PM period */
-            min_pmt: 0, /* Minimal PM timeout */
-            max_pmt: 0, /* Maximal PM timeout */
-            pmp_flags: 0, /* How to decode max/min PM period */
-            pmt_flags: 0, /* How to decode max/min PM timeout */
-            pm_capa: 0, /* What PM options are supported */
-            encoding_size: [0; IW_MAX_ENCODING_SIZES], /* Different token sizes */
-            num_encoding_sizes: 0, /* Number of entry in the list */
-            max_encoding_tokens: 0, /* Max number of tokens */
-            encoding_login_index: 0, /* token index for login token */
-            txpower_capa: 0, /* What options are supported */
-            num_txpower: 0, /* Number of entries in the list */
-            txpower: [0; IW_MAX_TXPOWER], /* list, in bps */
-            we_version_compiled: 0, /* Must be WIRELESS_EXT */
-            we_version_source: 0, /* Last update of source */
-            retry_capa: 0, /* What retry options are supported */
-            retry_flags: 0, /* How to decode max/min retry limit */
-            r_time_flags: 0, /* How to decode max/min retry life */
-            min_retry: 0, /* Minimal number of retries */
-            max_retry: 0, /* Maximal number of retries */
-            min_r_time: 0, /* Minimal retry lifetime */
-            max_r_time: 0, /* Maximal retry lifetime */
-            num_channels: 0, /* Number of channels [0, num - 1] */
-            num_frequency: 0, /* Number of entry in the list */
-            freq: [Default::default(); IW_MAX_FREQUENCIES], /* list */
-            enc_capa: 0, /* IW_ENC_CAPA_* bit field */
-            min_pms: 0, /* Minimal PM saving */
-            max_pms: 0, /* Maximal PM saving */
-            pms_flags: 0, /* How to decode max/min PM saving */
-            modul_capa: 0, /* IW_MODUL_* bit field */
-            bitrate_capa: 0, /* Types of bitrates supported */
+impl WirelessNetwork {
+    /// Decode the network's information elements.
+    pub fn elements(&self) -> ie::Elements<'_> {
+        ie::Elements::new(&self.ies)
+    }
+
+    /// The network name as text, if the network says its SSID is UTF-8 with
+    /// the UTF-8 SSID extended capability and it really is.
+    pub fn ssid_utf8(&self) -> Option<&str> {
+        let utf8 = self.elements().any(|element| match element {
+            ie::Element::ExtendedCapabilities(caps) => caps.has(ie::ExtendedCapabilities::UTF8_SSID),
+            _ => false,
+        });
+        if !utf8 {
+            return None;
         }
+        self.essid.as_ref().and_then(|ssid| ssid.as_str())
+    }
+
+    /// The channel `freq` is the center of, if it's a known 802.11 channel.
+    pub fn channel(&self) -> Option<Channel> {
+        self.freq.and_then(Channel::from_hz)
+    }
+
+    pub fn band(&self) -> Option<Band> {
+        self.channel().map(|channel| channel.band())
+    }
+
+    /// The signal level in dBm, if the backend reported one.
+    pub fn signal_dbm(&self) -> Option<i32> {
+        self.quality().and_then(|(quality, max)| quality.level_dbm(&max))
+    }
+
+    /// The noise level in dBm, if the backend reported one.
+    pub fn noise_dbm(&self) -> Option<i32> {
+        self.quality().and_then(|(quality, max)| quality.noise_dbm(&max))
+    }
+
+    /// The signal to noise ratio in dB.
+    pub fn snr(&self) -> Option<i32> {
+        self.quality().and_then(|(quality, max)| quality.snr(&max))
+    }
+
+    /// The link quality from 0 to 100, as `IwQuality::percent` works it out.
+    pub fn signal_percent(&self) -> Option<u8> {
+        self.quality().and_then(|(quality, max)| quality.percent(&max))
     }
-}
 
-#[link(name="iw")]
-extern "C" {
-    fn iw_sockets_open() -> c_int;
-    fn iw_get_range_info(socket: c_int, interface: *mut c_char, range: &iw_range) -> c_int;
-    fn iw_scan(socket: c_int,
-               interface: *mut c_char,
-               version: c_int,
-               head: *mut WirelessScanHead)
-               -> c_int;
+    /// The network's quality and the maximum it's relative to. Without a
+    /// maximum only a level in dBm or RCPI means anything.
+    fn quality(&self) -> Option<(IwQuality, IwQuality)> {
+        let quality = self.stats?.quality;
+        match self.max_quality {
+            Some(max) => Some((quality, max)),
+            None if quality.updated & (IwQuality::DBM | IwQuality::RCPI) != 0 => {
+                Some((quality, IwQuality::default()))
+            }
+            None => None,
+        }
+    }
 }
 
 /// The WifiScan struct is the base object for the dradis library.
 /// This struct runs the scan when created and consists of an array of available networks.
-pub struct WifiScan<'a> {
-    pub networks: Vec<WirelessNetwork<'a>>,
+///
+/// With the `serde` feature it serializes in the versioned format `schema`
+/// describes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "schema::ScanDocument", try_from = "schema::ScanDocument"))]
+pub struct WifiScan {
+    pub networks: Vec<WirelessNetwork>,
+    /// True if the networks weren't scanned for just now but are what the
+    /// kernel kept from an earlier scan, which may be minutes old.
+    pub stale: bool,
 }
 
-impl<'a> WifiScan<'a> {
+impl WifiScan {
     /// Run a scan of the local wifi networks and return a Result with an error or
     /// a WifiScan instance that contains a `Vec<WirelessNetwork>` called `networks`.
     /// `interface` is a `String` containing the name of the wireless interface to be scanned.
     ///
-    /// ```
+    /// ```no_run
     /// use dradis::WifiScan;
     ///
     /// let local_networks = match WifiScan::scan("wlan0".to_string()) {
     ///     Ok(scan) => scan,
     ///     Err(err) => panic!("Failed to scan for wireless networks: {}", err)
-    /// }
+    /// };
     ///
-    /// local_networks.networks.map(|network| {
-    ///                             println!("{}: Encryption: {}", network.essid,
-    ///                             network.encryption); });
+    /// for network in local_networks.networks {
+    ///     println!("{:?}: Security: {}", network.essid, network.security);
+    /// }
     /// ```
     ///
-    pub fn scan(interface: String) -> Result<WifiScan<'a>, Error> {
-        // Scan things here
-        let mut list = Vec::new();
-        // First get an iw socket.
-        let sock = unsafe { iw_sockets_open() };
-        let interface_name = CString::new(interface).unwrap();
-        let range: iw_range = Default::default();
-        let head: *mut WirelessScanHead;
-        unsafe {
-            head = mem::uninitialized();
-        }
-        if unsafe { iw_get_range_info(sock, interface_name.as_ptr() as *mut c_char, &range) < 0 } {
-            // We have to make this call in order to get the version of the library on the computer
-            return Err(Error::new(ErrorKind::InvalidData, "Got an error from the iw library"));
-        }
-        if unsafe {
-            iw_scan(sock,
-                    interface_name.as_ptr() as *mut c_char,
-                    range.we_version_compiled as c_int,
-                    head) < 0
-        } {
-            // This is the actual scan call that fills in the `head` struct with information about the visible networks.
-            return Err(Error::new(ErrorKind::InvalidData, "Got an error from the iw library"));
-        }
+    pub fn scan(interface: String) -> Result<WifiScan, Error> {
+        WifiScan::scan_with(&mut WextBackend::new(), &interface)
+    }
 
-        let mut result = unsafe { (*head).result };
-        while !result.is_null() {
-            // The scan results are a linked list of structs with a bunch of information about each network
-            // The type of encryption is encoded in a bitflag called `key_flags` which we check by doing
-            // a bitwise and against the known bitflags.
-            unsafe {
-                let answer =
-                    if (*result).b.key_flags & IW_AUTH_WPA_VERSION_DISABLED as c_int > 0 {
-                        "None".to_string()
-                    } else if (*result).b.key_flags & IW_AUTH_WPA_VERSION_WPA as c_int > 0 {
-                        "WPA".to_string()
-                    } else if (*result).b.key_flags & IW_AUTH_WPA_VERSION_WPA2 as c_int > 0 {
-                        "WPA2".to_string()
-                    } else {
-                        "Error".to_string()
-                    };
-                let network_name;
-                if (*result).b.has_essid == 1 {
-                    let u8slice: [u8; 34] = mem::transmute((*result).b.essid);
-                    //let mut ssid_string = CStr::new("data");
-                    let ssid_string =
-                        match CStr::from_bytes_with_nul(u8slice.split_at(u8slice.into_iter()
-                                .position(|&byte| byte == 0x0)
-                                .unwrap() + 1)
-                            .0) { 
-                            Ok(good_string) => good_string,
-                            Err(err) => panic!("Could not parse essid string: {}", err),
-                        };
-                    network_name = Some(ssid_string.to_string_lossy().into_owned());
-                } else {
-                    network_name = None;
-                }
-                list.push(WirelessNetwork {
-                    ap_addr4: None,
-                    ap_addr6: None,
-                    maxbitrate: None,
-                    freq: None,
-                    key: None,
-                    mode: None,
-                    essid: network_name,
-                    encryption: answer,
-                    stats: Some((*result).stats.clone()),
-                });
-                result = (*result).next;
+    /// Scan on the interface `default_interface` picks from `interfaces()`,
+    /// for callers that don't care which one is used.
+    pub fn scan_default() -> Result<WifiScan, Error> {
+        let list = interfaces()?;
+        match default_interface(&list) {
+            Some(interface) => WifiScan::scan(interface.name.clone()),
+            None => {
+                Err(Error::NoSuchInterface {
+                    call: "interfaces",
+                    errno: None,
+                })
             }
         }
-        Ok(WifiScan { networks: list })
+    }
+
+    /// Run a scan of `interface` through the given `ScanBackend` instead of the
+    /// default Wireless Extensions ioctls. Use a `FixtureBackend` to get canned results without
+    /// any wireless hardware.
+    ///
+    /// ```
+    /// use dradis::{FixtureBackend, WifiScan};
+    ///
+    /// let mut backend = FixtureBackend::new(Vec::new());
+    /// let scan = WifiScan::scan_with(&mut backend, "wlan0").unwrap();
+    /// assert!(scan.networks.is_empty());
+    /// ```
+    pub fn scan_with<B: ScanBackend + ?Sized>(backend: &mut B,
+                                              interface: &str)
+                                              -> Result<WifiScan, Error> {
+        WifiScan::scan_request_with(backend, &ScanRequest::new(interface))
+    }
+
+    /// Run the targeted scan `request` describes with the Wireless Extensions
+    /// ioctls.
+    pub fn scan_request(request: &ScanRequest) -> Result<WifiScan, Error> {
+        WifiScan::scan_request_with(&mut WextBackend::new(), request)
+    }
+
+    /// Run the targeted scan `request` describes through `backend`, retrying
+    /// as `request.retry` says if the driver is busy.
+    ///
+    /// ```
+    /// use dradis::{FixtureBackend, ScanRequest, WifiScan};
+    ///
+    /// let mut backend = FixtureBackend::new(Vec::new());
+    /// let request = ScanRequest::new("wlan0").frequency(2412).passive(true);
+    /// let scan = WifiScan::scan_request_with(&mut backend, &request).unwrap();
+    /// assert!(scan.networks.is_empty());
+    /// ```
+    pub fn scan_request_with<B: ScanBackend + ?Sized>(backend: &mut B,
+                                                      request: &ScanRequest)
+                                                      -> Result<WifiScan, Error> {
+        retry::scan(backend, request, &SystemClock)
+    }
+
+    /// Read the results the kernel kept from the last scan of `interface`,
+    /// without triggering a new one. Most drivers allow this without
+    /// CAP_NET_ADMIN. The scan is always `stale`.
+    ///
+    /// ```no_run
+    /// use dradis::WifiScan;
+    ///
+    /// let scan = WifiScan::cached("wlan0").unwrap();
+    /// println!("{} networks seen earlier", scan.networks.len());
+    /// ```
+    pub fn cached(interface: &str) -> Result<WifiScan, Error> {
+        WifiScan::cached_with(&mut WextBackend::new(), interface)
+    }
+
+    /// Read the cached results of the last scan of `interface` through
+    /// `backend`.
+    pub fn cached_with<B: ScanBackend + ?Sized>(backend: &mut B,
+                                                interface: &str)
+                                                -> Result<WifiScan, Error> {
+        let networks = backend.cached_results(&ScanRequest::new(interface))?;
+        Ok(WifiScan {
+            networks,
+            stale: true,
+        })
+    }
+
+    /// Scan `interface` without blocking: the returned future triggers the
+    /// scan right away and resolves once the results are in.
+    pub fn scan_async(interface: &str) -> ScanFuture {
+        WifiScan::scan_async_with(&mut WextBackend::new(), interface)
+    }
+
+    /// Like `scan_async`, but using `backend` to do the scan. For a targeted
+    /// scan, build the future with `ScanFuture::new` and a `ScanRequest`.
+    ///
+    /// ```
+    /// use dradis::{FixtureBackend, WifiScan};
+    ///
+    /// let mut backend = FixtureBackend::new(Vec::new());
+    /// let future = WifiScan::scan_async_with(&mut backend, "wlan0");
+    /// // Hand this to whatever should be able to stop the scan.
+    /// let cancel = future.cancel_handle();
+    /// cancel.cancel();
+    /// ```
+    pub fn scan_async_with<B: ScanBackend + ?Sized>(backend: &mut B, interface: &str) -> ScanFuture {
+        ScanFuture::new(backend, &ScanRequest::new(interface))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {}
+
+    #[test]
+    fn relative_quality() {
+        // An old Prism driver: everything out of 92, no dBm.
+        let max = IwQuality::new(92, 92, 92, 0);
+        let quality = IwQuality::new(46, 60, 20, IwQuality::ALL_UPDATED);
+        assert_eq!(quality.level_dbm(&max), None);
+        assert_eq!(quality.snr(&max), None);
+        assert_eq!(quality.percent(&max), Some(50));
+        let no_qual = IwQuality::new(0, 46, 0, IwQuality::LEVEL_UPDATED | IwQuality::QUAL_INVALID);
+        assert_eq!(no_qual.percent(&max), Some(50));
+    }
+
+    #[test]
+    fn dbm_without_flag_above_relative_max() {
+        // Drivers that predate IW_QUAL_DBM give dBm levels above max_qual.
+        let max = IwQuality::new(100, 0, 0, 0);
+        let quality = IwQuality::new(0, 201, 0, IwQuality::QUAL_INVALID | IwQuality::NOISE_INVALID);
+        assert_eq!(quality.level_dbm(&max), Some(-55));
+        assert_eq!(quality.noise_dbm(&max), None);
+        assert_eq!(quality.percent(&max), Some(90));
+    }
+
+    #[test]
+    fn from_dbm_clamps() {
+        let max = IwQuality::default();
+        for &(dbm, expected) in &[(-55, -55), (0, 0), (20, 20), (63, 63), (64, 63), (-1, -1), (-192, -192),
+                                  (-193, -192), (-300, -192), (i32::MAX, 63)] {
+            assert_eq!(IwQuality::from_dbm(dbm).level_dbm(&max), Some(expected), "{}", dbm);
+        }
+    }
+
+    #[test]
+    fn rcpi() {
+        let max = IwQuality::default();
+        let quality = IwQuality::new(0, 110, 20, IwQuality::RCPI | IwQuality::QUAL_INVALID);
+        assert_eq!(quality.level_dbm(&max), Some(-55));
+        assert_eq!(quality.noise_dbm(&max), Some(-100));
+        assert_eq!(quality.snr(&max), Some(45));
+    }
+
+    #[test]
+    fn percent_is_clamped() {
+        let max = IwQuality::new(70, 0, 0, IwQuality::DBM);
+        let strong = IwQuality::new(90, 226, 0, IwQuality::ALL_UPDATED | IwQuality::DBM);
+        assert_eq!(strong.percent(&max), Some(100));
+        let weak = IwQuality::new(0, 150, 0, IwQuality::DBM | IwQuality::QUAL_INVALID);
+        assert_eq!(weak.level_dbm(&max), Some(-106));
+        assert_eq!(weak.percent(&max), Some(0));
+        assert_eq!(IwQuality::new(0, 0, 0, IwQuality::ALL_INVALID).percent(&max), None);
+    }
+
+    #[test]
+    fn network_uses_max_quality() {
+        let network = WirelessNetwork {
+            bssid: None,
+            stats: Some(IwStats::new(IwQuality::new(35, 60, 0, IwQuality::ALL_UPDATED))),
+            max_quality: Some(IwQuality::new(70, 100, 100, 0)),
+            maxbitrate: None,
+            freq: None,
+            key: None,
+            essid: None,
+            mode: None,
+            security: Security::open(),
+            ies: Vec::new(),
+        };
+        assert_eq!(network.signal_percent(), Some(50));
+        assert_eq!(network.signal_dbm(), None);
+        // Without a maximum a relative level means nothing, but dBm still do.
+        let unknown_max = WirelessNetwork { max_quality: None, ..network.clone() };
+        assert_eq!((unknown_max.signal_dbm(), unknown_max.signal_percent()), (None, None));
+        let dbm = IwQuality::new(0, (-60i32) as u8, 0, IwQuality::DBM);
+        let absolute = WirelessNetwork { stats: Some(IwStats::new(dbm)), ..unknown_max };
+        assert_eq!((absolute.signal_dbm(), absolute.signal_percent()), (Some(-60), Some(80)));
+        assert_eq!(WirelessNetwork { stats: None, ..network }.signal_percent(), None);
+    }
+
+    #[test]
+    fn ssid_is_utf8_only_when_advertised() {
+        let network = WirelessNetwork {
+            bssid: None,
+            stats: None,
+            max_quality: None,
+            maxbitrate: None,
+            freq: None,
+            key: None,
+            essid: Ssid::new("caf\u{e9}".as_bytes()),
+            mode: None,
+            security: Security::open(),
+            ies: vec![0x7f, 0x07, 0, 0, 0, 0, 0, 0, 0x01],
+        };
+        assert_eq!(network.ssid_utf8(), Some("caf\u{e9}"));
+        assert_eq!(WirelessNetwork { ies: Vec::new(), ..network.clone() }.ssid_utf8(), None);
+        let latin1 = WirelessNetwork { essid: Ssid::new(b"caf\xe9"), ..network };
+        assert_eq!(latin1.ssid_utf8(), None);
+    }
+
+    #[test]
+    fn scans_are_owned() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<WifiScan>();
+        assert_send_sync::<ScanEvent>();
+
+        let network = WirelessNetwork {
+            bssid: None,
+            stats: None,
+            max_quality: None,
+            maxbitrate: None,
+            freq: Some(2.412e9),
+            key: None,
+            essid: Ssid::new(b"home"),
+            mode: Some(WirelessMode::Master),
+            security: Security::open(),
+            ies: Vec::new(),
+        };
+        let scan = WifiScan {
+            networks: vec![network.clone()],
+            stale: false,
+        };
+        let moved = ::std::thread::spawn(move || scan).join().unwrap();
+        assert_eq!(moved.networks, vec![network]);
+        assert!(format!("{:?}", moved).contains("Ssid(\"home\")"));
+    }
 }
@@ -0,0 +1,181 @@
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A 48-bit IEEE 802 MAC address, such as the BSSID of an access point.
+///
+/// ```
+/// use dradis::MacAddress;
+///
+/// let bssid: MacAddress = "00:11:22:AA:BB:CC".parse().unwrap();
+/// assert_eq!(bssid.to_string(), "00:11:22:aa:bb:cc");
+/// assert_eq!(bssid.oui(), [0x00, 0x11, 0x22]);
+/// assert!(!bssid.is_locally_administered());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct MacAddress([u8; 6]);
+
+impl MacAddress {
+    pub fn new(bytes: [u8; 6]) -> MacAddress {
+        MacAddress(bytes)
+    }
+
+    /// Build an address from the first six bytes of `bytes`, or `None` if there
+    /// are fewer than six.
+    pub fn from_slice(bytes: &[u8]) -> Option<MacAddress> {
+        if bytes.len() < 6 {
+            return None;
+        }
+        let mut addr = [0u8; 6];
+        addr.copy_from_slice(&bytes[..6]);
+        Some(MacAddress(addr))
+    }
+
+    pub fn bytes(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// The Organizationally Unique Identifier, the first three bytes. Only
+    /// meaningful for universally administered addresses.
+    pub fn oui(&self) -> [u8; 3] {
+        [self.0[0], self.0[1], self.0[2]]
+    }
+
+    /// True if the address was assigned locally rather than by the
+    /// manufacturer, as with randomized addresses and most virtual APs.
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+
+    /// True if the address was assigned by the manufacturer under its OUI.
+    pub fn is_universal(&self) -> bool {
+        !self.is_locally_administered()
+    }
+
+    /// True if this is a group address. Access points always have unicast BSSIDs.
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    pub fn is_unicast(&self) -> bool {
+        !self.is_multicast()
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [0xff; 6]
+    }
+}
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(bytes: [u8; 6]) -> MacAddress {
+        MacAddress(bytes)
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = &self.0;
+        write!(f,
+               "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+               b[0],
+               b[1],
+               b[2],
+               b[3],
+               b[4],
+               b[5])
+    }
+}
+
+impl fmt::Debug for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MacAddress({})", self)
+    }
+}
+
+/// The error returned when a string can't be parsed as a `MacAddress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMacAddressError(());
+
+impl fmt::Display for ParseMacAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid MAC address syntax")
+    }
+}
+
+impl error::Error for ParseMacAddressError {}
+
+impl FromStr for MacAddress {
+    type Err = ParseMacAddressError;
+
+    /// Parses six hex octets separated by colons or hyphens, or twelve hex
+    /// digits with no separators at all.
+    fn from_str(s: &str) -> Result<MacAddress, ParseMacAddressError> {
+        let octets: Vec<&str> = if s.contains(':') {
+            s.split(':').collect()
+        } else if s.contains('-') {
+            s.split('-').collect()
+        } else if s.len() == 12 && s.is_char_boundary(2) {
+            (0..6).filter_map(|i| s.get(i * 2..i * 2 + 2)).collect()
+        } else {
+            return Err(ParseMacAddressError(()));
+        };
+        if octets.len() != 6 {
+            return Err(ParseMacAddressError(()));
+        }
+        let mut addr = [0u8; 6];
+        for (byte, octet) in addr.iter_mut().zip(octets) {
+            if octet.is_empty() || octet.len() > 2 || !octet.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ParseMacAddressError(()));
+            }
+            *byte = u8::from_str_radix(octet, 16).map_err(|_| ParseMacAddressError(()))?;
+        }
+        Ok(MacAddress(addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_separators() {
+        let expected = MacAddress::new([0x00, 0x1b, 0x2c, 0xaa, 0xbb, 0x0c]);
+        for s in &["00:1b:2c:aa:bb:0c", "00-1B-2C-AA-BB-0C", "001b2caabb0c", "0:1b:2c:aa:bb:c"] {
+            assert_eq!(s.parse::<MacAddress>(), Ok(expected), "{}", s);
+        }
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        for s in &["", "00:11:22:33:44", "00:11:22:33:44:55:66", "00:11:22:33:44:5g",
+                   "001:1:22:33:44:55", "00:11-22:33:44:55", "+0:11:22:33:44:55", "é0112233445"] {
+            assert!(s.parse::<MacAddress>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let addr = MacAddress::new([0xde, 0xad, 0xbe, 0xef, 0x00, 0x01]);
+        assert_eq!(addr.to_string(), "de:ad:be:ef:00:01");
+        assert_eq!(addr.to_string().parse::<MacAddress>(), Ok(addr));
+    }
+
+    #[test]
+    fn address_bits() {
+        let universal = MacAddress::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert!(universal.is_universal() && universal.is_unicast());
+        let local = MacAddress::new([0x02, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert!(local.is_locally_administered() && local.is_unicast());
+        let multicast = MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0x01]);
+        assert!(multicast.is_multicast() && !multicast.is_broadcast());
+        assert!(MacAddress::new([0xff; 6]).is_broadcast());
+        assert_eq!(local.oui(), [0x02, 0x11, 0x22]);
+    }
+
+    #[test]
+    fn from_slice() {
+        assert_eq!(MacAddress::from_slice(&[1, 2, 3, 4, 5, 6, 7]),
+                   Some(MacAddress::new([1, 2, 3, 4, 5, 6])));
+        assert_eq!(MacAddress::from_slice(&[1, 2, 3]), None);
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvError, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use {Error, MacAddress, ScanBackend, ScanRequest, Security, Ssid, WirelessNetwork};
+use retry;
+
+/// Where a `ScanMonitor` gets the time from, so tests can run it without
+/// waiting.
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock that only moves when told to. Sleeping on it moves it forward
+/// instead of blocking. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock { now: Arc::new(Mutex::new(Instant::now())) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Something that changed between one scan and the next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanEvent {
+    /// A network that wasn't being tracked showed up in a scan.
+    NetworkAppeared(WirelessNetwork),
+    /// A network hasn't been in any scan for the monitor's expiry time. Holds
+    /// the network as it was last seen.
+    NetworkLost(WirelessNetwork),
+    /// The signal level moved by at least the monitor's threshold since the
+    /// last event for the network.
+    SignalChanged {
+        network: WirelessNetwork,
+        previous_dbm: i32,
+        dbm: i32,
+    },
+    /// The network now advertises different security.
+    SecurityChanged {
+        network: WirelessNetwork,
+        previous: Security,
+    },
+}
+
+impl ScanEvent {
+    /// The network the event is about, as the latest scan saw it.
+    pub fn network(&self) -> &WirelessNetwork {
+        match *self {
+            ScanEvent::NetworkAppeared(ref network) |
+            ScanEvent::NetworkLost(ref network) |
+            ScanEvent::SignalChanged { ref network, .. } |
+            ScanEvent::SecurityChanged { ref network, .. } => network,
+        }
+    }
+}
+
+/// How networks are told apart from one scan to the next: by BSSID, or by
+/// ESSID for backends that don't report one.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum NetworkKey {
+    Bssid(MacAddress),
+    Essid(Option<Ssid>),
+}
+
+impl NetworkKey {
+    fn of(network: &WirelessNetwork) -> NetworkKey {
+        match network.bssid {
+            Some(bssid) => NetworkKey::Bssid(bssid),
+            None => NetworkKey::Essid(network.essid),
+        }
+    }
+}
+
+struct Tracked {
+    network: WirelessNetwork,
+    last_seen: Instant,
+    /// The signal level the last appeared or changed event reported, so slow
+    /// drift adds up to a change eventually.
+    reported_dbm: Option<i32>,
+}
+
+/// Scans an interface over and over and reports what changed.
+///
+/// Iterating over a monitor scans every `interval` and yields the events
+/// each scan produces; `spawn` does the same on a thread and hands them over
+/// through `MonitorEvents`. `poll` runs a single scan right away.
+///
+/// ```no_run
+/// use dradis::{ScanEvent, ScanMonitor, WextBackend};
+/// use std::time::Duration;
+///
+/// let monitor = ScanMonitor::new(WextBackend::new(), "wlan0")
+///     .interval(Duration::from_secs(5))
+///     .expiry(Duration::from_secs(60));
+/// for event in monitor {
+///     match event.unwrap() {
+///         ScanEvent::NetworkAppeared(network) => println!("+ {:?}", network.essid),
+///         ScanEvent::NetworkLost(network) => println!("- {:?}", network.essid),
+///         _ => {}
+///     }
+/// }
+/// ```
+pub struct ScanMonitor<B, C = SystemClock> {
+    backend: B,
+    clock: C,
+    request: ScanRequest,
+    interval: Duration,
+    expiry: Duration,
+    signal_threshold: i32,
+    tracked: HashMap<NetworkKey, Tracked>,
+    events: VecDeque<ScanEvent>,
+    last_scan: Option<Instant>,
+}
+
+impl<B: ScanBackend> ScanMonitor<B, SystemClock> {
+    /// Monitor `interface` with `backend`, scanning every 10 seconds,
+    /// expiring networks after 30 and reporting signal changes of 5 dB.
+    pub fn new(backend: B, interface: &str) -> ScanMonitor<B, SystemClock> {
+        ScanMonitor::with_clock(backend, interface, SystemClock)
+    }
+}
+
+impl<B: ScanBackend, C: Clock> ScanMonitor<B, C> {
+    /// Like `new`, but telling the time with `clock`.
+    pub fn with_clock(backend: B, interface: &str, clock: C) -> ScanMonitor<B, C> {
+        ScanMonitor {
+            backend,
+            clock,
+            request: ScanRequest::new(interface),
+            interval: Duration::from_secs(10),
+            expiry: Duration::from_secs(30),
+            signal_threshold: 5,
+            tracked: HashMap::new(),
+            events: VecDeque::new(),
+            last_scan: None,
+        }
+    }
+
+    /// Run every scan as `request` describes, for instance only on the
+    /// channels worth watching. Its fallbacks to cached results are turned
+    /// off: networks in a stale scan would never be lost, so a scan that
+    /// can't run fails instead.
+    pub fn request(mut self, request: ScanRequest) -> ScanMonitor<B, C> {
+        let retry = request.retry.fallback_to_cached(false);
+        self.request = request.cached_if_denied(false).retry(retry);
+        self
+    }
+
+    /// How long to wait between the start of one scan and the next.
+    pub fn interval(mut self, interval: Duration) -> ScanMonitor<B, C> {
+        self.interval = interval;
+        self
+    }
+
+    /// How long a network has to be missing from scans before it is lost.
+    pub fn expiry(mut self, expiry: Duration) -> ScanMonitor<B, C> {
+        self.expiry = expiry;
+        self
+    }
+
+    /// The smallest change in dB worth a `SignalChanged` event.
+    pub fn signal_threshold(mut self, db: i32) -> ScanMonitor<B, C> {
+        self.signal_threshold = db;
+        self
+    }
+
+    /// The networks currently being tracked, as last seen.
+    pub fn networks(&self) -> Vec<&WirelessNetwork> {
+        self.tracked.values().map(|tracked| &tracked.network).collect()
+    }
+
+    /// Scan now and return what changed since the last scan.
+    pub fn poll(&mut self) -> Result<Vec<ScanEvent>, Error> {
+        let now = self.clock.now();
+        self.last_scan = Some(now);
+        let networks = retry::scan(&mut self.backend, &self.request, &self.clock)?.networks;
+        Ok(self.update(networks, now))
+    }
+
+    fn update(&mut self, networks: Vec<WirelessNetwork>, now: Instant) -> Vec<ScanEvent> {
+        let mut events = Vec::new();
+        for network in networks {
+            let key = NetworkKey::of(&network);
+            let dbm = network.signal_dbm();
+            let tracked = match self.tracked.get_mut(&key) {
+                Some(tracked) => tracked,
+                None => {
+                    events.push(ScanEvent::NetworkAppeared(network.clone()));
+                    self.tracked.insert(key,
+                                        Tracked {
+                                            network,
+                                            last_seen: now,
+                                            reported_dbm: dbm,
+                                        });
+                    continue;
+                }
+            };
+            if network.security != tracked.network.security {
+                events.push(ScanEvent::SecurityChanged {
+                    network: network.clone(),
+                    previous: tracked.network.security.clone(),
+                });
+            }
+            match (tracked.reported_dbm, dbm) {
+                (Some(previous_dbm), Some(dbm)) if (dbm - previous_dbm).abs() >=
+                                                   self.signal_threshold => {
+                    events.push(ScanEvent::SignalChanged {
+                        network: network.clone(),
+                        previous_dbm,
+                        dbm,
+                    });
+                    tracked.reported_dbm = Some(dbm);
+                }
+                (None, Some(dbm)) => tracked.reported_dbm = Some(dbm),
+                _ => {}
+            }
+            tracked.network = network;
+            tracked.last_seen = now;
+        }
+
+        let expiry = self.expiry;
+        let lost: Vec<NetworkKey> = self.tracked
+            .iter()
+            .filter(|&(_, tracked)| now.duration_since(tracked.last_seen) >= expiry)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in lost {
+            let tracked = self.tracked.remove(&key).unwrap();
+            events.push(ScanEvent::NetworkLost(tracked.network));
+        }
+        events
+    }
+
+    /// Wait until the next scan is due.
+    fn wait(&self) {
+        if let Some(last_scan) = self.last_scan {
+            let due = last_scan + self.interval;
+            let now = self.clock.now();
+            if due > now {
+                self.clock.sleep(due - now);
+            }
+        }
+    }
+}
+
+impl<B, C> ScanMonitor<B, C>
+    where B: ScanBackend + Send + 'static,
+          C: Clock + Send + 'static
+{
+    /// Run the monitor on its own thread, sending each event or scan error
+    /// to the returned `MonitorEvents`. Dropping that stops the thread before
+    /// its next scan.
+    pub fn spawn(mut self) -> MonitorEvents {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                self.wait();
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let sent = match self.poll() {
+                    Ok(events) => events.into_iter().all(|event| sender.send(Ok(event)).is_ok()),
+                    Err(err) => sender.send(Err(err)).is_ok(),
+                };
+                if !sent {
+                    break;
+                }
+            }
+        });
+        MonitorEvents { receiver, stop }
+    }
+}
+
+/// The events from a spawned `ScanMonitor`. Iterating blocks for each event
+/// in turn. Dropping it stops the monitor's thread before the next scan, even
+/// if no events came in meanwhile.
+pub struct MonitorEvents {
+    receiver: Receiver<Result<ScanEvent, Error>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MonitorEvents {
+    /// Wait for the next event.
+    pub fn recv(&self) -> Result<Result<ScanEvent, Error>, RecvError> {
+        self.receiver.recv()
+    }
+
+    /// The next event, if one is waiting.
+    pub fn try_recv(&self) -> Result<Result<ScanEvent, Error>, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Wait up to `timeout` for the next event.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Result<ScanEvent, Error>, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+}
+
+impl Iterator for MonitorEvents {
+    type Item = Result<ScanEvent, Error>;
+
+    fn next(&mut self) -> Option<Result<ScanEvent, Error>> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for MonitorEvents {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Never ends. Waits for the next scan whenever the last one's events have
+/// all been yielded; a failed scan is yielded as an error and the next scan
+/// goes ahead as usual.
+impl<B: ScanBackend, C: Clock> Iterator for ScanMonitor<B, C> {
+    type Item = Result<ScanEvent, Error>;
+
+    fn next(&mut self) -> Option<Result<ScanEvent, Error>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            self.wait();
+            match self.poll() {
+                Ok(events) => self.events.extend(events),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use {FixtureBackend, IwQuality, IwStats, PendingScan, Protocol, RetryPolicy};
+    use backend::FinishedScan;
+    use test_util;
+
+    fn network(last_octet: u8, dbm: i32) -> WirelessNetwork {
+        let flags = IwQuality::DBM | IwQuality::LEVEL_UPDATED;
+        WirelessNetwork {
+            bssid: Some(MacAddress::new([0x02, 0, 0, 0, 0, last_octet])),
+            stats: Some(IwStats::new(IwQuality::new(0, dbm as u8, 0, flags))),
+            ..test_util::network(&format!("net{}", last_octet))
+        }
+    }
+
+    fn describe(event: &ScanEvent) -> String {
+        match *event {
+            ScanEvent::NetworkAppeared(ref network) => {
+                format!("+{}", network.essid.unwrap())
+            }
+            ScanEvent::NetworkLost(ref network) => format!("-{}", network.essid.unwrap()),
+            ScanEvent::SignalChanged { ref network, previous_dbm, dbm } => {
+                format!("~{} {}->{}", network.essid.unwrap(), previous_dbm, dbm)
+            }
+            ScanEvent::SecurityChanged { ref network, ref previous } => {
+                format!("!{} {}->{}",
+                        network.essid.unwrap(),
+                        previous,
+                        network.security)
+            }
+        }
+    }
+
+    fn poll<B: ScanBackend>(monitor: &mut ScanMonitor<B, ManualClock>) -> Vec<String> {
+        let mut events: Vec<String> = monitor.poll().unwrap().iter().map(describe).collect();
+        events.sort();
+        events
+    }
+
+    #[test]
+    fn appear_and_expire() {
+        let clock = ManualClock::new();
+        let backend = FixtureBackend::sequence(vec![vec![network(1, -50), network(2, -60)],
+                                                    vec![network(1, -50)]]);
+        let mut monitor = ScanMonitor::with_clock(backend, "wlan0", clock.clone())
+            .expiry(Duration::from_secs(30));
+        assert_eq!(poll(&mut monitor), vec!["+net1", "+net2"]);
+        clock.advance(Duration::from_secs(20));
+        assert!(poll(&mut monitor).is_empty());
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(poll(&mut monitor), vec!["-net2"]);
+        assert_eq!(monitor.networks().len(), 1);
+    }
+
+    #[test]
+    fn signal_changes_past_threshold() {
+        let backend = FixtureBackend::sequence(vec![vec![network(1, -50)],
+                                                    vec![network(1, -53)],
+                                                    vec![network(1, -56)],
+                                                    vec![network(1, -57)]]);
+        let mut monitor = ScanMonitor::with_clock(backend, "wlan0", ManualClock::new())
+            .signal_threshold(5);
+        assert_eq!(poll(&mut monitor), vec!["+net1"]);
+        assert!(poll(&mut monitor).is_empty());
+        // Small steps add up against the last reported level.
+        assert_eq!(poll(&mut monitor), vec!["~net1 -50->-56"]);
+        assert!(poll(&mut monitor).is_empty());
+    }
+
+    #[test]
+    fn security_changes() {
+        let mut protected = network(1, -50);
+        protected.security = Security::from_ies(&[], true);
+        let backend = FixtureBackend::sequence(vec![vec![network(1, -50)], vec![protected]]);
+        let mut monitor = ScanMonitor::with_clock(backend, "wlan0", ManualClock::new());
+        poll(&mut monitor);
+        assert_eq!(poll(&mut monitor), vec!["!net1 Open->WEP"]);
+        assert_eq!(monitor.networks()[0].security.protocol, Protocol::Wep);
+    }
+
+    #[test]
+    fn never_settles_for_cached_results() {
+        let mut backend = FixtureBackend::new(vec![network(1, -50)]);
+        backend.fail(Error::from_errno("SIOCSIWSCAN", ::libc::EPERM));
+        backend.fail(Error::from_errno("SIOCSIWSCAN", ::libc::EBUSY));
+        let request = ScanRequest::new("wlan0")
+            .cached_if_denied(true)
+            .retry(RetryPolicy::never().fallback_to_cached(true));
+        let mut monitor = ScanMonitor::with_clock(backend, "wlan0", ManualClock::new()).request(request);
+        assert!(matches!(monitor.poll(), Err(Error::PermissionDenied { .. })));
+        assert!(matches!(monitor.poll(), Err(Error::Busy { .. })));
+        assert_eq!(poll(&mut monitor), vec!["+net1"]);
+    }
+
+    #[test]
+    fn iterator_waits_out_the_interval() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let backend = FixtureBackend::sequence(vec![vec![network(1, -50)],
+                                                    vec![],
+                                                    vec![],
+                                                    vec![network(2, -50)]]);
+        let monitor = ScanMonitor::with_clock(backend, "wlan0", clock.clone())
+            .interval(Duration::from_secs(10))
+            .expiry(Duration::from_secs(15));
+        let events: Vec<String> = monitor.take(3).map(|event| describe(&event.unwrap())).collect();
+        assert_eq!(events, vec!["+net1", "-net1", "+net2"]);
+        assert_eq!(clock.now() - start, Duration::from_secs(30));
+    }
+
+    /// Counts its scans, which never find anything.
+    struct Counting(Arc<AtomicUsize>);
+
+    impl ScanBackend for Counting {
+        fn start_scan(&mut self, _request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(FinishedScan::new(Vec::new())))
+        }
+    }
+
+    #[test]
+    fn dropping_the_events_stops_a_quiet_monitor() {
+        let scans = Arc::new(AtomicUsize::new(0));
+        let events = ScanMonitor::with_clock(Counting(scans.clone()), "wlan0", ManualClock::new()).spawn();
+        while scans.load(Ordering::SeqCst) < 3 {
+            thread::yield_now();
+        }
+        drop(events);
+        thread::sleep(Duration::from_millis(20));
+        let stopped_at = scans.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(scans.load(Ordering::SeqCst), stopped_at);
+    }
+
+    #[test]
+    fn spawned_monitor_sends_events() {
+        let backend = FixtureBackend::new(vec![network(1, -50)]);
+        let events = ScanMonitor::new(backend, "wlan0").interval(Duration::from_millis(10)).spawn();
+        assert_eq!(describe(&events.recv().unwrap().unwrap()), "+net1");
+    }
+}
@@ -0,0 +1,990 @@
+//! Scanning over nl80211, the generic netlink interface that current Linux
+//! wireless drivers implement natively. Unlike the Wireless Extensions path this
+//! does not need `libiw`, and it reports what the kernel knows about 5 and 6 GHz
+//! networks.
+//!
+//! The message encoding and decoding functions are public so captured netlink
+//! traffic can be fed through them without any wireless hardware.
+
+use std::ffi::CString;
+use std::mem;
+use std::time::{Duration, Instant};
+use libc::{self, c_int, c_void};
+
+use {Error, IwQuality, IwStats, MacAddress, PendingScan, ScanBackend, ScanRequest, Security,
+     Ssid, WirelessMode, WirelessNetwork};
+use ie::{Element, Elements, RawElements};
+
+const NLMSG_HDRLEN: usize = 16;
+const GENL_HDRLEN: usize = 4;
+const NLA_HDRLEN: usize = 4;
+
+const NLM_F_REQUEST: u16 = 0x01;
+const NLM_F_ACK: u16 = 0x04;
+const NLM_F_DUMP: u16 = 0x300;
+
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+
+const NLA_F_NESTED: u16 = 1 << 15;
+const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const CTRL_ATTR_MCAST_GROUPS: u16 = 7;
+const CTRL_ATTR_MCAST_GRP_NAME: u16 = 1;
+const CTRL_ATTR_MCAST_GRP_ID: u16 = 2;
+
+const NL80211_CMD_GET_SCAN: u8 = 32;
+const NL80211_CMD_TRIGGER_SCAN: u8 = 33;
+const NL80211_CMD_NEW_SCAN_RESULTS: u8 = 34;
+const NL80211_CMD_SCAN_ABORTED: u8 = 35;
+const NL80211_CMD_ABORT_SCAN: u8 = 114;
+
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_SCAN_FREQUENCIES: u16 = 44;
+const NL80211_ATTR_SCAN_SSIDS: u16 = 45;
+const NL80211_ATTR_BSS: u16 = 47;
+const NL80211_ATTR_SCAN_FLAGS: u16 = 158;
+
+const NL80211_SCAN_FLAG_FLUSH: u32 = 1 << 1;
+
+const NL80211_BSS_BSSID: u16 = 1;
+const NL80211_BSS_FREQUENCY: u16 = 2;
+const NL80211_BSS_CAPABILITY: u16 = 5;
+const NL80211_BSS_INFORMATION_ELEMENTS: u16 = 6;
+const NL80211_BSS_SIGNAL_MBM: u16 = 7;
+const NL80211_BSS_SIGNAL_UNSPEC: u16 = 8;
+const NL80211_BSS_BEACON_IES: u16 = 11;
+
+const WLAN_CAPABILITY_ESS: u16 = 0x0001;
+const WLAN_CAPABILITY_IBSS: u16 = 0x0002;
+const WLAN_CAPABILITY_PRIVACY: u16 = 0x0010;
+
+/// The fastest rate a Supported Rates element can list; anything above it is
+/// a BSS membership selector, not a rate.
+const MAX_LEGACY_KBPS: u32 = 54000;
+const NL80211_SIGNAL_UNSPEC_MAX: u8 = 100;
+
+const WLAN_EID_SSID: u8 = 0;
+
+const RECV_BUFFER_SIZE: usize = 32768;
+
+/// The generic netlink family ID the kernel assigned to nl80211, along with the
+/// multicast group that scan completion events are sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Family {
+    pub id: u16,
+    pub scan_group: Option<u32>,
+}
+
+/// Scan backend that talks nl80211 directly over a generic netlink socket.
+#[derive(Debug)]
+pub struct Nl80211Backend {
+    timeout: Duration,
+}
+
+impl Nl80211Backend {
+    pub fn new() -> Nl80211Backend {
+        Nl80211Backend { timeout: Duration::from_secs(10) }
+    }
+
+    /// How long to wait for the kernel to report that a triggered scan finished.
+    pub fn timeout(mut self, timeout: Duration) -> Nl80211Backend {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Default for Nl80211Backend {
+    fn default() -> Nl80211Backend {
+        Nl80211Backend::new()
+    }
+}
+
+impl ScanBackend for Nl80211Backend {
+    fn start_scan(&mut self, request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+        request.validate()?;
+        let ifindex = interface_index(&request.interface)?;
+        let mut control = Socket::open()?;
+        control.set_timeout(self.timeout)?;
+        let seq = control.next_seq();
+        let family = parse_family(&control.dump("CTRL_CMD_GETFAMILY",
+                                                &get_family_request(seq, "nl80211"))?)?;
+        let group = family.scan_group
+            .ok_or(Error::Unsupported {
+                call: "CTRL_CMD_GETFAMILY",
+                message: "nl80211 has no scan multicast group",
+            })?;
+
+        // Subscribe before triggering so the completion event can't slip past us.
+        let mut events = Socket::open()?;
+        events.join_group(group)?;
+
+        let seq = control.next_seq();
+        control.request("NL80211_CMD_TRIGGER_SCAN",
+                        &trigger_scan_request(family.id, seq, ifindex, request))?;
+        Ok(Box::new(Nl80211Scan {
+            control,
+            events,
+            family: family.id,
+            ifindex,
+            deadline: Instant::now() + request.timeout.unwrap_or(self.timeout),
+            running: true,
+        }))
+    }
+
+    fn cached_results(&mut self, request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        let ifindex = interface_index(&request.interface)?;
+        let mut control = Socket::open()?;
+        control.set_timeout(self.timeout)?;
+        let seq = control.next_seq();
+        let family = parse_family(&control.dump("CTRL_CMD_GETFAMILY",
+                                                &get_family_request(seq, "nl80211"))?)?;
+        let seq = control.next_seq();
+        let request = get_scan_request(family.id, seq, ifindex);
+        parse_scan_results(&control.dump("NL80211_CMD_GET_SCAN", &request)?)
+    }
+}
+
+/// A triggered nl80211 scan, finished once the kernel sends the scan
+/// multicast group a NEW_SCAN_RESULTS event for the interface.
+struct Nl80211Scan {
+    control: Socket,
+    events: Socket,
+    family: u16,
+    ifindex: u32,
+    deadline: Instant,
+    running: bool,
+}
+
+impl PendingScan for Nl80211Scan {
+    fn poll_results(&mut self) -> Result<Option<Vec<WirelessNetwork>>, Error> {
+        while let Some(buf) = self.events.try_recv()? {
+            match scan_event(&buf, self.family, self.ifindex)? {
+                Some(NL80211_CMD_NEW_SCAN_RESULTS) => {
+                    self.running = false;
+                    let seq = self.control.next_seq();
+                    let request = get_scan_request(self.family, seq, self.ifindex);
+                    return parse_scan_results(&self.control.dump("NL80211_CMD_GET_SCAN", &request)?)
+                        .map(Some);
+                }
+                Some(_) => {
+                    self.running = false;
+                    // Usually another scan or a connection attempt got in
+                    // the way, so it is worth trying again.
+                    return Err(Error::Busy {
+                        call: "NL80211_CMD_SCAN_ABORTED",
+                        errno: None,
+                    });
+                }
+                None => continue,
+            }
+        }
+        if Instant::now() >= self.deadline {
+            self.cancel();
+            return Err(Error::Timeout {
+                call: "NL80211_CMD_NEW_SCAN_RESULTS",
+                errno: None,
+            });
+        }
+        Ok(None)
+    }
+
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(50)
+    }
+
+    fn cancel(&mut self) {
+        if self.running {
+            self.running = false;
+            let seq = self.control.next_seq();
+            // Best effort: the scan may have finished or never have started.
+            let abort = abort_scan_request(self.family, seq, self.ifindex);
+            let _ = self.control.request("NL80211_CMD_ABORT_SCAN", &abort);
+        }
+    }
+}
+
+/// Build a `CTRL_CMD_GETFAMILY` request asking the kernel for the ID of the
+/// generic netlink family called `name`.
+pub fn get_family_request(seq: u32, name: &str) -> Vec<u8> {
+    let mut msg = MessageBuilder::new(GENL_ID_CTRL, NLM_F_REQUEST | NLM_F_ACK, seq, CTRL_CMD_GETFAMILY);
+    msg.put_str(CTRL_ATTR_FAMILY_NAME, name);
+    msg.finish()
+}
+
+/// Build an `NL80211_CMD_TRIGGER_SCAN` request for the interface `ifindex`
+/// doing what `request` asks. Leaving out the SSID list makes the scan
+/// passive, so an active scan without SSIDs probes for the wildcard SSID.
+pub fn trigger_scan_request(family: u16, seq: u32, ifindex: u32, request: &ScanRequest) -> Vec<u8> {
+    let mut msg = MessageBuilder::new(family, NLM_F_REQUEST | NLM_F_ACK, seq, NL80211_CMD_TRIGGER_SCAN);
+    msg.put_u32(NL80211_ATTR_IFINDEX, ifindex);
+    if !request.passive {
+        let nest = msg.begin_nested(NL80211_ATTR_SCAN_SSIDS);
+        if request.ssids.is_empty() {
+            msg.put(1, &[]);
+        }
+        for (i, ssid) in request.ssids.iter().enumerate() {
+            msg.put(i as u16 + 1, ssid);
+        }
+        msg.end_nested(nest);
+    }
+    if !request.frequencies.is_empty() {
+        let nest = msg.begin_nested(NL80211_ATTR_SCAN_FREQUENCIES);
+        for (i, &mhz) in request.frequencies.iter().enumerate() {
+            msg.put_u32(i as u16 + 1, mhz);
+        }
+        msg.end_nested(nest);
+    }
+    if request.flush {
+        msg.put_u32(NL80211_ATTR_SCAN_FLAGS, NL80211_SCAN_FLAG_FLUSH);
+    }
+    msg.finish()
+}
+
+/// Build an `NL80211_CMD_ABORT_SCAN` request for the interface `ifindex`.
+pub fn abort_scan_request(family: u16, seq: u32, ifindex: u32) -> Vec<u8> {
+    let mut msg = MessageBuilder::new(family, NLM_F_REQUEST | NLM_F_ACK, seq, NL80211_CMD_ABORT_SCAN);
+    msg.put_u32(NL80211_ATTR_IFINDEX, ifindex);
+    msg.finish()
+}
+
+/// Build an `NL80211_CMD_GET_SCAN` dump request for the interface `ifindex`.
+pub fn get_scan_request(family: u16, seq: u32, ifindex: u32) -> Vec<u8> {
+    let mut msg = MessageBuilder::new(family, NLM_F_REQUEST | NLM_F_DUMP, seq, NL80211_CMD_GET_SCAN);
+    msg.put_u32(NL80211_ATTR_IFINDEX, ifindex);
+    msg.finish()
+}
+
+/// Pull the nl80211 family ID and scan multicast group out of the kernel's reply
+/// to `get_family_request`.
+pub fn parse_family(buf: &[u8]) -> Result<Family, Error> {
+    for message in Messages::new(buf) {
+        let message = message?;
+        let payload = message.payload();
+        if message.kind != GENL_ID_CTRL || payload.len() < GENL_HDRLEN {
+            continue;
+        }
+        let mut family = None;
+        let mut scan_group = None;
+        for (kind, value) in Attributes::new(&payload[GENL_HDRLEN..]) {
+            match kind {
+                CTRL_ATTR_FAMILY_ID => family = read_u16(value),
+                CTRL_ATTR_MCAST_GROUPS => {
+                    for (_, group) in Attributes::new(value) {
+                        let mut name = None;
+                        let mut id = None;
+                        for (kind, value) in Attributes::new(group) {
+                            match kind {
+                                CTRL_ATTR_MCAST_GRP_NAME => name = Some(read_str(value)),
+                                CTRL_ATTR_MCAST_GRP_ID => id = read_u32(value),
+                                _ => {}
+                            }
+                        }
+                        if name == Some("scan") {
+                            scan_group = id;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(id) = family {
+            return Ok(Family {
+                id,
+                scan_group,
+            });
+        }
+    }
+    Err(Error::Unsupported {
+        call: "CTRL_CMD_GETFAMILY",
+        message: "the kernel has no nl80211 family",
+    })
+}
+
+/// Decode the messages returned by an `NL80211_CMD_GET_SCAN` dump into networks.
+/// `buf` may hold any number of netlink messages back to back, so the output of
+/// several `recv` calls can simply be concatenated.
+pub fn parse_scan_results(buf: &[u8]) -> Result<Vec<WirelessNetwork>, Error> {
+    let mut list = Vec::new();
+    for message in Messages::new(buf) {
+        let payload = message?.payload();
+        if payload.len() < GENL_HDRLEN || payload[0] != NL80211_CMD_NEW_SCAN_RESULTS {
+            continue;
+        }
+        for (kind, value) in Attributes::new(&payload[GENL_HDRLEN..]) {
+            if kind == NL80211_ATTR_BSS {
+                list.push(parse_bss(value));
+            }
+        }
+    }
+    Ok(list)
+}
+
+fn parse_bss(buf: &[u8]) -> WirelessNetwork {
+    let mut bssid = None;
+    let mut freq = None;
+    let mut capability = 0;
+    let mut ies = None;
+    let mut beacon_ies = None;
+    let mut quality = None;
+    for (kind, value) in Attributes::new(buf) {
+        match kind {
+            NL80211_BSS_BSSID => bssid = MacAddress::from_slice(value),
+            NL80211_BSS_FREQUENCY => freq = read_u32(value).map(|mhz| mhz as f64 * 1e6),
+            NL80211_BSS_CAPABILITY => capability = read_u16(value).unwrap_or(0),
+            NL80211_BSS_INFORMATION_ELEMENTS => ies = Some(value),
+            NL80211_BSS_BEACON_IES => beacon_ies = Some(value),
+            // Signal is reported in mBm; Wireless Extensions store dBm in a u8.
+            NL80211_BSS_SIGNAL_MBM => {
+                quality = read_u32(value).map(|mbm| {
+                    IwQuality::new(0,
+                                   (mbm as i32 / 100) as u8,
+                                   0,
+                                   IwQuality::LEVEL_UPDATED | IwQuality::DBM |
+                                   IwQuality::QUAL_INVALID |
+                                   IwQuality::NOISE_INVALID)
+                })
+            }
+            // Unspecified units scaled from 0 to 100, reported as the quality.
+            NL80211_BSS_SIGNAL_UNSPEC => {
+                quality = quality.or_else(|| {
+                    value.first().map(|&signal| {
+                        IwQuality::new(signal,
+                                       0,
+                                       0,
+                                       IwQuality::QUAL_UPDATED | IwQuality::LEVEL_INVALID |
+                                       IwQuality::NOISE_INVALID)
+                    })
+                })
+            }
+            _ => {}
+        }
+    }
+    let ies = ies.or(beacon_ies).unwrap_or(&[]);
+
+    WirelessNetwork {
+        bssid,
+        stats: quality.map(IwStats::new),
+        max_quality: Some(IwQuality::new(NL80211_SIGNAL_UNSPEC_MAX, 0, 0, 0)),
+        maxbitrate: max_rate(ies),
+        freq,
+        key: None,
+        essid: find_ie(ies, WLAN_EID_SSID).map(Ssid::truncated),
+        mode: mode(capability),
+        security: Security::from_ies(ies, capability & WLAN_CAPABILITY_PRIVACY != 0),
+        ies: ies.to_vec(),
+    }
+}
+
+/// Returns the `NL80211_CMD_*` of a scan completion event for `ifindex` in `buf`,
+/// if there is one.
+fn scan_event(buf: &[u8], family: u16, ifindex: u32) -> Result<Option<u8>, Error> {
+    for message in Messages::new(buf) {
+        let message = message?;
+        let payload = message.payload();
+        if message.kind != family || payload.len() < GENL_HDRLEN {
+            continue;
+        }
+        let cmd = payload[0];
+        if cmd != NL80211_CMD_NEW_SCAN_RESULTS && cmd != NL80211_CMD_SCAN_ABORTED {
+            continue;
+        }
+        let matches = Attributes::new(&payload[GENL_HDRLEN..])
+            .any(|(kind, value)| kind == NL80211_ATTR_IFINDEX && read_u32(value) == Some(ifindex));
+        if matches {
+            return Ok(Some(cmd));
+        }
+    }
+    Ok(None)
+}
+
+/// The mode the ESS and IBSS capability bits say the BSS is in.
+fn mode(capability: u16) -> Option<WirelessMode> {
+    if capability & WLAN_CAPABILITY_ESS != 0 {
+        Some(WirelessMode::Master)
+    } else if capability & WLAN_CAPABILITY_IBSS != 0 {
+        Some(WirelessMode::AdHoc)
+    } else {
+        None
+    }
+}
+
+/// The fastest rate in the Supported Rates elements, in bits per second.
+fn max_rate(ies: &[u8]) -> Option<i32> {
+    Elements::new(ies)
+        .flat_map(|element| match element {
+            Element::SupportedRates(rates) | Element::ExtendedSupportedRates(rates) => rates,
+            _ => Vec::new(),
+        })
+        .map(|rate| rate.kbps)
+        .filter(|&kbps| kbps <= MAX_LEGACY_KBPS)
+        .max()
+        .map(|kbps| kbps as i32 * 1000)
+}
+
+fn find_ie(ies: &[u8], id: u8) -> Option<&[u8]> {
+    RawElements::new(ies).find(|&(kind, _)| kind == id).map(|(_, body)| body)
+}
+
+fn read_u16(value: &[u8]) -> Option<u16> {
+    if value.len() < 2 {
+        return None;
+    }
+    Some(u16::from_ne_bytes([value[0], value[1]]))
+}
+
+fn read_u32(value: &[u8]) -> Option<u32> {
+    if value.len() < 4 {
+        return None;
+    }
+    Some(u32::from_ne_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+fn read_str(value: &[u8]) -> &str {
+    let end = value.iter().position(|&byte| byte == 0).unwrap_or(value.len());
+    ::std::str::from_utf8(&value[..end]).unwrap_or("")
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// A single netlink message, header included.
+#[derive(Debug, Clone, Copy)]
+struct Message<'a> {
+    kind: u16,
+    seq: u32,
+    data: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    fn payload(&self) -> &'a [u8] {
+        &self.data[NLMSG_HDRLEN..]
+    }
+}
+
+/// Walks the netlink messages packed into a buffer.
+struct Messages<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Messages<'a> {
+    fn new(buf: &'a [u8]) -> Messages<'a> {
+        Messages { buf }
+    }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<Message<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let len = match read_u32(self.buf) {
+            Some(len) if len as usize >= NLMSG_HDRLEN && len as usize <= self.buf.len() => len as usize,
+            _ => {
+                self.buf = &[];
+                return Some(Err(Error::parse("netlink", "truncated netlink message")));
+            }
+        };
+        let message = Message {
+            kind: read_u16(&self.buf[4..]).unwrap(),
+            seq: read_u32(&self.buf[8..]).unwrap(),
+            data: &self.buf[..len],
+        };
+        self.buf = &self.buf[align(len).min(self.buf.len())..];
+        Some(Ok(message))
+    }
+}
+
+/// Walks a run of netlink attributes, yielding each type and value. Malformed
+/// trailing data ends the walk instead of being returned.
+struct Attributes<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Attributes<'a> {
+    fn new(buf: &'a [u8]) -> Attributes<'a> {
+        Attributes { buf }
+    }
+}
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = read_u16(self.buf)? as usize;
+        if len < NLA_HDRLEN || len > self.buf.len() {
+            self.buf = &[];
+            return None;
+        }
+        let kind = read_u16(&self.buf[2..])? & NLA_TYPE_MASK;
+        let value = &self.buf[NLA_HDRLEN..len];
+        self.buf = &self.buf[align(len).min(self.buf.len())..];
+        Some((kind, value))
+    }
+}
+
+struct MessageBuilder {
+    buf: Vec<u8>,
+}
+
+impl MessageBuilder {
+    fn new(kind: u16, flags: u16, seq: u32, cmd: u8) -> MessageBuilder {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(&[0; 4]); // Length, filled in by finish()
+        buf.extend_from_slice(&kind.to_ne_bytes());
+        buf.extend_from_slice(&flags.to_ne_bytes());
+        buf.extend_from_slice(&seq.to_ne_bytes());
+        buf.extend_from_slice(&0u32.to_ne_bytes()); // Port ID, the kernel fills it in
+        buf.extend_from_slice(&[cmd, 1, 0, 0]); // Command, version, reserved
+        MessageBuilder { buf }
+    }
+
+    fn put(&mut self, kind: u16, value: &[u8]) {
+        let len = NLA_HDRLEN + value.len();
+        self.buf.extend_from_slice(&(len as u16).to_ne_bytes());
+        self.buf.extend_from_slice(&kind.to_ne_bytes());
+        self.buf.extend_from_slice(value);
+        let padded = align(self.buf.len());
+        self.buf.resize(padded, 0);
+    }
+
+    /// Start an attribute holding other attributes, returning where it
+    /// starts for `end_nested`.
+    fn begin_nested(&mut self, kind: u16) -> usize {
+        let start = self.buf.len();
+        self.put(kind, &[]);
+        start
+    }
+
+    /// Fill in the length of the nested attribute begun at `start`.
+    fn end_nested(&mut self, start: usize) {
+        let len = (self.buf.len() - start) as u16;
+        self.buf[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+    }
+
+    fn put_u32(&mut self, kind: u16, value: u32) {
+        self.put(kind, &value.to_ne_bytes());
+    }
+
+    fn put_str(&mut self, kind: u16, value: &str) {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.put(kind, &bytes);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let len = (self.buf.len() as u32).to_ne_bytes();
+        self.buf[..4].copy_from_slice(&len);
+        self.buf
+    }
+}
+
+fn interface_index(interface: &str) -> Result<u32, Error> {
+    let name = CString::new(interface)
+        .map_err(|_| {
+            Error::InvalidInput {
+                call: "if_nametoindex",
+                message: "interface name contains a NUL byte",
+            }
+        })?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(Error::last_os_error("if_nametoindex")),
+        index => Ok(index),
+    }
+}
+
+/// A generic netlink socket, closed on drop.
+struct Socket {
+    fd: c_int,
+    seq: u32,
+}
+
+impl Socket {
+    fn open() -> Result<Socket, Error> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK,
+                         libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                         libc::NETLINK_GENERIC)
+        };
+        if fd < 0 {
+            return Err(Error::last_os_error("socket"));
+        }
+        let socket = Socket { fd, seq: 1 };
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let ret = unsafe {
+            libc::bind(fd,
+                       &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                       mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t)
+        };
+        if ret < 0 {
+            return Err(Error::last_os_error("bind"));
+        }
+        Ok(socket)
+    }
+
+    fn next_seq(&mut self) -> u32 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn join_group(&mut self, group: u32) -> Result<(), Error> {
+        self.setsockopt(libc::SOL_NETLINK, libc::NETLINK_ADD_MEMBERSHIP, &group)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        let tv = libc::timeval {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        };
+        self.setsockopt(libc::SOL_SOCKET, libc::SO_RCVTIMEO, &tv)
+    }
+
+    fn setsockopt<T>(&mut self, level: c_int, name: c_int, value: &T) -> Result<(), Error> {
+        let ret = unsafe {
+            libc::setsockopt(self.fd,
+                             level,
+                             name,
+                             value as *const T as *const c_void,
+                             mem::size_of::<T>() as libc::socklen_t)
+        };
+        if ret < 0 {
+            return Err(Error::last_os_error("setsockopt"));
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, call: &'static str, msg: &[u8]) -> Result<(), Error> {
+        let ret = unsafe { libc::send(self.fd, msg.as_ptr() as *const c_void, msg.len(), 0) };
+        if ret < 0 {
+            return Err(Error::last_os_error(call));
+        }
+        Ok(())
+    }
+
+    /// Wait for a reply to `call`. Running into the receive timeout is a
+    /// `Timeout` rather than the `Busy` its EAGAIN would otherwise mean.
+    fn recv(&mut self, call: &'static str) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+        let ret = unsafe { libc::recv(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len(), 0) };
+        if ret < 0 {
+            return Err(match Error::last_os_error(call) {
+                Error::Busy { call, errno } => Error::Timeout { call, errno },
+                err => err,
+            });
+        }
+        buf.truncate(ret as usize);
+        Ok(buf)
+    }
+
+    /// Receive whatever is queued on the socket without waiting, or `None` if
+    /// nothing is.
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+        let ret = unsafe {
+            libc::recv(self.fd,
+                       buf.as_mut_ptr() as *mut c_void,
+                       buf.len(),
+                       libc::MSG_DONTWAIT)
+        };
+        if ret < 0 {
+            let err = Error::last_os_error("recv");
+            if err.errno() == Some(libc::EAGAIN) {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        buf.truncate(ret as usize);
+        Ok(Some(buf))
+    }
+
+    /// Send a request and wait for the kernel to acknowledge it. `call` names
+    /// the request in any error.
+    fn request(&mut self, call: &'static str, msg: &[u8]) -> Result<(), Error> {
+        let seq = read_u32(&msg[8..]).unwrap_or(0);
+        self.send(call, msg)?;
+        loop {
+            let buf = self.recv(call)?;
+            for message in Messages::new(&buf) {
+                let message = message?;
+                if message.seq == seq && message.kind == NLMSG_ERROR {
+                    return check_error(call, message.payload());
+                }
+            }
+        }
+    }
+
+    /// Send a request and collect every reply message up to the end of the dump
+    /// or the acknowledgement, whichever comes first.
+    fn dump(&mut self, call: &'static str, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let seq = read_u32(&msg[8..]).unwrap_or(0);
+        self.send(call, msg)?;
+        let mut replies = Vec::new();
+        loop {
+            let buf = self.recv(call)?;
+            for message in Messages::new(&buf) {
+                let message = message?;
+                if message.seq != seq {
+                    continue;
+                }
+                match message.kind {
+                    NLMSG_DONE => return Ok(replies),
+                    NLMSG_ERROR => {
+                        check_error(call, message.payload())?;
+                        return Ok(replies);
+                    }
+                    _ => {
+                        replies.extend_from_slice(message.data);
+                        replies.resize(align(replies.len()), 0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Turns the payload of an `NLMSG_ERROR` message into a result. An error code
+/// of zero is an acknowledgement; anything else is the negated errno `call`
+/// failed with.
+fn check_error(call: &'static str, payload: &[u8]) -> Result<(), Error> {
+    match read_u32(payload).map(|code| code as i32) {
+        Some(0) => Ok(()),
+        Some(code) => Err(Error::from_errno(call, -code)),
+        None => Err(Error::parse(call, "truncated netlink error message")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util;
+    use {Band, Protocol};
+
+    // A reply to a CTRL_CMD_GETFAMILY request for "nl80211", assembled by hand
+    // in the kernel's layout (port id 1234, sequence 2) rather than captured.
+    const FAMILY_REPLY: &[u8] = &[
+        0x5c, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0xd2, 0x04, 0x00, 0x00, 0x01, 0x01, 0x00, 0x00, 0x0c, 0x00, 0x02, 0x00,
+        0x6e, 0x6c, 0x38, 0x30, 0x32, 0x31, 0x31, 0x00, 0x06, 0x00, 0x01, 0x00,
+        0x1c, 0x00, 0x00, 0x00, 0x34, 0x00, 0x07, 0x80, 0x18, 0x00, 0x01, 0x80,
+        0x08, 0x00, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x01, 0x00,
+        0x63, 0x6f, 0x6e, 0x66, 0x69, 0x67, 0x00, 0x00, 0x18, 0x00, 0x02, 0x80,
+        0x08, 0x00, 0x02, 0x00, 0x05, 0x00, 0x00, 0x00, 0x09, 0x00, 0x01, 0x00,
+        0x73, 0x63, 0x61, 0x6e, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // A synthetic NL80211_CMD_GET_SCAN dump, assembled by hand rather than
+    // captured: three BSSes followed by NLMSG_DONE, a WPA2 network on channel
+    // 6, a WPA network on channel 36 that only has beacon IEs, and an open
+    // network on channel 1 that reports no signal.
+    const SCAN_DUMP: &[u8] = &[
+        0x70, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00, 0x00,
+        0xd2, 0x04, 0x00, 0x00, 0x22, 0x01, 0x00, 0x00, 0x08, 0x00, 0x03, 0x00,
+        0x03, 0x00, 0x00, 0x00, 0x54, 0x00, 0x2f, 0x80, 0x0a, 0x00, 0x01, 0x00,
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x00, 0x08, 0x00, 0x02, 0x00,
+        0x85, 0x09, 0x00, 0x00, 0x06, 0x00, 0x05, 0x00, 0x11, 0x04, 0x00, 0x00,
+        0x2b, 0x00, 0x06, 0x00, 0x00, 0x06, 0x64, 0x72, 0x61, 0x64, 0x69, 0x73,
+        0x01, 0x04, 0x82, 0x84, 0x8b, 0x96, 0x03, 0x01, 0x06, 0x30, 0x14, 0x01,
+        0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01,
+        0x00, 0x00, 0x0f, 0xac, 0x02, 0x00, 0x00, 0x00, 0x08, 0x00, 0x07, 0x00,
+        0x6c, 0xee, 0xff, 0xff, 0x6c, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x02, 0x00,
+        0x03, 0x00, 0x00, 0x00, 0xd2, 0x04, 0x00, 0x00, 0x22, 0x01, 0x00, 0x00,
+        0x08, 0x00, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x50, 0x00, 0x2f, 0x80,
+        0x0a, 0x00, 0x01, 0x00, 0x02, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x00, 0x00,
+        0x08, 0x00, 0x02, 0x00, 0x3c, 0x14, 0x00, 0x00, 0x06, 0x00, 0x05, 0x00,
+        0x11, 0x00, 0x00, 0x00, 0x25, 0x00, 0x0b, 0x00, 0x00, 0x07, 0x63, 0x61,
+        0x66, 0xc3, 0xa9, 0x20, 0x35, 0xdd, 0x16, 0x00, 0x50, 0xf2, 0x01, 0x01,
+        0x00, 0x00, 0x50, 0xf2, 0x02, 0x01, 0x00, 0x00, 0x50, 0xf2, 0x02, 0x01,
+        0x00, 0x00, 0x50, 0xf2, 0x02, 0x00, 0x00, 0x00, 0x08, 0x00, 0x07, 0x00,
+        0xe0, 0xe3, 0xff, 0xff, 0x48, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x02, 0x00,
+        0x03, 0x00, 0x00, 0x00, 0xd2, 0x04, 0x00, 0x00, 0x22, 0x01, 0x00, 0x00,
+        0x08, 0x00, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x2f, 0x80,
+        0x0a, 0x00, 0x01, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x66, 0x00, 0x00,
+        0x08, 0x00, 0x02, 0x00, 0x6c, 0x09, 0x00, 0x00, 0x06, 0x00, 0x05, 0x00,
+        0x01, 0x04, 0x00, 0x00, 0x0a, 0x00, 0x06, 0x00, 0x00, 0x04, 0x6f, 0x70,
+        0x65, 0x6e, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00,
+        0x03, 0x00, 0x00, 0x00, 0xd2, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn encodes_passive_trigger_scan() {
+        let msg = trigger_scan_request(0x1c, 7, 3, &ScanRequest::new("wlan0").passive(true));
+        assert_eq!(msg,
+                   vec![0x1c, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x05, 0x00, 0x07, 0x00, 0x00, 0x00,
+                        0x00, 0x00, 0x00, 0x00, 0x21, 0x01, 0x00, 0x00, 0x08, 0x00, 0x03, 0x00,
+                        0x03, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn active_scan_probes_wildcard_ssid() {
+        let msg = trigger_scan_request(0x1c, 7, 3, &ScanRequest::new("wlan0"));
+        assert_eq!(&msg[28..], &[0x08, 0x00, 0x2d, 0x00, 0x04, 0x00, 0x01, 0x00]);
+        assert_eq!(read_u32(&msg[..]), Some(msg.len() as u32));
+    }
+
+    #[test]
+    fn encodes_targeted_trigger_scan() {
+        let request = ScanRequest::new("wlan0").ssid("hidden").frequency(2412).flush(true);
+        let msg = trigger_scan_request(0x1c, 7, 3, &request);
+        let attrs: Vec<(u16, Vec<u8>)> = Attributes::new(&msg[20..])
+            .map(|(kind, value)| (kind, value.to_vec()))
+            .collect();
+        assert_eq!(attrs.len(), 4);
+        assert_eq!(attrs[1].0, NL80211_ATTR_SCAN_SSIDS);
+        assert_eq!(Attributes::new(&attrs[1].1).collect::<Vec<_>>(), vec![(1, &b"hidden"[..])]);
+        assert_eq!(attrs[2].0, NL80211_ATTR_SCAN_FREQUENCIES);
+        assert_eq!(Attributes::new(&attrs[2].1).collect::<Vec<_>>(),
+                   vec![(1, &2412u32.to_ne_bytes()[..])]);
+        assert_eq!(attrs[3], (NL80211_ATTR_SCAN_FLAGS, NL80211_SCAN_FLAG_FLUSH.to_ne_bytes().to_vec()));
+    }
+
+    #[test]
+    fn encodes_get_scan_as_dump() {
+        let msg = get_scan_request(0x1c, 8, 3);
+        assert_eq!(read_u16(&msg[6..]), Some(NLM_F_REQUEST | NLM_F_DUMP));
+        assert_eq!(msg[16], NL80211_CMD_GET_SCAN);
+        assert_eq!(read_u32(&msg[..]), Some(msg.len() as u32));
+    }
+
+    #[test]
+    fn encodes_family_name_with_padding() {
+        let msg = get_family_request(2, "nl80211");
+        assert_eq!(msg.len(), 32);
+        assert_eq!(&msg[20..32], b"\x0c\x00\x02\x00nl80211\x00");
+    }
+
+    #[test]
+    fn decodes_family() {
+        assert_eq!(parse_family(FAMILY_REPLY).unwrap(),
+                   Family {
+                       id: 0x1c,
+                       scan_group: Some(5),
+                   });
+    }
+
+    #[test]
+    fn decodes_scan_dump() {
+        let networks = parse_scan_results(SCAN_DUMP).unwrap();
+        assert_eq!(networks.len(), 3);
+
+        let bssids: Vec<_> = networks.iter().map(|n| n.bssid.unwrap().to_string()).collect();
+        assert_eq!(bssids, vec!["00:11:22:33:44:55", "02:aa:bb:cc:dd:ee", "00:11:22:33:44:66"]);
+        let essids: Vec<_> = networks.iter().map(|n| n.essid.unwrap().to_string()).collect();
+        assert_eq!(essids, vec!["dradis", "caf\u{e9} 5", "open"]);
+        let protocols: Vec<_> = networks.iter().map(|n| n.security.protocol).collect();
+        assert_eq!(protocols, vec![Protocol::Wpa2, Protocol::Wpa, Protocol::Open]);
+        let channels: Vec<_> = networks.iter().map(|n| n.channel().unwrap().number()).collect();
+        assert_eq!(channels, vec![6, 36, 1]);
+        assert_eq!(networks[1].band(), Some(Band::FiveGhz));
+        let freqs: Vec<_> = networks.iter().map(|n| n.freq.unwrap()).collect();
+        assert_eq!(freqs, vec![2.437e9, 5.18e9, 2.412e9]);
+        assert!(networks.iter().all(|n| n.mode == Some(WirelessMode::Master)));
+        assert_eq!(networks[0].maxbitrate, Some(11000000));
+        assert_eq!(networks[1].maxbitrate, None);
+
+        assert_eq!(networks[0].stats.unwrap().quality.level as i8, -45);
+        assert_eq!(networks[1].stats.unwrap().quality.level as i8, -72);
+        assert_eq!(networks[0].signal_dbm(), Some(-45));
+        assert_eq!(networks[0].signal_percent(), Some(100));
+        assert_eq!(networks[1].signal_percent(), Some(56));
+        assert_eq!(networks[0].noise_dbm(), None);
+        assert!(networks[2].stats.is_none());
+    }
+
+    #[test]
+    fn decodes_captured_dumps() {
+        for (name, dump) in test_util::captures("nl80211") {
+            let networks = parse_scan_results(&dump).unwrap_or_else(|err| panic!("{}: {}", name, err));
+            assert!(!networks.is_empty(), "{}: no networks", name);
+            for network in &networks {
+                assert!(network.bssid.is_some(), "{}: {:?}", name, network);
+                assert!(network.channel().is_some(), "{}: {:?}", name, network);
+            }
+        }
+    }
+
+    // Records testdata/nl80211/<interface>.bin from the kernel's cached scan
+    // results, for `decodes_captured_dumps` to replay. Needs a wireless
+    // interface that has scanned recently:
+    // DRADIS_CAPTURE_INTERFACE=wlan0 cargo test records_live_dump -- --ignored
+    #[test]
+    #[ignore]
+    fn records_live_dump() {
+        let interface = test_util::capture_interface();
+        let ifindex = interface_index(&interface).unwrap();
+        let mut control = Socket::open().unwrap();
+        let seq = control.next_seq();
+        let family = parse_family(&control.dump("CTRL_CMD_GETFAMILY",
+                                                &get_family_request(seq, "nl80211"))
+                                          .unwrap())
+            .unwrap();
+        let seq = control.next_seq();
+        let dump = control.dump("NL80211_CMD_GET_SCAN", &get_scan_request(family.id, seq, ifindex))
+                          .unwrap();
+        assert!(!parse_scan_results(&dump).unwrap().is_empty(), "no cached scan results on {}", interface);
+        test_util::save_capture("nl80211", &interface, &dump);
+    }
+
+    #[test]
+    fn mode_and_rate_from_beacon() {
+        assert_eq!(mode(0x0002), Some(WirelessMode::AdHoc));
+        assert_eq!(mode(0x0010), None);
+        // 1, 54 and 6 Mb/s, then the HT PHY membership selector.
+        assert_eq!(max_rate(&[0x01, 0x04, 0x82, 0x6c, 0x8c, 0xff]), Some(54000000));
+        assert_eq!(max_rate(&[]), None);
+    }
+
+    #[test]
+    fn truncated_dump_is_an_error() {
+        assert!(parse_scan_results(&SCAN_DUMP[..SCAN_DUMP.len() - 30]).is_err());
+        assert_eq!(parse_scan_results(&[]).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn truncated_attributes_are_ignored() {
+        // Chop the first message short but fix up its length so only the
+        // attributes inside it are damaged.
+        let mut msg = SCAN_DUMP[..60].to_vec();
+        let len = (msg.len() as u32).to_ne_bytes();
+        msg[..4].copy_from_slice(&len);
+        let networks = parse_scan_results(&msg).unwrap();
+        assert!(networks.len() <= 1);
+    }
+
+    #[test]
+    fn scan_events_match_interface() {
+        let mut done = MessageBuilder::new(0x1c, 0, 0, NL80211_CMD_NEW_SCAN_RESULTS);
+        done.put_u32(NL80211_ATTR_IFINDEX, 3);
+        let done = done.finish();
+        assert_eq!(scan_event(&done, 0x1c, 3).unwrap(), Some(NL80211_CMD_NEW_SCAN_RESULTS));
+        assert_eq!(scan_event(&done, 0x1c, 4).unwrap(), None);
+        assert_eq!(scan_event(&done, 0x1d, 3).unwrap(), None);
+    }
+
+    #[test]
+    fn error_messages() {
+        assert!(check_error("NL80211_CMD_TRIGGER_SCAN", &0i32.to_ne_bytes()).is_ok());
+        let err = check_error("NL80211_CMD_TRIGGER_SCAN", &(-libc::EBUSY).to_ne_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Busy { call: "NL80211_CMD_TRIGGER_SCAN", .. }));
+        assert_eq!(err.errno(), Some(libc::EBUSY));
+        let err = check_error("NL80211_CMD_TRIGGER_SCAN", &(-libc::EPERM).to_ne_bytes()).unwrap_err();
+        assert!(matches!(err, Error::PermissionDenied { .. }));
+        assert!(matches!(check_error("CTRL_CMD_GETFAMILY", &[0, 0]), Err(Error::Parse { .. })));
+    }
+}
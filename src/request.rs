@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use {Channel, Error, RetryPolicy};
+use ssid::SSID_MAX_LEN;
+
+/// What to scan for and how: the interface, which hidden networks to probe
+/// for, which frequencies to visit, whether to transmit at all, how long to
+/// wait, and what to do if the driver is busy or the scan isn't allowed.
+///
+/// A backend that can't do what a request asks fails the scan with
+/// `Error::Unsupported` rather than quietly doing a different scan.
+///
+/// ```
+/// use dradis::{Band, Channel, ScanRequest};
+/// use std::time::Duration;
+///
+/// // Look for one hidden network on channel 36 only.
+/// let request = ScanRequest::new("wlan0")
+///     .ssid("backroom")
+///     .channel(Channel::new(Band::FiveGhz, 36).unwrap())
+///     .timeout(Duration::from_millis(500));
+/// assert_eq!(request.frequencies, vec![5180]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanRequest {
+    pub interface: String,
+    /// Networks to send directed probe requests for, so hidden ones answer.
+    /// Empty means a wildcard probe.
+    pub ssids: Vec<Vec<u8>>,
+    /// Center frequencies in MHz to scan. Empty means every allowed channel.
+    pub frequencies: Vec<u32>,
+    /// Only listen for beacons, don't send probe requests.
+    pub passive: bool,
+    /// Throw away cached results from earlier scans.
+    pub flush: bool,
+    /// How long to wait for the scan, instead of the backend's own timeout.
+    pub timeout: Option<Duration>,
+    /// How to retry a scan the driver was too busy for. `WifiScan` and
+    /// `ScanMonitor` follow it; a `ScanFuture` fails with `Error::Busy`
+    /// straight away.
+    pub retry: RetryPolicy,
+    /// Without the privileges to trigger a scan, return the kernel's cached
+    /// results from the last one instead, marked stale, the way `iwlist`
+    /// behaves. Off by default, so such a scan fails with `PermissionDenied`.
+    pub cached_if_denied: bool,
+}
+
+impl ScanRequest {
+    /// A full, active scan of `interface`, the same as the backends do by
+    /// default.
+    pub fn new(interface: &str) -> ScanRequest {
+        ScanRequest {
+            interface: interface.to_string(),
+            ssids: Vec::new(),
+            frequencies: Vec::new(),
+            passive: false,
+            flush: false,
+            timeout: None,
+            retry: RetryPolicy::new(),
+            cached_if_denied: false,
+        }
+    }
+
+    /// Probe for the network named `ssid` as well.
+    pub fn ssid<S: AsRef<[u8]>>(mut self, ssid: S) -> ScanRequest {
+        self.ssids.push(ssid.as_ref().to_vec());
+        self
+    }
+
+    /// Scan `mhz` as well as any frequencies already added.
+    pub fn frequency(mut self, mhz: u32) -> ScanRequest {
+        if !self.frequencies.contains(&mhz) {
+            self.frequencies.push(mhz);
+        }
+        self
+    }
+
+    /// Scan `channel` as well as any frequencies already added.
+    pub fn channel(self, channel: Channel) -> ScanRequest {
+        self.frequency(channel.mhz())
+    }
+
+    pub fn passive(mut self, passive: bool) -> ScanRequest {
+        self.passive = passive;
+        self
+    }
+
+    pub fn flush(mut self, flush: bool) -> ScanRequest {
+        self.flush = flush;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> ScanRequest {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> ScanRequest {
+        self.retry = retry;
+        self
+    }
+
+    pub fn cached_if_denied(mut self, cached: bool) -> ScanRequest {
+        self.cached_if_denied = cached;
+        self
+    }
+
+    /// True if the request asks for anything beyond a default scan of the
+    /// interface.
+    pub fn is_targeted(&self) -> bool {
+        !self.ssids.is_empty() || !self.frequencies.is_empty() || self.passive || self.flush
+    }
+
+    /// Check the request makes sense before any backend acts on it.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if self.ssids.iter().any(|ssid| ssid.len() > SSID_MAX_LEN) {
+            return Err(Error::InvalidInput {
+                call: "ScanRequest",
+                message: "SSIDs are at most 32 bytes",
+            });
+        }
+        if self.passive && !self.ssids.is_empty() {
+            return Err(Error::InvalidInput {
+                call: "ScanRequest",
+                message: "a passive scan can't probe for SSIDs",
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a str> for ScanRequest {
+    fn from(interface: &'a str) -> ScanRequest {
+        ScanRequest::new(interface)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Band;
+
+    #[test]
+    fn builds_targeted_requests() {
+        let request = ScanRequest::new("wlan0")
+            .ssid("hidden")
+            .ssid(b"\xffraw")
+            .frequency(2412)
+            .channel(Channel::new(Band::TwoGhz, 1).unwrap())
+            .channel(Channel::new(Band::TwoGhz, 6).unwrap())
+            .flush(true);
+        assert_eq!(request.ssids, vec![b"hidden".to_vec(), b"\xffraw".to_vec()]);
+        assert_eq!(request.frequencies, vec![2412, 2437]);
+        assert!(request.flush && !request.passive && request.is_targeted());
+        assert!(request.validate().is_ok());
+        assert!(!ScanRequest::from("wlan0").is_targeted());
+    }
+
+    #[test]
+    fn rejects_nonsense() {
+        let long = ScanRequest::new("wlan0").ssid([b'x'; 33]);
+        assert!(matches!(long.validate(), Err(Error::InvalidInput { .. })));
+        let passive_probe = ScanRequest::new("wlan0").ssid("x").passive(true);
+        assert!(matches!(passive_probe.validate(), Err(Error::InvalidInput { .. })));
+    }
+}
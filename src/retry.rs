@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use {Clock, Error, ScanBackend, ScanRequest, WifiScan};
+
+/// What to do when a scan fails because the driver is busy, usually because
+/// NetworkManager or wpa_supplicant is scanning at the same time.
+///
+/// Each retry waits twice as long as the one before, up to `max_backoff`.
+/// Once the retries run out the scan fails with `Error::Busy`, or, with
+/// `fallback_to_cached`, returns the results the kernel kept from the last
+/// scan instead. Other errors aren't retried.
+///
+/// ```
+/// use dradis::{RetryPolicy, ScanRequest};
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new()
+///     .retries(5)
+///     .backoff(Duration::from_millis(100), Duration::from_secs(1))
+///     .fallback_to_cached(true);
+/// assert_eq!(policy.backoff_before(1), Duration::from_millis(100));
+/// assert_eq!(policy.backoff_before(3), Duration::from_millis(400));
+/// assert_eq!(policy.backoff_before(5), Duration::from_secs(1));
+/// let request = ScanRequest::new("wlan0").retry(policy);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times to try again after the first attempt.
+    pub retries: u32,
+    /// How long to wait before the first retry.
+    pub initial_backoff: Duration,
+    /// The longest to wait between two attempts.
+    pub max_backoff: Duration,
+    /// Return the kernel's cached results once the retries run out.
+    pub fallback_to_cached: bool,
+}
+
+impl RetryPolicy {
+    /// Three retries, waiting 250ms, then 500ms, then 1s, and no fallback.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(2),
+            fallback_to_cached: false,
+        }
+    }
+
+    /// Fail on the first busy error.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy { retries: 0, ..RetryPolicy::new() }
+    }
+
+    pub fn retries(mut self, retries: u32) -> RetryPolicy {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait `initial` before the first retry, doubling each time up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> RetryPolicy {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn fallback_to_cached(mut self, fallback: bool) -> RetryPolicy {
+        self.fallback_to_cached = fallback;
+        self
+    }
+
+    /// How long to wait before retry number `retry`, counting from 1.
+    pub fn backoff_before(&self, retry: u32) -> Duration {
+        let doublings = retry.saturating_sub(1).min(31);
+        self.initial_backoff
+            .checked_mul(1 << doublings)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::new()
+    }
+}
+
+/// Run the scan `request` describes on `backend`, retrying while the driver
+/// is busy as `request.retry` says, with `clock` doing the waiting. Falls back
+/// to the cached results where the request allows it.
+pub(crate) fn scan<B, C>(backend: &mut B,
+                         request: &ScanRequest,
+                         clock: &C)
+                         -> Result<WifiScan, Error>
+    where B: ScanBackend + ?Sized,
+          C: Clock
+{
+    let policy = request.retry;
+    let mut retry = 0;
+    loop {
+        match backend.scan(request) {
+            Ok(networks) => {
+                return Ok(WifiScan {
+                    networks,
+                    stale: false,
+                })
+            }
+            Err(Error::Busy { .. }) if retry < policy.retries => {
+                retry += 1;
+                clock.sleep(policy.backoff_before(retry));
+            }
+            Err(Error::Busy { .. }) if policy.fallback_to_cached => {
+                return WifiScan::cached_with(backend, &request.interface)
+            }
+            Err(Error::PermissionDenied { .. }) if request.cached_if_denied => {
+                return WifiScan::cached_with(backend, &request.interface)
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {FixtureBackend, ManualClock, Ssid};
+    use test_util::network;
+
+    fn busy() -> Error {
+        Error::from_errno("SIOCSIWSCAN", ::libc::EBUSY)
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let policy = RetryPolicy::new().backoff(Duration::from_millis(10), Duration::from_millis(70));
+        let waits: Vec<u128> = (1..6).map(|retry| policy.backoff_before(retry).as_millis()).collect();
+        assert_eq!(waits, vec![10, 20, 40, 70, 70]);
+        assert_eq!(policy.backoff_before(u32::MAX), Duration::from_millis(70));
+    }
+
+    #[test]
+    fn retries_while_busy() {
+        let mut backend = FixtureBackend::new(vec![network("home")]);
+        backend.fail(busy());
+        backend.fail(busy());
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let scan = scan(&mut backend, &"wlan0".into(), &clock).unwrap();
+        assert_eq!(scan.networks[0].essid, Ssid::new(b"home"));
+        assert!(!scan.stale);
+        assert_eq!(backend.scan_count(), 3);
+        assert_eq!(clock.now() - start, Duration::from_millis(750));
+    }
+
+    #[test]
+    fn gives_up_or_falls_back() {
+        let mut backend = FixtureBackend::new(vec![network("home")]);
+        let request = ScanRequest::new("wlan0").retry(RetryPolicy::new().retries(1));
+        scan(&mut backend, &request, &ManualClock::new()).unwrap();
+        backend.fail(busy());
+        backend.fail(busy());
+        let err = scan(&mut backend, &request, &ManualClock::new()).unwrap_err();
+        assert!(matches!(err, Error::Busy { .. }));
+
+        backend.fail(busy());
+        backend.fail(busy());
+        let request = request.retry(RetryPolicy::new().retries(1).fallback_to_cached(true));
+        let cached = scan(&mut backend, &request, &ManualClock::new()).unwrap();
+        assert_eq!(cached.networks[0].essid, Ssid::new(b"home"));
+        assert!(cached.stale);
+        assert_eq!(backend.scan_count(), 5);
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        let mut backend = FixtureBackend::new(Vec::new());
+        backend.fail(Error::from_errno("SIOCSIWSCAN", ::libc::EIO));
+        let err = scan(&mut backend, &"wlan0".into(), &ManualClock::new()).unwrap_err();
+        assert_eq!(err.errno(), Some(::libc::EIO));
+        assert_eq!(backend.scan_count(), 1);
+    }
+
+    #[test]
+    fn degrades_to_cached_results_without_privileges_if_asked() {
+        let mut backend = FixtureBackend::new(vec![network("home")]);
+        scan(&mut backend, &"wlan0".into(), &ManualClock::new()).unwrap();
+        backend.fail(Error::from_errno("SIOCSIWSCAN", ::libc::EPERM));
+        let result = scan(&mut backend, &"wlan0".into(), &ManualClock::new());
+        assert!(matches!(result, Err(Error::PermissionDenied { .. })));
+
+        backend.fail(Error::from_errno("SIOCSIWSCAN", ::libc::EPERM));
+        let lenient = ScanRequest::new("wlan0").cached_if_denied(true);
+        let cached = scan(&mut backend, &lenient, &ManualClock::new()).unwrap();
+        assert_eq!(cached.networks[0].essid, Ssid::new(b"home"));
+        assert!(cached.stale);
+    }
+}
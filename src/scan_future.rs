@@ -0,0 +1,388 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use {Error, PendingScan, ScanBackend, ScanRequest, WifiScan};
+use backend::FinishedScan;
+
+/// A scan running in the background, resolving to its results once the
+/// backend reports it finished.
+///
+/// It works with any executor. Between polls a helper thread wakes the task
+/// every `PendingScan::poll_interval`; with the `tokio` feature,
+/// `tokio_timer` switches that to a tokio timer.
+///
+/// Dropping the future before it resolves cancels the scan. Busy scans aren't
+/// retried, but a scan without the privileges to trigger it resolves to the
+/// cached results if the request allows that, like a blocking scan.
+///
+/// ```no_run
+/// # fn block_on<F: std::future::Future>(f: F) -> F::Output { unimplemented!() }
+/// use dradis::WifiScan;
+///
+/// let scan = block_on(WifiScan::scan_async("wlan0")).unwrap();
+/// println!("{} networks", scan.networks.len());
+/// ```
+pub struct ScanFuture {
+    pending: Option<Box<dyn PendingScan>>,
+    error: Option<Error>,
+    stale: bool,
+    shared: Arc<Shared>,
+    ticking: bool,
+    #[cfg(feature = "tokio")]
+    tokio_timer: bool,
+    #[cfg(feature = "tokio")]
+    sleep: Option<Pin<Box<::tokio::time::Sleep>>>,
+}
+
+/// Cancels the `ScanFuture` it came from, from anywhere.
+#[derive(Clone)]
+pub struct CancelHandle {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    waker: Mutex<Option<Waker>>,
+    cancelled: AtomicBool,
+    done: AtomicBool,
+}
+
+impl Shared {
+    fn wake(&self) {
+        if let Some(ref waker) = *self.waker.lock().unwrap() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+impl ScanFuture {
+    /// Trigger the scan `request` describes on `backend`. Starting the scan
+    /// doesn't wait for it; an error starting it comes out of the first poll.
+    pub fn new<B: ScanBackend + ?Sized>(backend: &mut B, request: &ScanRequest) -> ScanFuture {
+        let mut stale = false;
+        let started = match backend.start_scan(request) {
+            Err(Error::PermissionDenied { .. }) if request.cached_if_denied => {
+                stale = true;
+                backend.cached_results(request)
+                    .map(|networks| Box::new(FinishedScan::new(networks)) as Box<dyn PendingScan>)
+            }
+            started => started,
+        };
+        let (pending, error) = match started {
+            Ok(pending) => (Some(pending), None),
+            Err(err) => (None, Some(err)),
+        };
+        ScanFuture {
+            pending,
+            error,
+            stale,
+            shared: Arc::new(Shared {
+                waker: Mutex::new(None),
+                cancelled: AtomicBool::new(false),
+                done: AtomicBool::new(false),
+            }),
+            ticking: false,
+            #[cfg(feature = "tokio")]
+            tokio_timer: false,
+            #[cfg(feature = "tokio")]
+            sleep: None,
+        }
+    }
+
+    /// Wait between polls on a tokio timer instead of a helper thread when
+    /// polled inside a tokio runtime. tokio can't be asked whether a runtime
+    /// has its time driver, and its timers panic without one, so this is up
+    /// to the caller: only use it on runtimes built with `enable_time` or
+    /// `enable_all`.
+    #[cfg(feature = "tokio")]
+    pub fn tokio_timer(mut self) -> ScanFuture {
+        self.tokio_timer = true;
+        self
+    }
+
+    /// A handle that makes this future resolve to `Error::Cancelled` and stops
+    /// the scan.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle { shared: self.shared.clone() }
+    }
+
+    fn finish(&mut self) -> Option<Box<dyn PendingScan>> {
+        self.shared.done.store(true, Ordering::SeqCst);
+        self.pending.take()
+    }
+
+    fn cancelled(&mut self) -> bool {
+        if !self.shared.cancelled.load(Ordering::SeqCst) {
+            return false;
+        }
+        if let Some(mut pending) = self.finish() {
+            pending.cancel();
+        }
+        true
+    }
+
+    /// Arrange for the task to be woken after `interval`. Returns true if
+    /// that time has already passed and the scan can be polled again now.
+    #[cfg(feature = "tokio")]
+    fn wait(&mut self, cx: &mut Context, interval: Duration) -> bool {
+        if !self.tokio_timer || ::tokio::runtime::Handle::try_current().is_err() {
+            self.tick(interval);
+            return false;
+        }
+        let ready = self.sleep
+            .get_or_insert_with(|| Box::pin(::tokio::time::sleep(interval)))
+            .as_mut()
+            .poll(cx)
+            .is_ready();
+        if ready {
+            self.sleep = None;
+        }
+        ready
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn wait(&mut self, _cx: &mut Context, interval: Duration) -> bool {
+        self.tick(interval);
+        false
+    }
+
+    /// Start the helper thread that wakes the task every `interval` until the
+    /// scan is over.
+    fn tick(&mut self, interval: Duration) {
+        if self.ticking {
+            return;
+        }
+        self.ticking = true;
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            while !shared.done.load(Ordering::SeqCst) {
+                thread::sleep(interval);
+                shared.wake();
+            }
+        });
+    }
+}
+
+impl Future for ScanFuture {
+    type Output = Result<WifiScan, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            this.finish();
+            return Poll::Ready(Err(err));
+        }
+        loop {
+            if this.cancelled() {
+                return Poll::Ready(Err(Error::Cancelled));
+            }
+            let interval = match this.pending {
+                Some(ref mut pending) => {
+                    match pending.poll_results() {
+                        Ok(Some(networks)) => {
+                            this.finish();
+                            return Poll::Ready(Ok(WifiScan {
+                                networks,
+                                stale: this.stale,
+                            }));
+                        }
+                        Ok(None) => pending.poll_interval(),
+                        Err(err) => {
+                            this.finish();
+                            return Poll::Ready(Err(err));
+                        }
+                    }
+                }
+                None => {
+                    return Poll::Ready(Err(Error::InvalidInput {
+                        call: "ScanFuture::poll",
+                        message: "scan future polled after completion",
+                    }))
+                }
+            };
+            *this.shared.waker.lock().unwrap() = Some(cx.waker().clone());
+            // Catch a cancel that came in before the waker was stored.
+            if this.shared.cancelled.load(Ordering::SeqCst) || this.wait(cx, interval) {
+                continue;
+            }
+            return Poll::Pending;
+        }
+    }
+}
+
+impl Drop for ScanFuture {
+    fn drop(&mut self) {
+        if let Some(mut pending) = self.finish() {
+            pending.cancel();
+        }
+    }
+}
+
+impl CancelHandle {
+    /// Cancel the scan. Does nothing if it has already finished.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+        self.shared.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::task::Wake;
+    use {FixtureBackend, WirelessNetwork};
+    use test_util::{essids, network};
+
+    struct Unpark {
+        thread: thread::Thread,
+        wakes: AtomicUsize,
+    }
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.wakes.fetch_add(1, Ordering::SeqCst);
+            self.thread.unpark();
+        }
+    }
+
+    /// Drive `future` to completion on this thread, returning its output and
+    /// how many times it was woken.
+    fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+        let unpark = Arc::new(Unpark {
+            thread: thread::current(),
+            wakes: AtomicUsize::new(0),
+        });
+        let waker = Waker::from(unpark.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return (output, unpark.wakes.load(Ordering::SeqCst));
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn resolves_once_the_scan_finishes() {
+        let mut backend = FixtureBackend::new(vec![network("home")]).delay(3);
+        let (scan, wakes) = block_on(WifiScan::scan_async_with(&mut backend, "wlan0"));
+        assert_eq!(essids(&scan.unwrap().networks), vec!["home"]);
+        assert!(wakes >= 3);
+    }
+
+    #[test]
+    fn blocking_scan_waits_too() {
+        let mut backend = FixtureBackend::new(vec![network("cafe")]).delay(2);
+        let scan = WifiScan::scan_with(&mut backend, "wlan0").unwrap();
+        assert_eq!(essids(&scan.networks), vec!["cafe"]);
+    }
+
+    #[test]
+    fn cancel_interrupts_the_scan() {
+        let mut backend = FixtureBackend::new(vec![network("home")]).delay(usize::MAX);
+        let future = WifiScan::scan_async_with(&mut backend, "wlan0");
+        let handle = future.cancel_handle();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            handle.cancel();
+        });
+        let err = block_on(future).0.unwrap_err();
+        canceller.join().unwrap();
+        assert!(matches!(err, Error::Cancelled));
+    }
+
+    struct Recorder(Arc<AtomicBool>);
+
+    impl PendingScan for Recorder {
+        fn poll_results(&mut self) -> Result<Option<Vec<WirelessNetwork>>, Error> {
+            Ok(None)
+        }
+
+        fn cancel(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    struct RecordingBackend(Arc<AtomicBool>);
+
+    impl ScanBackend for RecordingBackend {
+        fn start_scan(&mut self, _request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+            Ok(Box::new(Recorder(self.0.clone())))
+        }
+    }
+
+    #[test]
+    fn dropping_cancels_the_backend_scan() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut backend = RecordingBackend(cancelled.clone());
+        let mut future = ScanFuture::new(&mut backend, &"wlan0".into());
+        let waker = Waker::from(Arc::new(Unpark {
+            thread: thread::current(),
+            wakes: AtomicUsize::new(0),
+        }));
+        assert!(Pin::new(&mut future).poll(&mut Context::from_waker(&waker)).is_pending());
+        assert!(!cancelled.load(Ordering::SeqCst));
+        drop(future);
+        assert!(cancelled.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn start_errors_come_out_of_the_first_poll() {
+        struct Failing;
+        impl ScanBackend for Failing {
+            fn start_scan(&mut self, _request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+                Err(Error::from_errno("if_nametoindex", libc::ENODEV))
+            }
+        }
+        let (result, _) = block_on(ScanFuture::new(&mut Failing, &"wlan9".into()));
+        assert!(matches!(result, Err(Error::NoSuchInterface { .. })));
+    }
+
+    #[test]
+    fn unprivileged_scans_resolve_to_cached_results_if_asked() {
+        let mut backend = FixtureBackend::new(vec![network("home")]);
+        WifiScan::scan_with(&mut backend, "wlan0").unwrap();
+        backend.fail(Error::from_errno("SIOCSIWSCAN", libc::EPERM));
+        let (result, _) = block_on(WifiScan::scan_async_with(&mut backend, "wlan0"));
+        assert!(matches!(result, Err(Error::PermissionDenied { .. })));
+
+        backend.fail(Error::from_errno("SIOCSIWSCAN", libc::EPERM));
+        let request = ScanRequest::new("wlan0").cached_if_denied(true);
+        let (scan, _) = block_on(ScanFuture::new(&mut backend, &request));
+        let scan = scan.unwrap();
+        assert_eq!(essids(&scan.networks), vec!["home"]);
+        assert!(scan.stale);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn runs_on_tokio() {
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut backend = FixtureBackend::new(vec![network("home")]).delay(2);
+        let future = WifiScan::scan_async_with(&mut backend, "wlan0").tokio_timer();
+        let scan = runtime.block_on(future).unwrap();
+        assert_eq!(essids(&scan.networks), vec!["home"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn runs_on_tokio_without_timers() {
+        let runtime = ::tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let mut backend = FixtureBackend::new(vec![network("home")]).delay(2);
+        let scan = runtime.block_on(WifiScan::scan_async_with(&mut backend, "wlan0")).unwrap();
+        assert_eq!(essids(&scan.networks), vec!["home"]);
+    }
+}
@@ -0,0 +1,289 @@
+//! The serialized form of scan results, with the `serde` feature.
+//!
+//! A `WifiScan` serializes to a document carrying `VERSION`, and
+//! deserializing one with a different version fails rather than guessing.
+//! `JSON_SCHEMA` is the JSON Schema for the document, also checked in as
+//! `schema/scan-v1.json`, for validating payloads away from Rust.
+//!
+//! Most fields serialize as they're named in Rust. The exceptions: BSSIDs are
+//! `"00:11:22:aa:bb:cc"` strings, SSIDs are strings when they're UTF-8 and
+//! arrays of bytes when they aren't, and information elements and keys are
+//! lowercase hex.
+//!
+//! ```
+//! # extern crate dradis;
+//! # extern crate serde_json;
+//! use dradis::WifiScan;
+//!
+//! let scan = WifiScan { networks: Vec::new(), stale: false };
+//! let json = serde_json::to_string(&scan).unwrap();
+//! assert_eq!(json, r#"{"version":1,"networks":[],"stale":false}"#);
+//! assert_eq!(serde_json::from_str::<WifiScan>(&json).unwrap(), scan);
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, SeqAccess, Visitor};
+
+use {MacAddress, Ssid, WifiScan, WirelessNetwork};
+
+/// The version of the format this crate writes and reads.
+pub const VERSION: u32 = 1;
+
+/// The JSON Schema for version `VERSION` of the format.
+pub const JSON_SCHEMA: &str = include_str!("../schema/scan-v1.json");
+
+/// A `WifiScan` as it goes over the wire.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ScanDocument {
+    version: u32,
+    networks: Vec<WirelessNetwork>,
+    stale: bool,
+}
+
+impl From<WifiScan> for ScanDocument {
+    fn from(scan: WifiScan) -> ScanDocument {
+        ScanDocument {
+            version: VERSION,
+            networks: scan.networks,
+            stale: scan.stale,
+        }
+    }
+}
+
+impl TryFrom<ScanDocument> for WifiScan {
+    type Error = String;
+
+    fn try_from(document: ScanDocument) -> Result<WifiScan, String> {
+        if document.version != VERSION {
+            return Err(format!("unsupported scan format version {}, expected {}",
+                               document.version,
+                               VERSION));
+        }
+        Ok(WifiScan {
+            networks: document.networks,
+            stale: document.stale,
+        })
+    }
+}
+
+/// Byte strings as lowercase hex, for `#[serde(with)]`.
+pub(crate) mod hex {
+    use std::fmt::Write;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut text = String::with_capacity(bytes.len() * 2);
+        for byte in bytes {
+            write!(text, "{:02x}", byte).unwrap();
+        }
+        serializer.serialize_str(&text)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        if text.len() % 2 != 0 || !text.is_ascii() {
+            return Err(D::Error::custom("expected an even number of hex digits"));
+        }
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+impl Serialize for Ssid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_str() {
+            Some(text) => serializer.serialize_str(text),
+            None => serializer.collect_seq(self.as_bytes()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Ssid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Ssid, D::Error> {
+        deserializer.deserialize_any(SsidVisitor)
+    }
+}
+
+struct SsidVisitor;
+
+impl<'de> Visitor<'de> for SsidVisitor {
+    type Value = Ssid;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an SSID of at most 32 bytes, as a string or an array of bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Ssid, E> {
+        self.visit_bytes(text.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Ssid, E> {
+        Ssid::new(bytes).ok_or_else(|| E::invalid_length(bytes.len(), &self))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Ssid, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MacAddress, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{self, Value};
+    use ie::{AkmSuite, CipherSuite};
+    use {IwQuality, IwStats, Protocol, Security, WirelessKey, WirelessMode};
+    use security::WPA_OUI;
+
+    fn network() -> WirelessNetwork {
+        WirelessNetwork {
+            bssid: Some(MacAddress::new([0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc])),
+            stats: Some(IwStats::new(IwQuality::new(55, 201, 161, IwQuality::ALL_UPDATED | IwQuality::DBM))),
+            max_quality: Some(IwQuality::new(70, 0, 0, IwQuality::DBM)),
+            maxbitrate: Some(54000000),
+            freq: Some(2.412e9),
+            key: Some(WirelessKey::new(&[0xde, 0xad], 0x0001)),
+            essid: Ssid::new(b"home"),
+            mode: Some(WirelessMode::Master),
+            security: Security {
+                protocol: Protocol::Wpa2,
+                group_cipher: Some(CipherSuite::Tkip),
+                pairwise_ciphers: vec![CipherSuite::Ccmp128],
+                akm_suites: vec![AkmSuite::Psk, AkmSuite::Other(WPA_OUI, 2)],
+                pmf_required: false,
+                pmf_capable: true,
+                wpa_compatible: true,
+            },
+            ies: vec![0x00, 0x04, b'h', b'o', b'm', b'e'],
+        }
+    }
+
+    #[test]
+    fn format_is_stable() {
+        let scan = WifiScan {
+            networks: vec![network()],
+            stale: true,
+        };
+        let expected = ::serde_json::json!({
+            "version": 1,
+            "networks": [{
+                "bssid": "00:11:22:aa:bb:cc",
+                "stats": {
+                    "status": 0,
+                    "quality": { "quality": 55, "level": 201, "noise": 161, "updated": 15 },
+                    "discard": [0, 0, 0, 0, 0],
+                    "miss": 0
+                },
+                "max_quality": { "quality": 70, "level": 0, "noise": 0, "updated": 8 },
+                "maxbitrate": 54000000,
+                "freq": 2412000000.0,
+                "key": { "key": "dead", "flags": 1 },
+                "essid": "home",
+                "mode": "Master",
+                "security": {
+                    "protocol": "Wpa2",
+                    "group_cipher": "Tkip",
+                    "pairwise_ciphers": ["Ccmp128"],
+                    "akm_suites": ["Psk", { "Other": [[0, 80, 242], 2] }],
+                    "pmf_required": false,
+                    "pmf_capable": true,
+                    "wpa_compatible": true
+                },
+                "ies": "0004686f6d65"
+            }],
+            "stale": true
+        });
+        assert_eq!(serde_json::to_value(&scan).unwrap(), expected);
+        assert_eq!(serde_json::from_value::<WifiScan>(expected).unwrap(), scan);
+    }
+
+    #[test]
+    fn round_trips_empty_and_odd_networks() {
+        let empty = WirelessNetwork {
+            essid: Ssid::new(b"caf\xc3\xa9\x00\xff"),
+            ..WirelessNetwork::default()
+        };
+        let scan = WifiScan {
+            networks: vec![network(), empty],
+            stale: false,
+        };
+        let json = serde_json::to_string(&scan).unwrap();
+        assert!(json.contains(r#""essid":[99,97,102,195,169,0,255]"#));
+        assert_eq!(serde_json::from_str::<WifiScan>(&json).unwrap(), scan);
+    }
+
+    #[test]
+    fn rejects_other_versions_and_bad_fields() {
+        let future = r#"{"version":2,"networks":[],"stale":false}"#;
+        let err = serde_json::from_str::<WifiScan>(future).unwrap_err();
+        assert!(err.to_string().contains("version 2"));
+
+        let mut json = serde_json::to_value(network()).unwrap();
+        json["ies"] = Value::from("abc");
+        assert!(serde_json::from_value::<WirelessNetwork>(json.clone()).is_err());
+        json["ies"] = Value::from("");
+        json["essid"] = Value::from("x".repeat(33));
+        assert!(serde_json::from_value::<WirelessNetwork>(json.clone()).is_err());
+        json["essid"] = Value::Null;
+        json["bssid"] = Value::from("00:11:22");
+        assert!(serde_json::from_value::<WirelessNetwork>(json).is_err());
+    }
+
+    fn properties<'a>(schema: &'a Value, pointer: &str) -> Vec<&'a str> {
+        let mut names: Vec<&str> = schema.pointer(pointer)
+            .and_then(Value::as_object)
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut names: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn schema_matches_the_format() {
+        let schema: Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+        assert_eq!(schema["properties"]["version"]["const"], VERSION);
+        let scan = serde_json::to_value(WifiScan {
+            networks: vec![network()],
+            stale: false,
+        })
+            .unwrap();
+        let network = &scan["networks"][0];
+        assert_eq!(properties(&schema, "/properties"), keys(&scan));
+        assert_eq!(properties(&schema, "/$defs/network/properties"), keys(network));
+        assert_eq!(properties(&schema, "/$defs/security/properties"), keys(&network["security"]));
+        assert_eq!(properties(&schema, "/$defs/stats/properties"), keys(&network["stats"]));
+        assert_eq!(properties(&schema, "/$defs/quality/properties"), keys(&network["max_quality"]));
+        assert_eq!(properties(&schema, "/$defs/key/properties"), keys(&network["key"]));
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+
+use ie::{self, AkmSuite, CipherSuite, Element, Rsn};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The vendor element type of the original WPA element under `WPA_OUI`.
+const WPA_ELEMENT_TYPE: u8 = 1;
+
+/// The OUI of the pre-standard WPA element and its suites.
+pub const WPA_OUI: [u8; 3] = [0x00, 0x50, 0xf2];
+
+/// The security protocol generation a network offers, ordered from weakest to
+/// strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Protocol {
+    #[default]
+    Open,
+    Wep,
+    Wpa,
+    Wpa2,
+    Wpa3,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Protocol::Open => "Open",
+            Protocol::Wep => "WEP",
+            Protocol::Wpa => "WPA",
+            Protocol::Wpa2 => "WPA2",
+            Protocol::Wpa3 => "WPA3",
+        })
+    }
+}
+
+/// How a network protects itself, decoded from its RSN and WPA elements.
+///
+/// `protocol` is the strongest generation the network offers. A WPA2/WPA3
+/// transition network advertises both PSK and SAE and so reports `Wpa3`, with
+/// `is_transition_mode` telling it apart from a network that only takes SAE.
+///
+/// ```
+/// use dradis::{Protocol, Security};
+///
+/// // RSN: CCMP group and pairwise, PSK and SAE, MFP capable.
+/// let ies = [0x30, 0x18, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac,
+///            0x04, 0x02, 0x00, 0x00, 0x0f, 0xac, 0x02, 0x00, 0x0f, 0xac, 0x08, 0x80, 0x00];
+/// let security = Security::from_ies(&ies, true);
+/// assert_eq!(security.protocol, Protocol::Wpa3);
+/// assert!(security.is_transition_mode());
+/// assert!(security.pmf_capable && !security.pmf_required);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Security {
+    pub protocol: Protocol,
+    pub group_cipher: Option<CipherSuite>,
+    /// Pairwise ciphers from the RSN element and, for WPA/WPA2 mixed mode
+    /// networks, the WPA element, without duplicates.
+    pub pairwise_ciphers: Vec<CipherSuite>,
+    /// AKM suites from the RSN element, or from the WPA element on networks
+    /// that only send that one. What a mixed mode network offers WPA clients
+    /// doesn't change what it is to everyone else.
+    pub akm_suites: Vec<AkmSuite>,
+    pub pmf_required: bool,
+    pub pmf_capable: bool,
+    /// The network also sends the original WPA element for older clients.
+    pub wpa_compatible: bool,
+}
+
+impl Security {
+    /// An unprotected network.
+    pub fn open() -> Security {
+        Security::default()
+    }
+
+    /// Decode the security of a network from its information elements.
+    /// `privacy` is the Privacy bit of the capability field, or whether the
+    /// driver reported an encryption key, and marks a network with neither
+    /// element as WEP.
+    pub fn from_ies(ies: &[u8], privacy: bool) -> Security {
+        let mut rsn = None;
+        let mut wpa = None;
+        for element in ie::Elements::new(ies) {
+            match element {
+                Element::Rsn(parsed) => rsn = rsn.or(Some(parsed)),
+                Element::VendorSpecific { oui, ref data }
+                    if oui == WPA_OUI && data.first() == Some(&WPA_ELEMENT_TYPE) => {
+                    wpa = wpa.or_else(|| Rsn::parse(&data[1..], WPA_OUI));
+                }
+                _ => {}
+            }
+        }
+        Security::from_elements(rsn.as_ref(), wpa.as_ref(), privacy)
+    }
+
+    /// Build a description from an already parsed RSN element and WPA element
+    /// body.
+    pub fn from_elements(rsn: Option<&Rsn>, wpa: Option<&Rsn>, privacy: bool) -> Security {
+        let mut security = Security::open();
+        security.wpa_compatible = wpa.is_some();
+        for element in rsn.iter().chain(wpa.iter()) {
+            security.group_cipher = security.group_cipher.or(element.group_cipher);
+            for cipher in &element.pairwise_ciphers {
+                if !security.pairwise_ciphers.contains(cipher) {
+                    security.pairwise_ciphers.push(*cipher);
+                }
+            }
+        }
+        // The protocol and transition mode are decided by the AKMs, so the
+        // WPA element's only count when there's no RSN element.
+        if let Some(element) = rsn.or(wpa) {
+            for akm in &element.akm_suites {
+                if !security.akm_suites.contains(akm) {
+                    security.akm_suites.push(*akm);
+                }
+            }
+        }
+        if let Some(rsn) = rsn {
+            security.pmf_required = rsn.mfp_required();
+            security.pmf_capable = rsn.mfp_capable() || security.pmf_required;
+        }
+        security.protocol = if rsn.is_some() {
+            if security.akm_suites.iter().any(|akm| security.is_wpa3_akm(*akm)) {
+                Protocol::Wpa3
+            } else {
+                Protocol::Wpa2
+            }
+        } else if wpa.is_some() {
+            Protocol::Wpa
+        } else if privacy {
+            Protocol::Wep
+        } else {
+            Protocol::Open
+        };
+        security
+    }
+
+    /// True if the network offers a WPA3 AKM alongside a legacy PSK or 802.1X
+    /// one, so clients without WPA3 can still join.
+    pub fn is_transition_mode(&self) -> bool {
+        self.protocol == Protocol::Wpa3 &&
+        self.akm_suites.iter().any(|akm| !self.is_wpa3_akm(*akm))
+    }
+
+    /// True if a pre-shared key or SAE password is enough to join.
+    pub fn is_personal(&self) -> bool {
+        self.akm_suites.iter().any(|akm| {
+            matches!(*akm,
+                     AkmSuite::Psk | AkmSuite::FtPsk | AkmSuite::PskSha256 |
+                     AkmSuite::FtPskSha384 | AkmSuite::PskSha384 | AkmSuite::Sae |
+                     AkmSuite::FtSae | AkmSuite::SaeExt | AkmSuite::FtSaeExt)
+        })
+    }
+
+    fn is_wpa3_akm(&self, akm: AkmSuite) -> bool {
+        match akm {
+            AkmSuite::Sae | AkmSuite::FtSae | AkmSuite::SaeExt | AkmSuite::FtSaeExt |
+            AkmSuite::Owe | AkmSuite::SuiteB192 | AkmSuite::FtIeee8021xSha384 => true,
+            // WPA3-Enterprise only mode is 802.1X with SHA-256 and PMF required.
+            AkmSuite::Ieee8021xSha256 => self.pmf_required,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.protocol)?;
+        if self.is_transition_mode() {
+            f.write_str(" transition")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSN_CCMP: [u8; 8] = [0x30, 0x00, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04];
+
+    /// An RSN element with CCMP everywhere, the given AKM types and RSN
+    /// capabilities.
+    fn rsn(akms: &[u8], capabilities: u16) -> Vec<u8> {
+        let mut ie = RSN_CCMP.to_vec();
+        ie.extend_from_slice(&[0x01, 0x00, 0x00, 0x0f, 0xac, 0x04]);
+        ie.extend_from_slice(&[akms.len() as u8, 0x00]);
+        for akm in akms {
+            ie.extend_from_slice(&[0x00, 0x0f, 0xac, *akm]);
+        }
+        ie.extend_from_slice(&capabilities.to_le_bytes());
+        ie[1] = (ie.len() - 2) as u8;
+        ie
+    }
+
+    const WPA_TKIP_PSK: [u8; 24] = [0xdd, 0x16, 0x00, 0x50, 0xf2, 0x01, 0x01, 0x00, 0x00, 0x50,
+                                    0xf2, 0x02, 0x01, 0x00, 0x00, 0x50, 0xf2, 0x02, 0x01, 0x00,
+                                    0x00, 0x50, 0xf2, 0x02];
+
+    #[test]
+    fn open_and_wep() {
+        assert_eq!(Security::from_ies(&[], false), Security::open());
+        assert_eq!(Security::from_ies(&[0x00, 0x01, b'x'], true).protocol, Protocol::Wep);
+    }
+
+    #[test]
+    fn wpa_only() {
+        let security = Security::from_ies(&WPA_TKIP_PSK, true);
+        assert_eq!(security.protocol, Protocol::Wpa);
+        assert_eq!(security.group_cipher, Some(CipherSuite::Tkip));
+        assert_eq!(security.pairwise_ciphers, vec![CipherSuite::Tkip]);
+        assert_eq!(security.akm_suites, vec![AkmSuite::Psk]);
+        assert!(security.wpa_compatible && !security.pmf_capable);
+    }
+
+    #[test]
+    fn wpa2_mixed_with_wpa() {
+        let mut ies = rsn(&[2], 0);
+        ies.extend_from_slice(&WPA_TKIP_PSK);
+        let security = Security::from_ies(&ies, true);
+        assert_eq!(security.protocol, Protocol::Wpa2);
+        assert_eq!(security.pairwise_ciphers,
+                   vec![CipherSuite::Ccmp128, CipherSuite::Tkip]);
+        assert_eq!(security.akm_suites, vec![AkmSuite::Psk]);
+        assert!(security.wpa_compatible && security.is_personal());
+        assert_eq!(security.to_string(), "WPA2");
+    }
+
+    #[test]
+    fn transition_versus_pure_sae() {
+        let transition = Security::from_ies(&rsn(&[2, 8], Rsn::CAPABILITY_MFP_CAPABLE), true);
+        assert_eq!(transition.protocol, Protocol::Wpa3);
+        assert!(transition.is_transition_mode());
+        assert!(transition.pmf_capable && !transition.pmf_required);
+        assert_eq!(transition.to_string(), "WPA3 transition");
+
+        let sae = Security::from_ies(&rsn(&[8], Rsn::CAPABILITY_MFP_REQUIRED |
+                                                Rsn::CAPABILITY_MFP_CAPABLE),
+                                     true);
+        assert_eq!(sae.protocol, Protocol::Wpa3);
+        assert!(!sae.is_transition_mode());
+        assert!(sae.pmf_required && sae.pmf_capable);
+        assert_eq!(sae.to_string(), "WPA3");
+    }
+
+    #[test]
+    fn wpa_psk_next_to_pure_sae() {
+        let mut ies = rsn(&[8], Rsn::CAPABILITY_MFP_REQUIRED | Rsn::CAPABILITY_MFP_CAPABLE);
+        ies.extend_from_slice(&WPA_TKIP_PSK);
+        let security = Security::from_ies(&ies, true);
+        assert_eq!(security.protocol, Protocol::Wpa3);
+        assert_eq!(security.akm_suites, vec![AkmSuite::Sae]);
+        assert!(!security.is_transition_mode());
+        assert!(security.wpa_compatible);
+        assert_eq!(security.to_string(), "WPA3");
+    }
+
+    #[test]
+    fn enterprise_and_owe() {
+        let wpa2 = Security::from_ies(&rsn(&[1, 3], 0), true);
+        assert_eq!(wpa2.protocol, Protocol::Wpa2);
+        assert_eq!(wpa2.akm_suites, vec![AkmSuite::Ieee8021x, AkmSuite::FtIeee8021x]);
+        assert!(!wpa2.is_personal());
+
+        let wpa3 = Security::from_ies(&rsn(&[5], Rsn::CAPABILITY_MFP_REQUIRED), true);
+        assert_eq!(wpa3.protocol, Protocol::Wpa3);
+        assert!(wpa3.pmf_capable);
+        // Without PMF required 802.1X-SHA256 is still WPA2.
+        assert_eq!(Security::from_ies(&rsn(&[5], 0), true).protocol, Protocol::Wpa2);
+
+        let owe = Security::from_ies(&rsn(&[18], Rsn::CAPABILITY_MFP_REQUIRED), false);
+        assert_eq!(owe.protocol, Protocol::Wpa3);
+        assert_eq!(owe.akm_suites, vec![AkmSuite::Owe]);
+    }
+
+    #[test]
+    fn malformed_wpa_element_is_ignored() {
+        let security = Security::from_ies(&[0xdd, 0x05, 0x00, 0x50, 0xf2, 0x01, 0x01], true);
+        assert_eq!(security.protocol, Protocol::Wep);
+    }
+
+    #[test]
+    fn protocols_are_ordered() {
+        assert!(Protocol::Open < Protocol::Wep && Protocol::Wep < Protocol::Wpa &&
+                Protocol::Wpa < Protocol::Wpa2 && Protocol::Wpa2 < Protocol::Wpa3);
+    }
+}
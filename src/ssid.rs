@@ -0,0 +1,195 @@
+use std::fmt;
+use std::str;
+
+/// The longest SSID 802.11 allows, in bytes.
+pub(crate) const SSID_MAX_LEN: usize = 32;
+
+/// A network name exactly as it went over the air: up to 32 bytes that
+/// needn't be UTF-8 and may contain NULs.
+///
+/// Displaying an SSID escapes anything that isn't printable, including
+/// invisible format characters like bidi overrides, so a hostile beacon can't
+/// mess up a terminal or log or pass itself off as another name.
+///
+/// ```
+/// use dradis::Ssid;
+///
+/// let ssid = Ssid::new(b"caf\xc3\xa9\x00\xff").unwrap();
+/// assert_eq!(ssid.len(), 7);
+/// assert_eq!(ssid.as_str(), None);
+/// assert_eq!(ssid.to_string(), "caf\\xc3\\xa9\\x00\\xff");
+/// assert_eq!(Ssid::new(b"caf\xc3\xa9").unwrap().to_string(), "caf\u{e9}");
+/// assert!(Ssid::new(&[0; 8]).unwrap().is_hidden());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Ssid {
+    len: u8,
+    bytes: [u8; SSID_MAX_LEN],
+}
+
+impl Ssid {
+    /// The SSID made of `bytes`, or `None` if there are more than 32.
+    pub fn new(bytes: &[u8]) -> Option<Ssid> {
+        if bytes.len() > SSID_MAX_LEN {
+            return None;
+        }
+        let mut ssid = Ssid {
+            len: bytes.len() as u8,
+            bytes: [0; SSID_MAX_LEN],
+        };
+        ssid.bytes[..bytes.len()].copy_from_slice(bytes);
+        Some(ssid)
+    }
+
+    /// The SSID made of the first 32 of `bytes`, for decoding reports that
+    /// may be malformed.
+    pub(crate) fn truncated(bytes: &[u8]) -> Ssid {
+        Ssid::new(&bytes[..bytes.len().min(SSID_MAX_LEN)]).unwrap()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// True for the empty or all-NUL SSID a hidden network advertises in its
+    /// beacons.
+    pub fn is_hidden(&self) -> bool {
+        self.as_bytes().iter().all(|&byte| byte == 0)
+    }
+
+    /// The SSID as text, if it is valid UTF-8. 802.11 only promises that
+    /// when the network sets the UTF-8 SSID extended capability; see
+    /// `WirelessNetwork::ssid_utf8`.
+    pub fn as_str(&self) -> Option<&str> {
+        str::from_utf8(self.as_bytes()).ok()
+    }
+}
+
+/// The Unicode format characters (category Cf), which include the bidi
+/// overrides that can make text display in a different order than it reads.
+const FORMAT_CHARS: [(u32, u32); 21] = [(0xad, 0xad),
+                                         (0x600, 0x605),
+                                         (0x61c, 0x61c),
+                                         (0x6dd, 0x6dd),
+                                         (0x70f, 0x70f),
+                                         (0x890, 0x891),
+                                         (0x8e2, 0x8e2),
+                                         (0x180e, 0x180e),
+                                         (0x200b, 0x200f),
+                                         (0x202a, 0x202e),
+                                         (0x2060, 0x2064),
+                                         (0x2066, 0x206f),
+                                         (0xfeff, 0xfeff),
+                                         (0xfff9, 0xfffb),
+                                         (0x110bd, 0x110bd),
+                                         (0x110cd, 0x110cd),
+                                         (0x13430, 0x1343f),
+                                         (0x1bca0, 0x1bca3),
+                                         (0x1d173, 0x1d17a),
+                                         (0xe0001, 0xe0001),
+                                         (0xe0020, 0xe007f)];
+
+fn is_format(c: char) -> bool {
+    FORMAT_CHARS.iter().any(|&(first, last)| (first..=last).contains(&(c as u32)))
+}
+
+impl Ssid {
+    /// Write the SSID escaped for display, and with `"` escaped too if it's
+    /// going between quotes.
+    fn write_escaped(&self, f: &mut fmt::Formatter, quoted: bool) -> fmt::Result {
+        match self.as_str() {
+            Some(text) => {
+                for c in text.chars() {
+                    match c {
+                        '\\' => f.write_str("\\\\")?,
+                        '"' if quoted => f.write_str("\\\"")?,
+                        c if (c as u32) < 0x80 && !(' '..='~').contains(&c) => {
+                            write!(f, "\\x{:02x}", c as u32)?
+                        }
+                        c if c.is_control() || is_format(c) => write!(f, "\\u{{{:x}}}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+            }
+            None => {
+                for &byte in self.as_bytes() {
+                    match byte {
+                        b'\\' => f.write_str("\\\\")?,
+                        b'"' if quoted => f.write_str("\\\"")?,
+                        b' '..=b'~' => write!(f, "{}", byte as char)?,
+                        _ => write!(f, "\\x{:02x}", byte)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Ssid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_escaped(f, false)
+    }
+}
+
+impl fmt::Debug for Ssid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Ssid(\"")?;
+        self.write_escaped(f, true)?;
+        f.write_str("\")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_up_to_32_bytes() {
+        assert_eq!(Ssid::new(&[b'a'; 32]).unwrap().len(), 32);
+        assert_eq!(Ssid::new(&[b'a'; 33]), None);
+        assert_eq!(Ssid::truncated(&[b'a'; 40]).as_bytes(), &[b'a'; 32][..]);
+        assert!(Ssid::default().is_empty());
+    }
+
+    #[test]
+    fn hidden_ssids() {
+        assert!(Ssid::default().is_hidden());
+        assert!(Ssid::new(&[0, 0, 0]).unwrap().is_hidden());
+        assert!(!Ssid::new(b"\x00a").unwrap().is_hidden());
+    }
+
+    #[test]
+    fn keeps_nuls_and_bad_utf8() {
+        let ssid = Ssid::new(b"a\x00b").unwrap();
+        assert_eq!(ssid.as_bytes(), b"a\x00b");
+        assert_eq!(ssid.as_str(), Some("a\u{0}b"));
+        assert_eq!(ssid.to_string(), "a\\x00b");
+        assert_eq!(Ssid::new(b"\xc3").unwrap().as_str(), None);
+    }
+
+    #[test]
+    fn escapes_display() {
+        let escaped = |bytes: &[u8]| Ssid::new(bytes).unwrap().to_string();
+        assert_eq!(escaped(b"home"), "home");
+        assert_eq!(escaped(b"back\\slash"), "back\\\\slash");
+        assert_eq!(escaped(b"\x1b[2J"), "\\x1b[2J");
+        assert_eq!(escaped("\u{85}x".as_bytes()), "\\u{85}x");
+        assert_eq!(escaped("\u{1f4f6} net".as_bytes()), "\u{1f4f6} net");
+        assert_eq!(escaped(b"\xffok\\"), "\\xffok\\\\");
+        assert_eq!(escaped("\u{202e}txt.exe".as_bytes()), "\\u{202e}txt.exe");
+        assert_eq!(escaped("zero\u{200b}width".as_bytes()), "zero\\u{200b}width");
+        assert_eq!(escaped(b"say \"hi\""), "say \"hi\"");
+        assert_eq!(format!("{:?}", Ssid::new(b"x").unwrap()), "Ssid(\"x\")");
+        assert_eq!(format!("{:?}", Ssid::new(b"a\"b\\").unwrap()), "Ssid(\"a\\\"b\\\\\")");
+        assert_eq!(format!("{:?}", Ssid::new(b"\"\xff").unwrap()), "Ssid(\"\\\"\\xff\")");
+    }
+}
@@ -0,0 +1,52 @@
+//! Networks and captured scan results for the tests.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use {Ssid, WirelessNetwork};
+
+/// An open network called `essid`, with nothing else known about it.
+pub(crate) fn network(essid: &str) -> WirelessNetwork {
+    WirelessNetwork {
+        essid: Ssid::new(essid.as_bytes()),
+        ..WirelessNetwork::default()
+    }
+}
+
+/// The names of `networks`, in order.
+pub(crate) fn essids(networks: &[WirelessNetwork]) -> Vec<String> {
+    networks.iter().filter_map(|n| n.essid.map(|ssid| ssid.to_string())).collect()
+}
+
+/// The directory under `testdata` that holds the raw kernel replies captured
+/// for one parser.
+pub(crate) fn capture_dir(parser: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(parser)
+}
+
+/// Every `.bin` capture recorded for `parser`, with its file stem, sorted by
+/// name so failures always point at the same file first.
+pub(crate) fn captures(parser: &str) -> Vec<(String, Vec<u8>)> {
+    let mut captures: Vec<_> = fs::read_dir(capture_dir(parser))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bin"))
+        .map(|path| {
+            let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+            (stem, fs::read(&path).unwrap())
+        })
+        .collect();
+    captures.sort();
+    captures
+}
+
+/// The interface to record live captures from, named by `DRADIS_CAPTURE_INTERFACE`.
+pub(crate) fn capture_interface() -> String {
+    ::std::env::var("DRADIS_CAPTURE_INTERFACE")
+        .expect("set DRADIS_CAPTURE_INTERFACE to a wireless interface to record from")
+}
+
+/// Save `buf` as `<name>.bin` alongside the other captures for `parser`.
+pub(crate) fn save_capture(parser: &str, name: &str, buf: &[u8]) {
+    fs::write(capture_dir(parser).join(format!("{}.bin", name)), buf).unwrap();
+}
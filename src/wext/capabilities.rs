@@ -0,0 +1,272 @@
+//! What an interface's driver says it can do, from its `iw_range`.
+
+use std::cmp;
+
+use {Channel, Error, IwQuality};
+use super::{iw_range, IwReq, Socket, IW_MAX_BITRATES, IW_MAX_ENCODING_SIZES,
+            IW_MAX_FREQUENCIES, IW_MAX_TXPOWER};
+
+const IW_ENC_CAPA_WPA: u32 = 0x00000001;
+const IW_ENC_CAPA_WPA2: u32 = 0x00000002;
+const IW_ENC_CAPA_CIPHER_TKIP: u32 = 0x00000004;
+const IW_ENC_CAPA_CIPHER_CCMP: u32 = 0x00000008;
+const IW_ENC_CAPA_4WAY_HANDSHAKE: u32 = 0x00000010;
+
+const IW_POWER_PERIOD: u16 = 0x1000;
+const IW_POWER_TIMEOUT: u16 = 0x2000;
+const IW_POWER_SAVING: u16 = 0x4000;
+const IW_POWER_MODE: u16 = 0x0F00;
+const IW_POWER_UNICAST_R: u16 = 0x0100;
+const IW_POWER_MULTICAST_R: u16 = 0x0200;
+const IW_POWER_ALL_R: u16 = 0x0300;
+const IW_POWER_FORCE_S: u16 = 0x0400;
+const IW_POWER_REPEATER: u16 = 0x0800;
+
+const IW_TXPOW_TYPE: u16 = 0x00FF;
+const IW_TXPOW_MWATT: u16 = 0x0001;
+const IW_TXPOW_RELATIVE: u16 = 0x0002;
+
+/// The capabilities of a wireless interface as its driver reports them
+/// through SIOCGIWRANGE.
+///
+/// ```no_run
+/// use dradis::InterfaceCapabilities;
+///
+/// let caps = InterfaceCapabilities::query("wlan0").unwrap();
+/// if !caps.ciphers.ccmp {
+///     println!("wlan0 can't do CCMP");
+/// }
+/// for channel in &caps.channels {
+///     println!("{} {} ({} MHz)", channel, channel.band(), channel.mhz());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterfaceCapabilities {
+    /// The Wireless Extensions version the driver was built against.
+    pub we_version: u8,
+    /// The Wireless Extensions version the driver source was last updated for.
+    pub we_version_source: u8,
+    /// The channels the driver can tune to. Entries the driver gave that
+    /// aren't 802.11 channels are left out.
+    pub channels: Vec<Channel>,
+    /// Supported bit rates in bits per second.
+    pub bitrates: Vec<i32>,
+    pub txpower: Vec<TxPower>,
+    /// Supported WEP key sizes in bytes.
+    pub encoding_sizes: Vec<u16>,
+    /// How many WEP keys the driver can hold.
+    pub max_encoding_tokens: u8,
+    pub ciphers: CipherCapabilities,
+    pub power_management: PowerManagement,
+    /// The best quality, level and noise scan results can report.
+    pub max_quality: IwQuality,
+}
+
+/// A transmit power level from the driver's list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxPower {
+    Dbm(i32),
+    Milliwatts(i32),
+    /// In driver specific units, only good for comparing with each other.
+    Relative(i32),
+}
+
+/// The WPA support the driver advertises in `enc_capa`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CipherCapabilities {
+    pub wpa: bool,
+    pub wpa2: bool,
+    pub tkip: bool,
+    pub ccmp: bool,
+    /// The driver does the 4-way handshake itself.
+    pub four_way_handshake: bool,
+}
+
+/// The power management options the driver advertises in `pm_capa`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PowerManagement {
+    /// The sleep period can be set.
+    pub period: bool,
+    /// The timeout before going back to sleep can be set.
+    pub timeout: bool,
+    /// The power saving level can be set.
+    pub saving: bool,
+    /// Can wake for unicast packets only.
+    pub unicast_only: bool,
+    /// Can wake for multicast and broadcast packets only.
+    pub multicast_only: bool,
+    /// Can wake for all packets.
+    pub all_packets: bool,
+    /// Can be forced to sleep.
+    pub force_sleep: bool,
+    /// Can repeat multicast packets while saving power.
+    pub repeater: bool,
+}
+
+impl InterfaceCapabilities {
+    /// Ask the driver behind `interface` what it supports.
+    pub fn query(interface: &str) -> Result<InterfaceCapabilities, Error> {
+        let socket = Socket::open()?;
+        let mut req = IwReq::new(interface)?;
+        Ok(InterfaceCapabilities::from_range(&socket.range(&mut req)?))
+    }
+
+    pub(crate) fn from_range(range: &iw_range) -> InterfaceCapabilities {
+        let num_frequency = cmp::min(range.num_frequency as usize, IW_MAX_FREQUENCIES);
+        let mut channels: Vec<Channel> = range.freq[..num_frequency]
+            .iter()
+            .filter_map(|freq| Channel::from_wext(freq.m, freq.e))
+            .collect();
+        channels.sort();
+        channels.dedup();
+
+        let num_bitrates = cmp::min(range.num_bitrates as usize, IW_MAX_BITRATES);
+        let num_txpower = cmp::min(range.num_txpower as usize, IW_MAX_TXPOWER);
+        let num_encoding_sizes = cmp::min(range.num_encoding_sizes as usize,
+                                          IW_MAX_ENCODING_SIZES);
+        let txpower = range.txpower[..num_txpower]
+            .iter()
+            .map(|&power| {
+                match range.txpower_capa & IW_TXPOW_TYPE {
+                    IW_TXPOW_MWATT => TxPower::Milliwatts(power),
+                    IW_TXPOW_RELATIVE => TxPower::Relative(power),
+                    _ => TxPower::Dbm(power),
+                }
+            })
+            .collect();
+
+        let enc_capa = range.enc_capa;
+        let pm_capa = range.pm_capa;
+        let pm_mode = pm_capa & IW_POWER_MODE;
+        InterfaceCapabilities {
+            we_version: range.we_version_compiled,
+            we_version_source: range.we_version_source,
+            channels,
+            bitrates: range.bitrate[..num_bitrates].to_vec(),
+            txpower,
+            encoding_sizes: range.encoding_size[..num_encoding_sizes].to_vec(),
+            max_encoding_tokens: range.max_encoding_tokens,
+            ciphers: CipherCapabilities {
+                wpa: enc_capa & IW_ENC_CAPA_WPA != 0,
+                wpa2: enc_capa & IW_ENC_CAPA_WPA2 != 0,
+                tkip: enc_capa & IW_ENC_CAPA_CIPHER_TKIP != 0,
+                ccmp: enc_capa & IW_ENC_CAPA_CIPHER_CCMP != 0,
+                four_way_handshake: enc_capa & IW_ENC_CAPA_4WAY_HANDSHAKE != 0,
+            },
+            power_management: PowerManagement {
+                period: pm_capa & IW_POWER_PERIOD != 0,
+                timeout: pm_capa & IW_POWER_TIMEOUT != 0,
+                saving: pm_capa & IW_POWER_SAVING != 0,
+                // The receive modes are an enumeration inside the mode bits, not
+                // separate flags, apart from force and repeater.
+                unicast_only: pm_mode & IW_POWER_ALL_R == IW_POWER_UNICAST_R,
+                multicast_only: pm_mode & IW_POWER_ALL_R == IW_POWER_MULTICAST_R,
+                all_packets: pm_mode & IW_POWER_ALL_R == IW_POWER_ALL_R,
+                force_sleep: pm_mode & IW_POWER_FORCE_S != 0,
+                repeater: pm_mode & IW_POWER_REPEATER != 0,
+            },
+            max_quality: range.max_quality(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Band;
+    use wext::{priv_iw_freq, priv_iw_quality};
+
+    fn freq(m: i32, e: i16, i: u8) -> priv_iw_freq {
+        priv_iw_freq {
+            m,
+            e,
+            i,
+            flags: 0,
+        }
+    }
+
+    #[test]
+    fn decodes_range() {
+        let mut range = iw_range {
+            we_version_compiled: 22,
+            we_version_source: 21,
+            num_frequency: 4,
+            num_bitrates: 3,
+            num_txpower: 2,
+            num_encoding_sizes: 2,
+            max_encoding_tokens: 4,
+            enc_capa: IW_ENC_CAPA_WPA | IW_ENC_CAPA_WPA2 | IW_ENC_CAPA_CIPHER_CCMP,
+            pm_capa: IW_POWER_PERIOD | IW_POWER_ALL_R | IW_POWER_FORCE_S,
+            max_qual: priv_iw_quality {
+                qual: 70,
+                level: 0,
+                noise: 0,
+                updated: IwQuality::DBM,
+            },
+            ..iw_range::default()
+        };
+        range.freq[0] = freq(2412, 6, 1);
+        range.freq[1] = freq(518, 7, 36);
+        range.freq[2] = freq(2437, 6, 6);
+        range.freq[3] = freq(1234, 6, 99);
+        // Past num_frequency, must be ignored.
+        range.freq[4] = freq(2462, 6, 11);
+        range.bitrate[..3].copy_from_slice(&[1000000, 11000000, 54000000]);
+        range.txpower[..2].copy_from_slice(&[0, 20]);
+        range.encoding_size[..2].copy_from_slice(&[5, 13]);
+
+        let caps = InterfaceCapabilities::from_range(&range);
+        assert_eq!((caps.we_version, caps.we_version_source), (22, 21));
+        let channels: Vec<_> = caps.channels.iter().map(|c| (c.band(), c.number())).collect();
+        assert_eq!(channels,
+                   vec![(Band::TwoGhz, 1), (Band::TwoGhz, 6), (Band::FiveGhz, 36)]);
+        assert_eq!(caps.bitrates, vec![1000000, 11000000, 54000000]);
+        assert_eq!(caps.txpower, vec![TxPower::Dbm(0), TxPower::Dbm(20)]);
+        assert_eq!(caps.encoding_sizes, vec![5, 13]);
+        assert_eq!(caps.max_encoding_tokens, 4);
+        assert_eq!(caps.ciphers,
+                   CipherCapabilities {
+                       wpa: true,
+                       wpa2: true,
+                       tkip: false,
+                       ccmp: true,
+                       four_way_handshake: false,
+                   });
+        assert_eq!(caps.power_management,
+                   PowerManagement {
+                       period: true,
+                       all_packets: true,
+                       force_sleep: true,
+                       ..PowerManagement::default()
+                   });
+        assert_eq!(caps.max_quality, IwQuality::new(70, 0, 0, IwQuality::DBM));
+    }
+
+    #[test]
+    fn clamps_bogus_counts() {
+        let range = iw_range {
+            num_frequency: 255,
+            num_bitrates: 255,
+            num_txpower: 255,
+            num_encoding_sizes: 255,
+            txpower_capa: IW_TXPOW_MWATT,
+            ..iw_range::default()
+        };
+        let caps = InterfaceCapabilities::from_range(&range);
+        assert!(caps.channels.is_empty());
+        assert_eq!(caps.bitrates.len(), IW_MAX_BITRATES);
+        assert_eq!(caps.txpower, vec![TxPower::Milliwatts(0); IW_MAX_TXPOWER]);
+        assert_eq!(caps.encoding_sizes.len(), IW_MAX_ENCODING_SIZES);
+    }
+
+    #[test]
+    fn unicast_only_power_mode() {
+        let range = iw_range {
+            pm_capa: IW_POWER_UNICAST_R | IW_POWER_TIMEOUT,
+            ..iw_range::default()
+        };
+        let pm = InterfaceCapabilities::from_range(&range).power_management;
+        assert!(pm.unicast_only && pm.timeout);
+        assert!(!pm.multicast_only && !pm.all_packets && !pm.period);
+    }
+}
@@ -0,0 +1,640 @@
+//! A parser for the Wireless Extensions scan event stream, the packed run of
+//! `struct iw_event` records that SIOCGIWSCAN fills its buffer with.
+//!
+//! The layout of that stream depends on the kernel that wrote it: the event
+//! header is padded out to the kernel's pointer size, and before WE-19 the
+//! pointer inside `iw_point` events was copied into the stream as well. A
+//! `StreamLayout` describes which variant a buffer uses, so a capture taken on
+//! one machine can be decoded on any other.
+
+#![forbid(unsafe_code)]
+
+use {Channel, IwQuality, IwStats, MacAddress, Security, Ssid, WirelessKey, WirelessMode,
+     WirelessNetwork};
+
+const SIOCGIWNAME: u16 = 0x8B01;
+const SIOCGIWNWID: u16 = 0x8B03;
+const SIOCGIWFREQ: u16 = 0x8B05;
+const SIOCGIWMODE: u16 = 0x8B07;
+const SIOCGIWAP: u16 = 0x8B15;
+const SIOCGIWESSID: u16 = 0x8B1B;
+const SIOCGIWRATE: u16 = 0x8B21;
+const SIOCGIWENCODE: u16 = 0x8B2B;
+const IWEVQUAL: u16 = 0x8C01;
+const IWEVCUSTOM: u16 = 0x8C02;
+const IWEVGENIE: u16 = 0x8C05;
+
+const IW_EV_LCP_PK_LEN: usize = 4;
+const IW_PARAM_LEN: usize = 8;
+const IW_FREQ_LEN: usize = 8;
+const SOCKADDR_LEN: usize = 16;
+const IFNAMSIZ: usize = 16;
+
+/// The first Wireless Extensions version that leaves the `iw_point` pointer
+/// out of the event stream.
+const WE_POINTER_DROPPED: u8 = 19;
+
+/// The first Wireless Extensions version whose ESSID length doesn't count a
+/// trailing NUL.
+const WE_ESSID_WITHOUT_NUL: u8 = 21;
+
+/// Describes how the kernel that produced an event stream laid it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamLayout {
+    /// Size in bytes of a pointer on the kernel that wrote the stream, 4 or 8.
+    pub pointer_size: usize,
+    /// The `we_version_compiled` the driver reported through SIOCGIWRANGE.
+    pub we_version: u8,
+}
+
+impl StreamLayout {
+    /// The layout this machine's kernel would use for `we_version`.
+    pub fn native(we_version: u8) -> StreamLayout {
+        StreamLayout {
+            pointer_size: ::std::mem::size_of::<usize>(),
+            we_version,
+        }
+    }
+
+    /// Guess the pointer size of the kernel that wrote `buf` from its first
+    /// SIOCGIWAP event, which is a bare `struct sockaddr` after the padded
+    /// header. Returns `None` if the stream doesn't start with one.
+    pub fn detect(buf: &[u8], we_version: u8) -> Option<StreamLayout> {
+        if buf.len() < IW_EV_LCP_PK_LEN || read_u16(&buf[2..]) != SIOCGIWAP {
+            return None;
+        }
+        let pointer_size = match read_u16(buf) as usize {
+            len if len == 4 + SOCKADDR_LEN => 4,
+            len if len == 8 + SOCKADDR_LEN => 8,
+            _ => return None,
+        };
+        Some(StreamLayout {
+            pointer_size,
+            we_version,
+        })
+    }
+
+    /// Offset of the fixed part of the event from the start of the event.
+    fn lcp_len(&self) -> usize {
+        self.pointer_size
+    }
+
+    /// Offset of the `length` and `flags` of an `iw_point` event.
+    fn point_header(&self) -> usize {
+        if self.we_version < WE_POINTER_DROPPED {
+            self.lcp_len() + self.pointer_size
+        } else {
+            self.lcp_len()
+        }
+    }
+
+    /// Offset of the variable length payload of an `iw_point` event.
+    fn point_payload(&self) -> usize {
+        // struct iw_point is the pointer followed by two u16s, padded out to
+        // pointer alignment. Since WE-19 the pointer is skipped but the space
+        // it took up is still there.
+        let iw_point = self.pointer_size + 4 + (self.pointer_size - 4);
+        if self.we_version < WE_POINTER_DROPPED {
+            self.lcp_len() + iw_point
+        } else {
+            self.lcp_len() + iw_point - self.pointer_size
+        }
+    }
+}
+
+impl Default for StreamLayout {
+    /// The native layout for current kernels, which all report WE-22.
+    fn default() -> StreamLayout {
+        StreamLayout::native(22)
+    }
+}
+
+/// A single decoded event from the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    /// Starts a new cell with the BSSID of the access point.
+    AccessPoint(MacAddress),
+    /// The protocol name, e.g. "IEEE 802.11bgn".
+    Name(&'a [u8]),
+    /// The network ID parameter of pre-802.11 WaveLAN cards.
+    NetworkId(i32),
+    /// A frequency or channel as the raw `struct iw_freq` mantissa, exponent,
+    /// channel index and flags.
+    Frequency {
+        m: i32,
+        e: i16,
+        i: u8,
+        flags: u8,
+    },
+    /// The operating mode, one of the IW_MODE_* values.
+    Mode(u32),
+    /// The ESSID bytes exactly as the driver gave them, NULs and all, and the
+    /// flags, which are nonzero when the ESSID is set.
+    Essid {
+        flags: u16,
+        essid: &'a [u8],
+    },
+    /// Encryption key flags (IW_ENCODE_*) and the key, if the driver exposed it.
+    Encode {
+        flags: u16,
+        key: &'a [u8],
+    },
+    /// Every bitrate in the event, in bits per second.
+    Bitrates(Vec<i32>),
+    /// Signal quality, level, noise and the IW_QUAL_* update flags.
+    Quality {
+        quality: u8,
+        level: u8,
+        noise: u8,
+        updated: u8,
+    },
+    /// Raw information elements from the beacon or probe response.
+    GenericIe(&'a [u8]),
+    /// Driver specific text, such as "tsf=..." or "Last beacon: ...".
+    Custom(&'a [u8]),
+    /// Any other event, with its command number and the data after the header.
+    Other {
+        cmd: u16,
+        data: &'a [u8],
+    },
+}
+
+/// Walks the events in a SIOCGIWSCAN buffer. Truncated or malformed events end
+/// the walk; everything before them is still returned.
+pub struct Events<'a> {
+    buf: &'a [u8],
+    layout: StreamLayout,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(buf: &'a [u8], layout: StreamLayout) -> Events<'a> {
+        Events { buf, layout }
+    }
+
+    fn fixed(&self, event: &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let start = self.layout.lcp_len();
+        event.get(start..start + len)
+    }
+
+    fn point(&self, event: &'a [u8]) -> Option<(u16, &'a [u8])> {
+        let header = self.layout.point_header();
+        let fields = event.get(header..header + 4)?;
+        let length = read_u16(fields) as usize;
+        let flags = read_u16(&fields[2..]);
+        let start = self.layout.point_payload();
+        // Events that claim more payload than they carry lose the payload, the
+        // same as libiw does.
+        let payload = event.get(start..start + length).unwrap_or(&[]);
+        Some((flags, payload))
+    }
+
+    fn decode(&self, cmd: u16, event: &'a [u8]) -> Option<Event<'a>> {
+        let event = match cmd {
+            SIOCGIWAP => {
+                // A struct sockaddr: the address family, then the MAC in sa_data.
+                let sockaddr = self.fixed(event, SOCKADDR_LEN)?;
+                Event::AccessPoint(MacAddress::from_slice(&sockaddr[2..])?)
+            }
+            SIOCGIWNAME => {
+                let name = self.fixed(event, IFNAMSIZ)?;
+                let end = name.iter().position(|&byte| byte == 0).unwrap_or(name.len());
+                Event::Name(&name[..end])
+            }
+            SIOCGIWNWID => Event::NetworkId(read_i32(self.fixed(event, IW_PARAM_LEN)?)),
+            SIOCGIWFREQ => {
+                let freq = self.fixed(event, IW_FREQ_LEN)?;
+                Event::Frequency {
+                    m: read_i32(freq),
+                    e: read_u16(&freq[4..]) as i16,
+                    i: freq[6],
+                    flags: freq[7],
+                }
+            }
+            SIOCGIWMODE => Event::Mode(read_i32(self.fixed(event, 4)?) as u32),
+            SIOCGIWESSID => {
+                let (flags, mut essid) = self.point(event)?;
+                if self.layout.we_version < WE_ESSID_WITHOUT_NUL && essid.last() == Some(&0) {
+                    essid = &essid[..essid.len() - 1];
+                }
+                Event::Essid { flags, essid }
+            }
+            SIOCGIWENCODE => {
+                let (flags, key) = self.point(event)?;
+                Event::Encode { flags, key }
+            }
+            SIOCGIWRATE => {
+                // Some drivers pack several iw_param values into a single event.
+                let start = self.layout.lcp_len();
+                let rates = event.get(start..)?
+                    .chunks(IW_PARAM_LEN)
+                    .filter(|param| param.len() == IW_PARAM_LEN)
+                    .map(read_i32)
+                    .collect();
+                Event::Bitrates(rates)
+            }
+            IWEVQUAL => {
+                let qual = self.fixed(event, 4)?;
+                Event::Quality {
+                    quality: qual[0],
+                    level: qual[1],
+                    noise: qual[2],
+                    updated: qual[3],
+                }
+            }
+            IWEVGENIE => Event::GenericIe(self.point(event)?.1),
+            IWEVCUSTOM => Event::Custom(self.point(event)?.1),
+            _ => {
+                Event::Other {
+                    cmd,
+                    data: event.get(self.layout.lcp_len()..).unwrap_or(&[]),
+                }
+            }
+        };
+        Some(event)
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            if self.buf.len() < IW_EV_LCP_PK_LEN {
+                return None;
+            }
+            let len = read_u16(self.buf) as usize;
+            let cmd = read_u16(&self.buf[2..]);
+            if len < IW_EV_LCP_PK_LEN || len > self.buf.len() {
+                self.buf = &[];
+                return None;
+            }
+            let (event, rest) = self.buf.split_at(len);
+            self.buf = rest;
+            // An event too short for its type is skipped, not fatal.
+            if let Some(event) = self.decode(cmd, event) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+/// A cell being put together from the events that describe it.
+struct Cell {
+    bssid: MacAddress,
+    essid: Option<Ssid>,
+    key: Option<WirelessKey>,
+    mode: Option<WirelessMode>,
+    maxbitrate: Option<i32>,
+    freq: Option<f64>,
+    quality: Option<IwQuality>,
+    ies: Vec<u8>,
+}
+
+impl Cell {
+    fn into_network(self) -> WirelessNetwork {
+        let privacy = self.key.as_ref().is_some_and(WirelessKey::is_enabled);
+        WirelessNetwork {
+            bssid: Some(self.bssid),
+            maxbitrate: self.maxbitrate,
+            freq: self.freq,
+            key: self.key,
+            mode: self.mode,
+            essid: self.essid,
+            security: Security::from_ies(&self.ies, privacy),
+            stats: self.quality.map(IwStats::new),
+            max_quality: None,
+            ies: self.ies,
+        }
+    }
+}
+
+/// Decode a SIOCGIWSCAN buffer laid out as `layout` into networks. Every
+/// SIOCGIWAP event starts a new cell and the events after it describe that
+/// cell; anything before the first one is ignored.
+///
+/// ```
+/// use dradis::wext::events::{parse_scan, StreamLayout};
+///
+/// let networks = parse_scan(&[], StreamLayout::default());
+/// assert!(networks.is_empty());
+/// ```
+pub fn parse_scan(buf: &[u8], layout: StreamLayout) -> Vec<WirelessNetwork> {
+    let mut list = Vec::new();
+    let mut cell: Option<Cell> = None;
+    for event in Events::new(buf, layout) {
+        if let Event::AccessPoint(bssid) = event {
+            if let Some(done) = cell.take() {
+                list.push(done.into_network());
+            }
+            cell = Some(Cell {
+                bssid,
+                essid: None,
+                key: None,
+                mode: None,
+                maxbitrate: None,
+                freq: None,
+                quality: None,
+                ies: Vec::new(),
+            });
+            continue;
+        }
+        let cell = match cell.as_mut() {
+            Some(cell) => cell,
+            None => continue,
+        };
+        match event {
+            Event::Essid { essid, .. } => {
+                cell.essid = Some(Ssid::truncated(essid));
+            }
+            Event::Encode { flags, key } => cell.key = Some(WirelessKey::new(key, flags)),
+            Event::Mode(mode) => cell.mode = WirelessMode::from_iw(mode as i32),
+            // The rates can come one to an event or all in one.
+            Event::Bitrates(rates) => {
+                cell.maxbitrate = rates.into_iter().chain(cell.maxbitrate).max();
+            }
+            Event::Frequency { m, e, .. } => {
+                // Drivers often report the channel number and then the
+                // frequency; only the latter says which band it's in.
+                let channel_only = e == 0 && m < 1000;
+                if cell.freq.is_none() || !channel_only {
+                    cell.freq = Channel::from_wext(m, e).map(|channel| channel.hz()).or(cell.freq);
+                }
+            }
+            // Drivers may split the elements across several events.
+            Event::GenericIe(ies) => cell.ies.extend_from_slice(ies),
+            Event::Quality { quality, level, noise, updated } => {
+                cell.quality = Some(IwQuality::new(quality, level, noise, updated));
+            }
+            _ => {}
+        }
+    }
+    if let Some(done) = cell {
+        list.push(done.into_network());
+    }
+    list
+}
+
+fn read_u16(buf: &[u8]) -> u16 {
+    u16::from_ne_bytes([buf[0], buf[1]])
+}
+
+fn read_i32(buf: &[u8]) -> i32 {
+    i32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util;
+    use Protocol;
+    use wext::IW_ENCODE_DISABLED;
+
+    const IW_ENCODE_NOKEY: u16 = 0x0800;
+
+    const LP64: StreamLayout = StreamLayout {
+        pointer_size: 8,
+        we_version: 22,
+    };
+    const ILP32: StreamLayout = StreamLayout {
+        pointer_size: 4,
+        we_version: 22,
+    };
+    const LP64_WE18: StreamLayout = StreamLayout {
+        pointer_size: 8,
+        we_version: 18,
+    };
+    const ILP32_WE18: StreamLayout = StreamLayout {
+        pointer_size: 4,
+        we_version: 18,
+    };
+
+    // Two cells laid out by hand, byte by byte, the way an LP64 kernel with
+    // WE-22 packs them (not captured from one): "dradis" with a key and a
+    // strong signal, and "guest" with an ESSID padded out with a NUL.
+    const HAND_LAID_LP64: &[u8] = &[
+        0x18, 0x00, 0x15, 0x8b, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x11,
+        0x22, 0x33, 0x44, 0x55, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x16, 0x00, 0x1b, 0x8b, 0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x01, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x64, 0x72, 0x61, 0x64, 0x69, 0x73, 0x10, 0x00,
+        0x2b, 0x8b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00,
+        0x00, 0x00, 0x0c, 0x00, 0x01, 0x8c, 0x00, 0x00, 0x00, 0x00, 0x3c, 0xc4,
+        0xa1, 0x0f, 0x18, 0x00, 0x15, 0x8b, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x66, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x16, 0x00, 0x1b, 0x8b, 0x00, 0x00, 0x00, 0x00, 0x06, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x67, 0x75, 0x65, 0x73, 0x74, 0x00,
+        0x10, 0x00, 0x2b, 0x8b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80,
+        0x00, 0x00, 0x00, 0x00,
+    ];
+
+    /// Lay an event out the way a kernel with `layout` would.
+    fn event(layout: StreamLayout, cmd: u16, data: &[u8]) -> Vec<u8> {
+        let mut event = vec![0u8; layout.lcp_len()];
+        event.extend_from_slice(data);
+        let len = (event.len() as u16).to_ne_bytes();
+        event[..2].copy_from_slice(&len);
+        event[2..4].copy_from_slice(&cmd.to_ne_bytes());
+        event
+    }
+
+    fn point_event(layout: StreamLayout, cmd: u16, flags: u16, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; layout.point_payload() - layout.lcp_len()];
+        let header = layout.point_header() - layout.lcp_len();
+        data[header..header + 2].copy_from_slice(&(payload.len() as u16).to_ne_bytes());
+        data[header + 2..header + 4].copy_from_slice(&flags.to_ne_bytes());
+        data.extend_from_slice(payload);
+        event(layout, cmd, &data)
+    }
+
+    fn ap(layout: StreamLayout, last: u8) -> Vec<u8> {
+        let mut sockaddr = vec![1, 0, 0, 0x11, 0x22, 0x33, 0x44, last];
+        sockaddr.resize(SOCKADDR_LEN, 0);
+        event(layout, SIOCGIWAP, &sockaddr)
+    }
+
+    fn stream(layout: StreamLayout) -> Vec<u8> {
+        let mut stream = Vec::new();
+        stream.extend(ap(layout, 0x55));
+        stream.extend(point_event(layout, SIOCGIWESSID, 1, b"dradis"));
+        stream.extend(point_event(layout, SIOCGIWENCODE, IW_ENCODE_NOKEY, &[]));
+        stream.extend(event(layout, IWEVQUAL, &[60, 0xc4, 0xa1, 0x0f]));
+        stream.extend(ap(layout, 0x66));
+        stream.extend(point_event(layout, SIOCGIWESSID, 1, b"guest\0"));
+        stream.extend(point_event(layout, SIOCGIWENCODE, IW_ENCODE_DISABLED, &[]));
+        stream
+    }
+
+    fn check(networks: Vec<WirelessNetwork>, layout: StreamLayout) {
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].bssid, Some(MacAddress::new([0, 0x11, 0x22, 0x33, 0x44, 0x55])));
+        assert_eq!(networks[1].bssid, Some(MacAddress::new([0, 0x11, 0x22, 0x33, 0x44, 0x66])));
+        assert_eq!(networks[0].essid, Ssid::new(b"dradis"));
+        assert_eq!(networks[0].security.protocol, Protocol::Wep);
+        let quality = networks[0].stats.unwrap().quality;
+        assert_eq!((quality.quality, quality.level, quality.noise), (60, 0xc4, 0xa1));
+        // Only old drivers counted the NUL in the length, so a newer one
+        // means it's really part of the SSID.
+        let guest: &[u8] = if layout.we_version < WE_ESSID_WITHOUT_NUL { b"guest" } else { b"guest\0" };
+        assert_eq!(networks[1].essid, Ssid::new(guest));
+        assert_eq!(networks[1].security, Security::open());
+        assert_eq!(networks[1].stats, None);
+    }
+
+    #[test]
+    fn parses_hand_laid_stream() {
+        check(parse_scan(HAND_LAID_LP64, LP64), LP64);
+    }
+
+    #[test]
+    fn parses_every_layout() {
+        for &layout in &[LP64, ILP32, LP64_WE18, ILP32_WE18] {
+            check(parse_scan(&stream(layout), layout), layout);
+        }
+    }
+
+    #[test]
+    fn security_comes_from_generic_ie() {
+        let rsn = [0x30, 0x14, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac,
+                   0x04, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x02, 0x00, 0x00];
+        let mut buf = ap(LP64, 0x55);
+        buf.extend(point_event(LP64, SIOCGIWENCODE, IW_ENCODE_NOKEY, &[]));
+        buf.extend(point_event(LP64, IWEVGENIE, 0, &rsn));
+        let networks = parse_scan(&buf, LP64);
+        assert_eq!(networks[0].security.protocol, Protocol::Wpa2);
+        assert_eq!(networks[0].security.akm_suites, vec![::ie::AkmSuite::Psk]);
+    }
+
+    #[test]
+    fn frequency_beats_channel_number() {
+        let freq = |m: i32, e: i16| {
+            let mut data = m.to_ne_bytes().to_vec();
+            data.extend_from_slice(&e.to_ne_bytes());
+            data.extend_from_slice(&[0, 0]);
+            event(LP64, SIOCGIWFREQ, &data)
+        };
+        let mut buf = ap(LP64, 0x55);
+        buf.extend(freq(36, 0));
+        buf.extend(freq(518, 7));
+        buf.extend(freq(40, 0));
+        buf.extend(ap(LP64, 0x66));
+        buf.extend(freq(6, 0));
+        let networks = parse_scan(&buf, LP64);
+        assert_eq!(networks[0].freq, Some(5.18e9));
+        assert_eq!(networks[0].channel().unwrap().number(), 36);
+        assert_eq!(networks[1].band(), Some(::Band::TwoGhz));
+        assert_eq!(networks[1].channel().unwrap().mhz(), 2437);
+    }
+
+    #[test]
+    fn detects_pointer_size() {
+        assert_eq!(StreamLayout::detect(&stream(LP64), 22), Some(LP64));
+        assert_eq!(StreamLayout::detect(&stream(ILP32), 22), Some(ILP32));
+        assert_eq!(StreamLayout::detect(&stream(ILP32_WE18), 18), Some(ILP32_WE18));
+        assert_eq!(StreamLayout::detect(&[], 22), None);
+        assert_eq!(StreamLayout::detect(&event(LP64, IWEVQUAL, &[0; 4]), 22), None);
+    }
+
+    #[test]
+    fn decodes_events() {
+        let mut buf = ap(ILP32, 0x55);
+        buf.extend(event(ILP32, SIOCGIWMODE, &3u32.to_ne_bytes()));
+        buf.extend(event(ILP32, SIOCGIWFREQ, &[0x85, 0x09, 0, 0, 6, 0, 0, 0]));
+        let mut rates = Vec::new();
+        for rate in &[1000000i32, 54000000] {
+            rates.extend_from_slice(&rate.to_ne_bytes());
+            rates.extend_from_slice(&[0; 4]);
+        }
+        buf.extend(event(ILP32, SIOCGIWRATE, &rates));
+        buf.extend(point_event(ILP32, IWEVGENIE, 0, &[0, 2, b'h', b'i']));
+        buf.extend(point_event(ILP32, IWEVCUSTOM, 0, b"tsf=0000000012345678"));
+        buf.extend(event(ILP32, 0x8B2D, &[1, 2, 3, 4]));
+
+        let events: Vec<_> = Events::new(&buf, ILP32).collect();
+        assert_eq!(events[1], Event::Mode(3));
+        assert_eq!(events[2],
+                   Event::Frequency {
+                       m: 2437,
+                       e: 6,
+                       i: 0,
+                       flags: 0,
+                   });
+        assert_eq!(events[3], Event::Bitrates(vec![1000000, 54000000]));
+        assert_eq!(events[4], Event::GenericIe(&[0, 2, b'h', b'i']));
+        assert_eq!(events[5], Event::Custom(b"tsf=0000000012345678"));
+        assert_eq!(events[6],
+                   Event::Other {
+                       cmd: 0x8B2D,
+                       data: &[1, 2, 3, 4],
+                   });
+    }
+
+    #[test]
+    fn fills_in_mode_rate_and_key() {
+        let rate = |bps: i32| {
+            let mut param = bps.to_ne_bytes().to_vec();
+            param.extend_from_slice(&[0; 4]);
+            param
+        };
+        let mut buf = ap(LP64, 0x55);
+        buf.extend(event(LP64, SIOCGIWMODE, &1u32.to_ne_bytes()));
+        buf.extend(event(LP64, SIOCGIWRATE, &rate(11000000)));
+        buf.extend(event(LP64, SIOCGIWRATE, &[rate(1000000), rate(54000000)].concat()));
+        buf.extend(point_event(LP64, SIOCGIWENCODE, IW_ENCODE_NOKEY, &[]));
+        buf.extend(ap(LP64, 0x66));
+        buf.extend(event(LP64, SIOCGIWMODE, &3u32.to_ne_bytes()));
+        let networks = parse_scan(&buf, LP64);
+        assert_eq!(networks[0].mode, Some(WirelessMode::AdHoc));
+        assert_eq!(networks[0].maxbitrate, Some(54000000));
+        let key = networks[0].key.as_ref().unwrap();
+        assert_eq!((key.flags(), key.key()), (IW_ENCODE_NOKEY, &[][..]));
+        assert!(key.is_enabled());
+        assert_eq!(networks[1].mode, Some(WirelessMode::Master));
+        assert_eq!((networks[1].maxbitrate, networks[1].key.clone()), (None, None));
+    }
+
+    #[test]
+    fn keeps_raw_essid_bytes() {
+        let mut stream = ap(LP64, 0x55);
+        stream.extend(point_event(LP64, SIOCGIWESSID, 1, b"\xff\0caf\xc3"));
+        stream.extend(ap(LP64, 0x66));
+        stream.extend(point_event(LP64, SIOCGIWESSID, 1, &[b'x'; 40]));
+        stream.extend(ap(LP64, 0x77));
+        stream.extend(point_event(LP64, SIOCGIWESSID, 0, &[0; 6]));
+        let networks = parse_scan(&stream, LP64);
+        assert_eq!(networks[0].essid.unwrap().as_bytes(), b"\xff\0caf\xc3");
+        assert_eq!(networks[1].essid.unwrap().len(), 32);
+        assert!(networks[2].essid.unwrap().is_hidden());
+    }
+
+    #[test]
+    fn decodes_captured_streams() {
+        for (name, stream) in test_util::captures("wext") {
+            // Captures are named <interface>-we<version> by `records_live_stream`.
+            let we_version = name.rsplit("-we").next().and_then(|v| v.parse().ok());
+            let we_version = we_version.unwrap_or_else(|| panic!("{}: no WE version in the name", name));
+            let layout = StreamLayout::detect(&stream, we_version)
+                .unwrap_or_else(|| StreamLayout::native(we_version));
+            let networks = parse_scan(&stream, layout);
+            assert!(!networks.is_empty(), "{}: no networks", name);
+            for network in &networks {
+                assert!(network.bssid.is_some(), "{}: {:?}", name, network);
+                assert!(network.essid.is_some(), "{}: {:?}", name, network);
+                assert!(network.freq.is_some(), "{}: {:?}", name, network);
+            }
+        }
+    }
+
+    #[test]
+    fn survives_truncation() {
+        let full = stream(LP64);
+        for len in 0..full.len() {
+            let networks = parse_scan(&full[..len], LP64);
+            assert!(networks.len() <= 2);
+        }
+        // A payload longer than the event is dropped rather than read past.
+        let mut liar = ap(LP64, 0x55);
+        let mut essid = point_event(LP64, SIOCGIWESSID, 1, b"abc");
+        essid[8] = 200;
+        liar.extend(essid);
+        assert_eq!(parse_scan(&liar, LP64)[0].essid, Some(Ssid::default()));
+    }
+}
@@ -0,0 +1,650 @@
+//! Wireless Extensions scanning done directly with the SIOCSIWSCAN and
+//! SIOCGIWSCAN ioctls, without linking against `libiw`. The event stream the
+//! kernel hands back is decoded in Rust by the `events` module.
+
+pub mod capabilities;
+pub mod events;
+
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_char;
+use std::ptr;
+use std::time::{Duration, Instant};
+use libc::{self, c_int, c_void};
+
+use {Error, IwQuality, PendingScan, ScanBackend, ScanRequest, WirelessNetwork};
+use self::events::StreamLayout;
+
+const IW_ENCODE_DISABLED: u16 = 0x8000;
+const IW_MAX_BITRATES: usize = 32;
+const IW_MAX_ENCODING_SIZES: usize = 8;
+const IW_MAX_FREQUENCIES: usize = 32;
+const IW_MAX_TXPOWER: usize = 8;
+const IFNAMSIZ: usize = 16;
+const IW_ESSID_MAX_SIZE: usize = 32;
+
+const SIOCGIWRANGE: u16 = 0x8B0B;
+const SIOCSIWSCAN: u16 = 0x8B18;
+const SIOCGIWSCAN: u16 = 0x8B19;
+
+const IW_SCAN_THIS_ESSID: u16 = 0x0002;
+const IW_SCAN_THIS_FREQ: u16 = 0x0008;
+const IW_SCAN_TYPE_PASSIVE: u8 = 1;
+const ARPHRD_ETHER: libc::sa_family_t = 1;
+
+const IW_SCAN_MAX_DATA: usize = 4096; // Starting buffer size, the same as libiw's
+const IW_SCAN_BUFFER_LIMIT: usize = 0xFFFF; // iw_point.length is a u16
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct IwPoint {
+    pointer: *mut c_void,
+    length: u16,
+    flags: u16,
+}
+
+#[repr(C)]
+union IwReqData {
+    data: IwPoint,
+    ap_addr: libc::sockaddr,
+}
+
+#[repr(C)]
+struct IwReq {
+    ifr_name: [c_char; IFNAMSIZ],
+    u: IwReqData,
+}
+
+#[derive(Default)]
+#[repr(C)]
+struct priv_iw_quality {
+    qual: u8,
+    level: u8,
+    noise: u8,
+    updated: u8,
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct priv_iw_freq {
+    m: i32,
+    e: i16,
+    i: u8,
+    flags: u8,
+}
+
+/// The options SIOCSIWSCAN takes for a scan other than the driver's default.
+#[repr(C)]
+struct iw_scan_req {
+    scan_type: u8,
+    essid_len: u8,
+    num_channels: u8,
+    flags: u8, /* Reserved, zero */
+    bssid: libc::sockaddr,
+    essid: [u8; IW_ESSID_MAX_SIZE],
+    min_channel_time: u32, /* In TU, zero for the driver's default */
+    max_channel_time: u32,
+    channel_list: [priv_iw_freq; IW_MAX_FREQUENCIES],
+}
+
+impl iw_scan_req {
+    /// Translate `request` into the `iw_scan_req` and `iw_point` flags to send
+    /// with SIOCSIWSCAN, or `None` for a default scan. Wireless Extensions can
+    /// only probe for one SSID and can't flush the driver's results.
+    fn from_request(request: &ScanRequest) -> Result<Option<(iw_scan_req, u16)>, Error> {
+        if request.flush {
+            return Err(Error::Unsupported {
+                call: "SIOCSIWSCAN",
+                message: "Wireless Extensions can't flush cached scan results",
+            });
+        }
+        if request.ssids.len() > 1 {
+            return Err(Error::Unsupported {
+                call: "SIOCSIWSCAN",
+                message: "Wireless Extensions can only probe for one SSID at a time",
+            });
+        }
+        if request.frequencies.len() > IW_MAX_FREQUENCIES {
+            return Err(Error::InvalidInput {
+                call: "SIOCSIWSCAN",
+                message: "Wireless Extensions can scan at most 32 frequencies",
+            });
+        }
+        if !request.passive && request.ssids.is_empty() && request.frequencies.is_empty() {
+            return Ok(None);
+        }
+
+        let mut req: iw_scan_req = unsafe { mem::zeroed() };
+        let mut flags = 0;
+        if request.passive {
+            req.scan_type = IW_SCAN_TYPE_PASSIVE;
+        }
+        req.bssid.sa_family = ARPHRD_ETHER;
+        for byte in &mut req.bssid.sa_data[..6] {
+            *byte = -1i8 as _;
+        }
+        if let Some(ssid) = request.ssids.first() {
+            req.essid[..ssid.len()].copy_from_slice(ssid);
+            req.essid_len = ssid.len() as u8;
+            flags |= IW_SCAN_THIS_ESSID;
+        }
+        for (freq, &mhz) in req.channel_list.iter_mut().zip(&request.frequencies) {
+            freq.m = mhz as i32;
+            freq.e = 6;
+        }
+        req.num_channels = request.frequencies.len() as u8;
+        if req.num_channels > 0 {
+            flags |= IW_SCAN_THIS_FREQ;
+        }
+        Ok(Some((req, flags)))
+    }
+}
+
+#[repr(C)]
+pub(crate) struct iw_range {
+    /* Informative stuff (to choose between different interface) */
+    throughput: u32, /* To give an idea... */
+    /* In theory this value should be the maximum benchmarked
+     * TCP/IP throughput, because with most of these devices the
+     * bit rate is meaningless (overhead an co) to estimate how
+     * fast the connection will go and pick the fastest one.
+     * I suggest people to play with Netperf or any benchmark...
+     */
+
+    /* NWID (or domain id) */
+    min_nwid: u32, /* Minimal NWID we are able to set */
+    max_nwid: u32, /* Maximal NWID we are able to set */
+
+    /* Old Frequency (backward compat - moved lower ) */
+    old_num_channels: u16,
+    old_num_frequency: u8,
+
+    /* Wireless event capability bitmasks */
+    event_capa: [u32; 6],
+
+    /* signal level threshold range */
+    sensitivity: i32,
+
+    /* Quality of link & SNR stuff */
+    /* Quality range (link, level, noise)
+     * If the quality is absolute, it will be in the range [0 , max_qual],
+     * if the quality is dBm, it will be in the range [max_qual , 0].
+     * Don't forget that we use 8 bit arithmetics... */
+    max_qual: priv_iw_quality, /* Quality of the link */
+    /* This should contain the average/typical values of the quality
+     * indicator. This should be the threshold between a "good" and
+     * a "bad" link (example : monitor going from green to orange).
+     * Currently, user space apps like quality monitors don't have any
+     * way to calibrate the measurement. With this, they can split
+     * the range between 0 and max_qual in different quality level
+     * (using a geometric subdivision centered on the average).
+     * I expect that people doing the user space apps will feedback
+     * us on which value we need to put in each driver... */
+    avg_qual: priv_iw_quality, /* Quality of the link */
+
+    /* Rates */
+    num_bitrates: u8, /* Number of entries in the list */
+    bitrate: [i32; IW_MAX_BITRATES], /* list, in bps */
+
+    /* RTS threshold */
+    min_rts: i32, /* Minimal RTS threshold */
+    max_rts: i32, /* Maximal RTS threshold */
+
+    /* Frag threshold */
+    min_frag: i32, /* Minimal frag threshold */
+    max_frag: i32, /* Maximal frag threshold */
+
+    /* Power Management duration & timeout */
+    min_pmp: i32, /* Minimal PM period */
+    max_pmp: i32, /* Maximal PM period */
+    min_pmt: i32, /* Minimal PM timeout */
+    max_pmt: i32, /* Maximal PM timeout */
+    pmp_flags: u16, /* How to decode max/min PM period */
+    pmt_flags: u16, /* How to decode max/min PM timeout */
+    pm_capa: u16, /* What PM options are supported */
+
+    /* Encoder stuff */
+    encoding_size: [u16; IW_MAX_ENCODING_SIZES], /* Different token sizes */
+    num_encoding_sizes: u8, /* Number of entry in the list */
+    max_encoding_tokens: u8, /* Max number of tokens */
+    /* For drivers that need a "login/passwd" form */
+    encoding_login_index: u8, /* token index for login token */
+
+    /* Transmit power */
+    txpower_capa: u16, /* What options are supported */
+    num_txpower: u8, /* Number of entries in the list */
+    txpower: [i32; IW_MAX_TXPOWER], /* list, in bps */
+
+    /* Wireless Extension version info */
+    we_version_compiled: u8, /* Must be WIRELESS_EXT */
+    we_version_source: u8, /* Last update of source */
+
+    /* Retry limits and lifetime */
+    retry_capa: u16, /* What retry options are supported */
+    retry_flags: u16, /* How to decode max/min retry limit */
+    r_time_flags: u16, /* How to decode max/min retry life */
+    min_retry: i32, /* Minimal number of retries */
+    max_retry: i32, /* Maximal number of retries */
+    min_r_time: i32, /* Minimal retry lifetime */
+    max_r_time: i32, /* Maximal retry lifetime */
+
+    /* Frequency */
+    num_channels: u16, /* Number of channels [0, num - 1] */
+    num_frequency: u8, /* Number of entry in the list */
+    freq: [priv_iw_freq; IW_MAX_FREQUENCIES], /* list */
+    /* Note : this frequency list doesn't need to fit channel numbers,
+     * because each entry contain its channel index */
+    enc_capa: u32, /* IW_ENC_CAPA_* bit field */
+
+    /* More power management stuff */
+    min_pms: i32, /* Minimal PM saving */
+    max_pms: i32, /* Maximal PM saving */
+    pms_flags: i16, /* How to decode max/min PM saving */
+
+    /* All available modulations for driver (hw may support less) */
+    modul_capa: u32, /* IW_MODUL_* bit field */
+
+    /* More bitrate stuff */
+    bitrate_capa: u32, /* Types of bitrates supported */
+}
+
+impl Default for iw_range {
+    fn default() -> iw_range {
+        iw_range {
+            throughput: 0,
+            min_nwid: 0,
+            max_nwid: 0,
+            old_num_channels: 0,
+            old_num_frequency: 0,
+            event_capa: [0; 6],
+            sensitivity: 0,
+            max_qual: Default::default(), /* Quality of the link */
+            avg_qual: Default::default(), /* Quality of the link */
+            num_bitrates: 0, /* Number of entries in the list */
+            bitrate: [0; IW_MAX_BITRATES], /* list, in bps */
+            min_rts: 0, /* Minimal RTS threshold */
+            max_rts: 0, /* Maximal RTS threshold */
+            min_frag: 0, /* Minimal frag threshold */
+            max_frag: 0, /* Maximal frag threshold */
+            min_pmp: 0, /* Minimal PM period */
+            max_pmp: 0, /* Maximal PM period */
+            min_pmt: 0, /* Minimal PM timeout */
+            max_pmt: 0, /* Maximal PM timeout */
+            pmp_flags: 0, /* How to decode max/min PM period */
+            pmt_flags: 0, /* How to decode max/min PM timeout */
+            pm_capa: 0, /* What PM options are supported */
+            encoding_size: [0; IW_MAX_ENCODING_SIZES], /* Different token sizes */
+            num_encoding_sizes: 0, /* Number of entry in the list */
+            max_encoding_tokens: 0, /* Max number of tokens */
+            encoding_login_index: 0, /* token index for login token */
+            txpower_capa: 0, /* What options are supported */
+            num_txpower: 0, /* Number of entries in the list */
+            txpower: [0; IW_MAX_TXPOWER], /* list, in bps */
+            we_version_compiled: 0, /* Must be WIRELESS_EXT */
+            we_version_source: 0, /* Last update of source */
+            retry_capa: 0, /* What retry options are supported */
+            retry_flags: 0, /* How to decode max/min retry limit */
+            r_time_flags: 0, /* How to decode max/min retry life */
+            min_retry: 0, /* Minimal number of retries */
+            max_retry: 0, /* Maximal number of retries */
+            min_r_time: 0, /* Minimal retry lifetime */
+            max_r_time: 0, /* Maximal retry lifetime */
+            num_channels: 0, /* Number of channels [0, num - 1] */
+            num_frequency: 0, /* Number of entry in the list */
+            freq: [Default::default(); IW_MAX_FREQUENCIES], /* list */
+            enc_capa: 0, /* IW_ENC_CAPA_* bit field */
+            min_pms: 0, /* Minimal PM saving */
+            max_pms: 0, /* Maximal PM saving */
+            pms_flags: 0, /* How to decode max/min PM saving */
+            modul_capa: 0, /* IW_MODUL_* bit field */
+            bitrate_capa: 0, /* Types of bitrates supported */
+        }
+    }
+}
+
+impl iw_range {
+    /// The Wireless Extensions version the driver was built against.
+    pub(crate) fn we_version(&self) -> u8 {
+        self.we_version_compiled
+    }
+
+    /// The best quality, level and noise the driver reports, which scan
+    /// results are relative to.
+    pub(crate) fn max_quality(&self) -> IwQuality {
+        let max = &self.max_qual;
+        IwQuality::new(max.qual, max.level, max.noise, max.updated)
+    }
+}
+
+/// Scan backend that issues the Wireless Extensions scan ioctls itself. This
+/// is what `WifiScan::scan` runs on.
+#[derive(Debug)]
+pub struct WextBackend {
+    timeout: Duration,
+}
+
+impl WextBackend {
+    pub fn new() -> WextBackend {
+        WextBackend { timeout: Duration::from_secs(15) }
+    }
+
+    /// How long to keep polling for results while the driver is still scanning.
+    pub fn timeout(mut self, timeout: Duration) -> WextBackend {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Default for WextBackend {
+    fn default() -> WextBackend {
+        WextBackend::new()
+    }
+}
+
+impl ScanBackend for WextBackend {
+    fn start_scan(&mut self, request: &ScanRequest) -> Result<Box<dyn PendingScan>, Error> {
+        request.validate()?;
+        let mut options = iw_scan_req::from_request(request)?;
+        let socket = Socket::open()?;
+        let mut req = IwReq::new(&request.interface)?;
+        let range = socket.range(&mut req)?;
+        // SIOCGIWRANGE left its buffer in the request; a default scan sends
+        // no data at all.
+        req.u.data = match options {
+            Some((ref mut options, flags)) => {
+                IwPoint {
+                    pointer: options as *mut iw_scan_req as *mut c_void,
+                    length: mem::size_of::<iw_scan_req>() as u16,
+                    flags,
+                }
+            }
+            None => {
+                IwPoint {
+                    pointer: ptr::null_mut(),
+                    length: 0,
+                    flags: 0,
+                }
+            }
+        };
+
+        // Without CAP_NET_ADMIN this fails with EPERM; `ScanRequest` decides
+        // whether to make do with the cached results instead.
+        socket.ioctl(SIOCSIWSCAN, &mut req)?;
+
+        Ok(Box::new(WextScan {
+            socket,
+            interface: request.interface.clone(),
+            range,
+            buf: vec![0u8; IW_SCAN_MAX_DATA],
+            deadline: Instant::now() + request.timeout.unwrap_or(self.timeout),
+        }))
+    }
+
+    fn cached_results(&mut self, request: &ScanRequest) -> Result<Vec<WirelessNetwork>, Error> {
+        let socket = Socket::open()?;
+        let mut req = IwReq::new(&request.interface)?;
+        let range = socket.range(&mut req)?;
+        let mut results = WextScan {
+            socket,
+            interface: request.interface.clone(),
+            range,
+            buf: vec![0u8; IW_SCAN_MAX_DATA],
+            deadline: Instant::now(),
+        };
+        match results.poll_results() {
+            Ok(Some(networks)) => Ok(networks),
+            // EAGAIN: a scan someone else started hasn't finished yet.
+            Ok(None) | Err(Error::Timeout { .. }) => {
+                Err(Error::Busy {
+                    call: "SIOCGIWSCAN",
+                    errno: Some(libc::EAGAIN),
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// A triggered Wireless Extensions scan. There's no completion event to wait
+/// for, so every poll asks for the results and the driver answers EAGAIN until
+/// it has them.
+struct WextScan {
+    socket: Socket,
+    interface: String,
+    range: iw_range,
+    buf: Vec<u8>,
+    deadline: Instant,
+}
+
+impl WextScan {
+    /// Ask for the results, returning how much of `buf` the event stream fills
+    /// or `None` while the driver is still scanning.
+    fn read(&mut self) -> Result<Option<usize>, Error> {
+        let mut req = IwReq::new(&self.interface)?;
+        loop {
+            req.u.data = IwPoint {
+                pointer: self.buf.as_mut_ptr() as *mut c_void,
+                length: self.buf.len() as u16,
+                flags: 0,
+            };
+            match self.socket.ioctl(SIOCGIWSCAN, &mut req) {
+                Ok(()) => {
+                    let len = unsafe { req.u.data.length } as usize;
+                    return Ok(Some(len.min(self.buf.len())));
+                }
+                Err(ref err) if err.errno() == Some(libc::E2BIG) => {
+                    // Too many networks for the buffer. Newer drivers tell us how
+                    // much room they need, otherwise keep doubling until the
+                    // length field can't describe a bigger buffer.
+                    let wanted = unsafe { req.u.data.length } as usize;
+                    match grow_buffer(self.buf.len(), wanted) {
+                        Some(len) => self.buf.resize(len, 0),
+                        None => {
+                            return Err(Error::Backend {
+                                call: "SIOCGIWSCAN",
+                                errno: Some(libc::E2BIG),
+                                message: "scan results do not fit in the largest buffer".to_string(),
+                            })
+                        }
+                    }
+                }
+                Err(ref err) if err.errno() == Some(libc::EAGAIN) => {
+                    // Still scanning.
+                    if Instant::now() >= self.deadline {
+                        return Err(Error::Timeout {
+                            call: "SIOCGIWSCAN",
+                            errno: Some(libc::EAGAIN),
+                        });
+                    }
+                    return Ok(None);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl PendingScan for WextScan {
+    fn poll_results(&mut self) -> Result<Option<Vec<WirelessNetwork>>, Error> {
+        let len = match self.read()? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        let buf = &self.buf[..len];
+        let we_version = self.range.we_version();
+        let layout = StreamLayout::detect(buf, we_version)
+            .unwrap_or_else(|| StreamLayout::native(we_version));
+        let mut networks = events::parse_scan(buf, layout);
+        for network in &mut networks {
+            network.max_quality = Some(self.range.max_quality());
+        }
+        Ok(Some(networks))
+    }
+}
+
+/// The next buffer size to try after SIOCGIWSCAN returned E2BIG, or `None` if
+/// the buffer can't get any bigger.
+fn grow_buffer(current: usize, wanted: usize) -> Option<usize> {
+    if current >= IW_SCAN_BUFFER_LIMIT {
+        return None;
+    }
+    let len = if wanted > current { wanted } else { current * 2 };
+    Some(len.min(IW_SCAN_BUFFER_LIMIT))
+}
+
+/// Whether the `flags` of an SIOCGIWENCODE event say the network uses
+/// encryption. The kernel sets IW_ENCODE_DISABLED for open networks rather than
+/// a flag for protected ones.
+pub(crate) fn privacy_from_key_flags(key_flags: u16) -> bool {
+    key_flags & IW_ENCODE_DISABLED == 0
+}
+
+impl IwReq {
+    fn new(interface: &str) -> Result<IwReq, Error> {
+        let name = CString::new(interface)
+            .map_err(|_| {
+                Error::InvalidInput {
+                    call: "ifr_name",
+                    message: "interface name contains a NUL byte",
+                }
+            })?;
+        let name = name.as_bytes_with_nul();
+        if name.len() > IFNAMSIZ {
+            return Err(Error::InvalidInput {
+                call: "ifr_name",
+                message: "interface name is too long",
+            });
+        }
+        let mut req: IwReq = unsafe { mem::zeroed() };
+        for (dst, &src) in req.ifr_name.iter_mut().zip(name) {
+            *dst = src as c_char;
+        }
+        Ok(req)
+    }
+}
+
+/// The datagram socket the wireless ioctls are issued on, closed on drop.
+struct Socket {
+    fd: c_int,
+}
+
+impl Socket {
+    fn open() -> Result<Socket, Error> {
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(Error::last_os_error("socket"));
+        }
+        Ok(Socket { fd })
+    }
+
+    /// Fetch the interface's `iw_range` with SIOCGIWRANGE.
+    fn range(&self, req: &mut IwReq) -> Result<iw_range, Error> {
+        // Leave room for drivers built against a newer, longer iw_range than ours.
+        let mut buf = vec![0u8; mem::size_of::<iw_range>() * 2];
+        req.u.data = IwPoint {
+            pointer: buf.as_mut_ptr() as *mut c_void,
+            length: buf.len() as u16,
+            flags: 0,
+        };
+        self.ioctl(SIOCGIWRANGE, req)?;
+        Ok(unsafe { ptr::read_unaligned(buf.as_ptr() as *const iw_range) })
+    }
+
+    fn ioctl(&self, request: u16, req: &mut IwReq) -> Result<(), Error> {
+        if unsafe { libc::ioctl(self.fd, request as _, req as *mut IwReq) } < 0 {
+            return Err(Error::last_os_error(ioctl_name(request)));
+        }
+        Ok(())
+    }
+}
+
+fn ioctl_name(request: u16) -> &'static str {
+    match request {
+        SIOCGIWRANGE => "SIOCGIWRANGE",
+        SIOCSIWSCAN => "SIOCSIWSCAN",
+        SIOCGIWSCAN => "SIOCGIWSCAN",
+        _ => "ioctl",
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util;
+
+    #[test]
+    fn buffer_growth() {
+        assert_eq!(grow_buffer(4096, 0), Some(8192));
+        assert_eq!(grow_buffer(4096, 10000), Some(10000));
+        assert_eq!(grow_buffer(40000, 0), Some(IW_SCAN_BUFFER_LIMIT));
+        assert_eq!(grow_buffer(IW_SCAN_BUFFER_LIMIT, 0), None);
+    }
+
+    #[test]
+    fn scan_options() {
+        assert!(iw_scan_req::from_request(&ScanRequest::new("wlan0")).unwrap().is_none());
+        assert_eq!(mem::size_of::<iw_scan_req>(), 316);
+
+        let request = ScanRequest::new("wlan0").ssid("hidden").frequency(2412).frequency(5180);
+        let (req, flags) = iw_scan_req::from_request(&request).unwrap().unwrap();
+        assert_eq!(flags, IW_SCAN_THIS_ESSID | IW_SCAN_THIS_FREQ);
+        assert_eq!(req.scan_type, 0);
+        assert_eq!(&req.essid[..req.essid_len as usize], b"hidden");
+        assert_eq!(req.num_channels, 2);
+        assert_eq!((req.channel_list[1].m, req.channel_list[1].e), (5180, 6));
+        assert_eq!(req.bssid.sa_data[..6].iter().map(|&b| b as u8).collect::<Vec<_>>(),
+                   vec![0xff; 6]);
+
+        let (req, flags) = iw_scan_req::from_request(&ScanRequest::new("wlan0").passive(true))
+            .unwrap()
+            .unwrap();
+        assert_eq!((req.scan_type, flags), (IW_SCAN_TYPE_PASSIVE, 0));
+    }
+
+    #[test]
+    fn unsupported_scan_options() {
+        let flush = ScanRequest::new("wlan0").flush(true);
+        assert!(matches!(iw_scan_req::from_request(&flush), Err(Error::Unsupported { .. })));
+        let two = ScanRequest::new("wlan0").ssid("a").ssid("b");
+        assert!(matches!(iw_scan_req::from_request(&two), Err(Error::Unsupported { .. })));
+    }
+
+    // Records testdata/wext/<interface>-we<version>.bin from the driver's
+    // cached scan results, for `decodes_captured_streams` in events.rs to
+    // replay. Needs a wireless interface that has scanned recently:
+    // DRADIS_CAPTURE_INTERFACE=wlan0 cargo test records_live_stream -- --ignored
+    #[test]
+    #[ignore]
+    fn records_live_stream() {
+        let interface = test_util::capture_interface();
+        let socket = Socket::open().unwrap();
+        let range = socket.range(&mut IwReq::new(&interface).unwrap()).unwrap();
+        let mut scan = WextScan {
+            socket,
+            interface: interface.clone(),
+            range,
+            buf: vec![0u8; IW_SCAN_MAX_DATA],
+            deadline: Instant::now(),
+        };
+        let len = scan.read().unwrap().expect("a scan is still running");
+        assert!(len > 0, "no cached scan results on {}", interface);
+        test_util::save_capture("wext",
+                                &format!("{}-we{}", interface, scan.range.we_version()),
+                                &scan.buf[..len]);
+    }
+
+    #[test]
+    fn interface_names() {
+        assert!(IwReq::new("wlan0").is_ok());
+        assert!(IwReq::new("a-name-much-too-long").is_err());
+        assert!(IwReq::new("wl\0an").is_err());
+    }
+}
@@ -0,0 +1,634 @@
+//! Surveys in the CSV format WiGLE (wigle.net) takes uploads in, version
+//! 1.6 of the `WigleWifi` format its Android app writes.
+//!
+//! `WigleWriter` writes scans along with where and when they were taken, and
+//! `read` turns a file written by it, the WiGLE app or anything else speaking
+//! the format back into networks. The format only keeps what WiGLE maps: the
+//! BSSID, SSID, channel, signal level and an `AuthMode` summary of the
+//! security, like `[WPA2-PSK-CCMP][ESS]`. Rows for Bluetooth and cell towers
+//! are skipped on the way in.
+//!
+//! ```
+//! use dradis::{FixtureBackend, WifiScan};
+//! use dradis::wigle::{self, GpsFix, WigleWriter};
+//!
+//! let scan = WifiScan::scan_with(&mut FixtureBackend::new(Vec::new()), "wlan0").unwrap();
+//! let mut writer = WigleWriter::new(Vec::new()).unwrap();
+//! writer.write_scan(&scan, Some(GpsFix::new(52.52, 13.405)), None).unwrap();
+//! let csv = writer.into_inner();
+//! assert!(wigle::read(&csv[..]).unwrap().is_empty());
+//! ```
+
+use std::io::{self, Read, Write};
+use std::time::SystemTime;
+
+use {Channel, Error, IwQuality, IwStats, MacAddress, Protocol, Security, Ssid, WifiScan, WirelessMode,
+     WirelessNetwork};
+use {csv, date};
+use ie::{AkmSuite, CipherSuite};
+
+const CALL: &str = "WigleWifi CSV";
+
+const COLUMNS: [&str; 14] = ["MAC", "SSID", "AuthMode", "FirstSeen", "Channel", "Frequency", "RSSI",
+                             "CurrentLatitude", "CurrentLongitude", "AltitudeMeters", "AccuracyMeters",
+                             "RCOIs", "MfgrId", "Type"];
+
+/// Where a scan was taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsFix {
+    /// Degrees north.
+    pub latitude: f64,
+    /// Degrees east.
+    pub longitude: f64,
+    /// Meters above sea level, 0 if unknown.
+    pub altitude: f64,
+    /// The radius of uncertainty in meters, 0 if unknown.
+    pub accuracy: f64,
+}
+
+impl GpsFix {
+    pub fn new(latitude: f64, longitude: f64) -> GpsFix {
+        GpsFix {
+            latitude,
+            longitude,
+            altitude: 0.0,
+            accuracy: 0.0,
+        }
+    }
+
+    pub fn altitude(mut self, meters: f64) -> GpsFix {
+        self.altitude = meters;
+        self
+    }
+
+    pub fn accuracy(mut self, meters: f64) -> GpsFix {
+        self.accuracy = meters;
+        self
+    }
+}
+
+/// A network read back from a survey, with when and where it was seen if
+/// the survey said.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WigleRecord {
+    pub network: WirelessNetwork,
+    pub first_seen: Option<SystemTime>,
+    pub fix: Option<GpsFix>,
+}
+
+/// Writes networks as WiGLE CSV rows, after the two header lines.
+pub struct WigleWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> WigleWriter<W> {
+    /// Start a survey by writing the header to `out`.
+    pub fn new(mut out: W) -> io::Result<WigleWriter<W>> {
+        writeln!(out,
+                 "WigleWifi-1.6,appRelease=dradis-{},model=,release=,device=,display=,board=,brand=,\
+                  star=Sol,body=3,subBody=0\r",
+                 env!("CARGO_PKG_VERSION"))?;
+        csv::write_record(&mut out, &COLUMNS)?;
+        Ok(WigleWriter { out })
+    }
+
+    /// Write every network in `scan`, seen at `fix` at `time`, or now if
+    /// `time` is `None`.
+    pub fn write_scan(&mut self,
+                      scan: &WifiScan,
+                      fix: Option<GpsFix>,
+                      time: Option<SystemTime>)
+                      -> io::Result<()> {
+        let time = time.unwrap_or_else(SystemTime::now);
+        for network in &scan.networks {
+            self.write_network(network, fix, time)?;
+        }
+        Ok(())
+    }
+
+    /// Write one network. Networks without a BSSID can't be told apart on
+    /// WiGLE and are left out.
+    pub fn write_network(&mut self,
+                         network: &WirelessNetwork,
+                         fix: Option<GpsFix>,
+                         time: SystemTime)
+                         -> io::Result<()> {
+        let bssid = match network.bssid {
+            Some(bssid) => bssid.to_string(),
+            None => return Ok(()),
+        };
+        let ssid = match network.essid {
+            Some(ssid) => ssid.as_str().map_or_else(|| ssid.to_string(), str::to_string),
+            None => String::new(),
+        };
+        let channel = network.channel();
+        let mhz = match (channel, network.freq) {
+            (Some(channel), _) => channel.mhz(),
+            (None, Some(hz)) => (hz / 1e6).round() as u32,
+            (None, None) => 0,
+        };
+        let fix = fix.unwrap_or_else(|| GpsFix::new(0.0, 0.0));
+        csv::write_record(&mut self.out,
+                          &[&bssid,
+                            &ssid,
+                            &auth_mode(network),
+                            &format_time(time),
+                            &channel.map_or(0, |channel| channel.number()).to_string(),
+                            &mhz.to_string(),
+                            &network.signal_dbm().unwrap_or(0).to_string(),
+                            &fix.latitude.to_string(),
+                            &fix.longitude.to_string(),
+                            &fix.altitude.to_string(),
+                            &fix.accuracy.to_string(),
+                            "",
+                            "",
+                            "WIFI"])
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Finish the survey and hand back the writer it went to.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Read the Wi-Fi networks out of a WiGLE CSV survey.
+///
+/// A network's security comes back as far as `AuthMode` describes it: the
+/// protocol, AKM suites and pairwise ciphers, but not the group cipher or
+/// management frame protection. An RSSI of 0 and a position of 0, 0 mean
+/// the survey didn't know them.
+pub fn read<R: Read>(mut input: R) -> Result<Vec<WigleRecord>, Error> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes).map_err(|err| Error::from_io(CALL, err))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut records = csv::records(text.trim_start_matches('\u{feff}')).into_iter();
+    let mut header = records.next();
+    if header.as_ref().is_some_and(|(_, fields)| fields[0].starts_with("WigleWifi")) {
+        header = records.next();
+    }
+    let header = match header {
+        Some((_, fields)) => fields,
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| header.iter().position(|field| field == name);
+    let required = |name: &'static str| {
+        column(name).ok_or_else(|| Error::parse(CALL, format!("no {} column", name)))
+    };
+    let (mac, ssid, auth, channel, rssi) =
+        (required("MAC")?, required("SSID")?, required("AuthMode")?, required("Channel")?, required("RSSI")?);
+    let (seen, frequency, kind) = (column("FirstSeen"), column("Frequency"), column("Type"));
+    let (latitude, longitude) = (column("CurrentLatitude"), column("CurrentLongitude"));
+    let (altitude, accuracy) = (column("AltitudeMeters"), column("AccuracyMeters"));
+
+    let mut networks = Vec::new();
+    for (line, fields) in records {
+        if fields.len() < header.len() {
+            return Err(Error::parse(CALL, format!("line {}: expected {} fields", line, header.len())));
+        }
+        if kind.is_some_and(|kind| fields[kind] != "WIFI") {
+            continue;
+        }
+        let bad = |what: &str, value: &str| {
+            Error::parse(CALL, format!("line {}: bad {} '{}'", line, what, value))
+        };
+        let number = |index: Option<usize>, what: &str| -> Result<f64, Error> {
+            match index.map(|index| fields[index].as_str()) {
+                None | Some("") => Ok(0.0),
+                Some(value) => value.parse().map_err(|_| bad(what, value)),
+            }
+        };
+        let bssid: MacAddress = fields[mac].parse().map_err(|_| bad("MAC", &fields[mac]))?;
+        let rssi = number(Some(rssi), "RSSI")? as i32;
+        let mhz = number(frequency, "Frequency")? as u32;
+        let channel = number(Some(channel), "Channel")? as u16;
+        let freq = if mhz > 0 {
+            Some(mhz as f64 * 1e6)
+        } else {
+            Channel::from_number(channel).map(|channel| channel.hz())
+        };
+        let fix = GpsFix {
+            latitude: number_or_zero(&fields, latitude),
+            longitude: number_or_zero(&fields, longitude),
+            altitude: number_or_zero(&fields, altitude),
+            accuracy: number_or_zero(&fields, accuracy),
+        };
+        let first_seen = match seen.map(|seen| fields[seen].as_str()) {
+            None | Some("") => None,
+            Some(time) => Some(parse_time(time).ok_or_else(|| bad("FirstSeen", time))?),
+        };
+        let (security, mode) = parse_auth_mode(&fields[auth]);
+        networks.push(WigleRecord {
+            network: WirelessNetwork {
+                bssid: Some(bssid),
+                stats: if rssi == 0 { None } else { Some(IwStats::new(IwQuality::from_dbm(rssi))) },
+                max_quality: None,
+                maxbitrate: None,
+                freq,
+                key: None,
+                essid: Some(Ssid::truncated(fields[ssid].as_bytes())),
+                mode,
+                security,
+                ies: Vec::new(),
+            },
+            first_seen,
+            fix: if fix.latitude == 0.0 && fix.longitude == 0.0 { None } else { Some(fix) },
+        });
+    }
+    Ok(networks)
+}
+
+fn number_or_zero(fields: &[String], index: Option<usize>) -> f64 {
+    index.and_then(|index| fields[index].parse().ok()).unwrap_or(0.0)
+}
+
+fn akm_name(akm: AkmSuite) -> Option<&'static str> {
+    Some(match akm {
+        AkmSuite::Ieee8021x => "EAP",
+        AkmSuite::Psk => "PSK",
+        AkmSuite::FtIeee8021x => "FT/EAP",
+        AkmSuite::FtPsk => "FT/PSK",
+        AkmSuite::Ieee8021xSha256 => "EAP-SHA256",
+        AkmSuite::PskSha256 => "PSK-SHA256",
+        AkmSuite::Sae => "SAE",
+        AkmSuite::FtSae => "FT/SAE",
+        AkmSuite::SuiteB => "EAP-SUITE-B",
+        AkmSuite::SuiteB192 => "EAP-SUITE-B-192",
+        AkmSuite::FtIeee8021xSha384 => "FT/EAP-SHA384",
+        AkmSuite::FilsSha256 => "FILS-SHA256",
+        AkmSuite::FilsSha384 => "FILS-SHA384",
+        AkmSuite::FtFilsSha256 => "FT/FILS-SHA256",
+        AkmSuite::FtFilsSha384 => "FT/FILS-SHA384",
+        AkmSuite::Owe => "OWE",
+        AkmSuite::FtPskSha384 => "FT/PSK-SHA384",
+        AkmSuite::PskSha384 => "PSK-SHA384",
+        AkmSuite::SaeExt => "SAE-EXT-KEY",
+        AkmSuite::FtSaeExt => "FT/SAE-EXT-KEY",
+        AkmSuite::Tdls | AkmSuite::ApPeerKey | AkmSuite::Other(..) => return None,
+    })
+}
+
+fn cipher_name(cipher: CipherSuite) -> Option<&'static str> {
+    Some(match cipher {
+        CipherSuite::Tkip => "TKIP",
+        CipherSuite::Ccmp128 => "CCMP",
+        CipherSuite::Ccmp256 => "CCMP-256",
+        CipherSuite::Gcmp128 => "GCMP",
+        CipherSuite::Gcmp256 => "GCMP-256",
+        _ => return None,
+    })
+}
+
+const AKMS: [AkmSuite; 20] = [AkmSuite::Ieee8021x, AkmSuite::Psk, AkmSuite::FtIeee8021x, AkmSuite::FtPsk,
+                              AkmSuite::Ieee8021xSha256, AkmSuite::PskSha256, AkmSuite::Sae, AkmSuite::FtSae,
+                              AkmSuite::SuiteB, AkmSuite::SuiteB192, AkmSuite::FtIeee8021xSha384,
+                              AkmSuite::FilsSha256, AkmSuite::FilsSha384, AkmSuite::FtFilsSha256,
+                              AkmSuite::FtFilsSha384, AkmSuite::Owe, AkmSuite::FtPskSha384,
+                              AkmSuite::PskSha384,
+                              AkmSuite::SaeExt, AkmSuite::FtSaeExt];
+
+const CIPHERS: [CipherSuite; 5] = [CipherSuite::Tkip, CipherSuite::Ccmp128, CipherSuite::Ccmp256,
+                                   CipherSuite::Gcmp128, CipherSuite::Gcmp256];
+
+/// One `[LABEL-AKM+AKM-CIPHER+CIPHER]` flag, the way wpa_supplicant and
+/// Android write them.
+fn flag(label: &str, akms: &[AkmSuite], ciphers: &[CipherSuite]) -> String {
+    let akms: Vec<&str> = akms.iter().filter_map(|&akm| akm_name(akm)).collect();
+    let ciphers: Vec<&str> = ciphers.iter().filter_map(|&cipher| cipher_name(cipher)).collect();
+    let mut flag = format!("[{}", label);
+    for part in &[akms.join("+"), ciphers.join("+")] {
+        if !part.is_empty() {
+            flag.push('-');
+            flag.push_str(part);
+        }
+    }
+    flag.push(']');
+    flag
+}
+
+/// The `AuthMode` for `network`, such as `[WPA-PSK-TKIP][WPA2-PSK-CCMP][ESS]`.
+pub fn auth_mode(network: &WirelessNetwork) -> String {
+    let security = &network.security;
+    let mut mode = String::new();
+    if security.protocol == Protocol::Wpa || security.wpa_compatible {
+        // The original WPA element only ever carried these.
+        let akms: Vec<AkmSuite> = security.akm_suites
+            .iter()
+            .cloned()
+            .filter(|&akm| akm == AkmSuite::Psk || akm == AkmSuite::Ieee8021x)
+            .collect();
+        let ciphers: Vec<CipherSuite> = security.pairwise_ciphers
+            .iter()
+            .cloned()
+            .filter(|&cipher| cipher == CipherSuite::Tkip || cipher == CipherSuite::Ccmp128)
+            .collect();
+        mode.push_str(&flag("WPA", &akms, &ciphers));
+    }
+    match security.protocol {
+        Protocol::Open | Protocol::Wpa => {}
+        Protocol::Wep => mode.push_str("[WEP]"),
+        Protocol::Wpa2 => mode.push_str(&flag("WPA2", &security.akm_suites, &security.pairwise_ciphers)),
+        Protocol::Wpa3 => mode.push_str(&flag("WPA3", &security.akm_suites, &security.pairwise_ciphers)),
+    }
+    mode.push_str(match network.mode {
+        Some(WirelessMode::AdHoc) => "[IBSS]",
+        Some(WirelessMode::Mesh) => "[MESH]",
+        _ => "[ESS]",
+    });
+    mode
+}
+
+fn is_wpa3(akm: AkmSuite) -> bool {
+    matches!(akm,
+             AkmSuite::Sae | AkmSuite::FtSae | AkmSuite::SaeExt | AkmSuite::FtSaeExt | AkmSuite::Owe |
+             AkmSuite::SuiteB192 | AkmSuite::FtIeee8021xSha384)
+}
+
+/// Decode an `AuthMode` into the security and the mode it describes.
+pub fn parse_auth_mode(auth_mode: &str) -> (Security, Option<WirelessMode>) {
+    let mut security = Security::open();
+    let mut mode = None;
+    let mut wpa = false;
+    let mut rsn = false;
+    for flag in auth_mode.split(']').map(|flag| flag.trim_start_matches('[')) {
+        let mut parts = flag.splitn(2, '-');
+        let label = parts.next().unwrap_or("");
+        let rest = parts.next().unwrap_or("");
+        match label {
+            "ESS" => mode = Some(WirelessMode::Master),
+            "IBSS" => mode = Some(WirelessMode::AdHoc),
+            "MESH" => mode = Some(WirelessMode::Mesh),
+            "WEP" => security.protocol = security.protocol.max(Protocol::Wep),
+            "WPA" | "WPA2" | "RSN" | "WPA3" => {
+                if label == "WPA" {
+                    wpa = true;
+                } else {
+                    rsn = true;
+                }
+                let protocol = match label {
+                    "WPA" => Protocol::Wpa,
+                    "WPA3" => Protocol::Wpa3,
+                    _ => Protocol::Wpa2,
+                };
+                security.protocol = security.protocol.max(protocol);
+                // AKM names contain dashes too, so match the known names
+                // instead of splitting.
+                for part in rest.split('+').flat_map(split_suites) {
+                    let akm = AKMS.iter().find(|&&akm| akm_name(akm) == Some(part));
+                    let cipher = CIPHERS.iter().find(|&&cipher| cipher_name(cipher) == Some(part));
+                    if let Some(&akm) = akm {
+                        if !security.akm_suites.contains(&akm) {
+                            security.akm_suites.push(akm);
+                        }
+                    } else if let Some(&cipher) = cipher {
+                        if !security.pairwise_ciphers.contains(&cipher) {
+                            security.pairwise_ciphers.push(cipher);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if rsn && security.akm_suites.iter().any(|&akm| is_wpa3(akm)) {
+        security.protocol = Protocol::Wpa3;
+    }
+    security.wpa_compatible = wpa && rsn;
+    (security, mode)
+}
+
+/// Split `PSK-CCMP` into `PSK` and `CCMP`, keeping known names that contain
+/// a dash, like `EAP-SUITE-B-192`, whole.
+fn split_suites(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let known = AKMS.iter()
+            .filter_map(|&akm| akm_name(akm))
+            .chain(CIPHERS.iter().filter_map(|&cipher| cipher_name(cipher)))
+            .filter(|name| rest == *name || rest.starts_with(&format!("{}-", name)))
+            .max_by_key(|name| name.len());
+        let end = match known {
+            Some(name) => name.len(),
+            None => rest.find('-').unwrap_or(rest.len()),
+        };
+        parts.push(&rest[..end]);
+        rest = rest[end..].trim_start_matches('-');
+    }
+    parts
+}
+
+/// `time` as WiGLE writes it, `2024-05-01 13:45:00`, in UTC.
+fn format_time(time: SystemTime) -> String {
+    let secs = date::unix_seconds(time);
+    let (year, month, day) = date::civil_from_days(secs.div_euclid(86400));
+    let secs = secs.rem_euclid(86400);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60)
+}
+
+fn parse_time(text: &str) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| text.get(range).and_then(|part| part.parse::<i64>().ok());
+    let bytes = text.as_bytes();
+    if bytes.len() != 19 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[10] != b' ' || bytes[13] != b':' ||
+       bytes[16] != b':' {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    date::from_civil(year, month, day, hour, minute, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use Band;
+    use security::WPA_OUI;
+
+    fn network(essid: &str, security: Security) -> WirelessNetwork {
+        WirelessNetwork {
+            bssid: Some(MacAddress::new([0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc])),
+            stats: Some(IwStats::new(IwQuality::from_dbm(-61))),
+            maxbitrate: Some(54000000),
+            freq: Some(5.18e9),
+            essid: Ssid::new(essid.as_bytes()),
+            mode: Some(WirelessMode::Master),
+            security,
+            ..WirelessNetwork::default()
+        }
+    }
+
+    fn security(protocol: Protocol, akms: &[AkmSuite], ciphers: &[CipherSuite], wpa: bool) -> Security {
+        Security {
+            protocol,
+            group_cipher: None,
+            pairwise_ciphers: ciphers.to_vec(),
+            akm_suites: akms.to_vec(),
+            pmf_required: false,
+            pmf_capable: false,
+            wpa_compatible: wpa,
+        }
+    }
+
+    #[test]
+    fn writes_the_wigle_format() {
+        let time = UNIX_EPOCH + Duration::from_secs(1714571100);
+        let fix = GpsFix::new(52.52, 13.405).altitude(34.5).accuracy(4.0);
+        let mut writer = WigleWriter::new(Vec::new()).unwrap();
+        let wpa2 = security(Protocol::Wpa2, &[AkmSuite::Psk], &[CipherSuite::Ccmp128], false);
+        let no_bssid = WirelessNetwork { bssid: None, ..network("no bssid", Security::open()) };
+        let scan = WifiScan {
+            networks: vec![network("caf\u{e9}, bar", wpa2), no_bssid],
+            stale: false,
+        };
+        writer.write_scan(&scan, Some(fix), Some(time)).unwrap();
+        let csv = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("WigleWifi-1.6,appRelease=dradis-"));
+        assert_eq!(lines[1],
+                   "MAC,SSID,AuthMode,FirstSeen,Channel,Frequency,RSSI,CurrentLatitude,CurrentLongitude,\
+                    AltitudeMeters,AccuracyMeters,RCOIs,MfgrId,Type");
+        assert_eq!(lines[2],
+                   "00:11:22:aa:bb:cc,\"caf\u{e9}, bar\",[WPA2-PSK-CCMP][ESS],2024-05-01 13:45:00,36,5180,\
+                    -61,52.52,13.405,34.5,4,,,WIFI");
+        assert!(csv.ends_with("WIFI\r\n"));
+    }
+
+    #[test]
+    fn builds_auth_modes() {
+        let cases = [(Security::open(), "[ESS]"),
+                     (security(Protocol::Wep, &[], &[], false), "[WEP][ESS]"),
+                     (security(Protocol::Wpa, &[AkmSuite::Psk], &[CipherSuite::Tkip], false),
+                      "[WPA-PSK-TKIP][ESS]"),
+                     (security(Protocol::Wpa2,
+                               &[AkmSuite::Psk],
+                               &[CipherSuite::Ccmp128, CipherSuite::Tkip],
+                               true),
+                      "[WPA-PSK-CCMP+TKIP][WPA2-PSK-CCMP+TKIP][ESS]"),
+                     (security(Protocol::Wpa3,
+                               &[AkmSuite::Psk, AkmSuite::Sae],
+                               &[CipherSuite::Ccmp128],
+                               false),
+                      "[WPA3-PSK+SAE-CCMP][ESS]"),
+                     (security(Protocol::Wpa3, &[AkmSuite::SuiteB192], &[CipherSuite::Gcmp256], false),
+                      "[WPA3-EAP-SUITE-B-192-GCMP-256][ESS]"),
+                     (security(Protocol::Wpa2,
+                               &[AkmSuite::FtPsk, AkmSuite::Other(WPA_OUI, 9)],
+                               &[CipherSuite::Ccmp128],
+                               false),
+                      "[WPA2-FT/PSK-CCMP][ESS]")];
+        for &(ref security, expected) in &cases {
+            let network = network("x", security.clone());
+            assert_eq!(auth_mode(&network), expected);
+            let mut known = security.clone();
+            known.akm_suites.retain(|&akm| akm_name(akm).is_some());
+            assert_eq!(parse_auth_mode(expected), (known, Some(WirelessMode::Master)), "{}", expected);
+        }
+        let ibss = WirelessNetwork { mode: Some(WirelessMode::AdHoc), ..network("x", Security::open()) };
+        assert_eq!(auth_mode(&ibss), "[IBSS]");
+    }
+
+    #[test]
+    fn reads_android_style_auth_modes() {
+        let (security, _) = parse_auth_mode("[RSN-PSK+SAE-CCMP][ESS][WPS]");
+        assert_eq!(security.protocol, Protocol::Wpa3);
+        assert!(security.is_transition_mode());
+        let (security, mode) = parse_auth_mode("[WPA2-EAP/SHA1-CCMP][RSN-EAP/SHA1-CCMP][ESS]");
+        assert_eq!(security.protocol, Protocol::Wpa2);
+        assert_eq!(security.pairwise_ciphers, vec![CipherSuite::Ccmp128]);
+        assert_eq!(mode, Some(WirelessMode::Master));
+        assert_eq!(parse_auth_mode("").0, Security::open());
+    }
+
+    #[test]
+    fn round_trips_a_survey() {
+        let time = UNIX_EPOCH + Duration::from_secs(1714571100);
+        let fix = GpsFix::new(-33.8688, 151.2093).accuracy(12.0);
+        let wpa2 = security(Protocol::Wpa2, &[AkmSuite::Psk], &[CipherSuite::Ccmp128], true);
+        let original = network("caf\u{e9}", wpa2);
+        let mut writer = WigleWriter::new(Vec::new()).unwrap();
+        writer.write_network(&original, Some(fix), time).unwrap();
+        writer.write_network(&original, None, time).unwrap();
+        let records = read(&writer.into_inner()[..]).unwrap();
+        assert_eq!(records.len(), 2);
+        let record = &records[0];
+        assert_eq!(record.first_seen, Some(time));
+        assert_eq!(record.fix, Some(fix));
+        assert_eq!(records[1].fix, None);
+        let network = &record.network;
+        assert_eq!(network.bssid, original.bssid);
+        assert_eq!(network.essid, original.essid);
+        assert_eq!(network.channel(), original.channel());
+        assert_eq!(network.signal_dbm(), Some(-61));
+        assert_eq!(network.security, original.security);
+        assert_eq!(network.mode, original.mode);
+    }
+
+    #[test]
+    fn reads_the_wigle_app_format() {
+        let csv = "WigleWifi-1.4,appRelease=2.26,model=Pixel 3,release=10,device=blueline,display=QQ1A,\
+                   board=blueline,brand=google\n\
+                   MAC,SSID,AuthMode,FirstSeen,Channel,RSSI,CurrentLatitude,CurrentLongitude,AltitudeMeters,\
+                   AccuracyMeters,Type\n\
+                   a4:2b:b0:11:22:33,CoffeeShop,[WPA2-PSK-CCMP][ESS],2019-08-02 17:25:12,6,-67,40.7128,\
+                   -74.006,10,5,WIFI\n\
+                   00:1a:7d:da:71:13,Headphones,Misc [LE],2019-08-02 17:25:13,0,-80,40.7128,-74.006,10,5,\
+                   BLE\n\
+                   b8:27:eb:00:00:01,,[ESS],2019-08-02 17:25:14,149,0,0,0,0,0,WIFI\n";
+        let records = read(csv.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+        let cafe = &records[0].network;
+        assert_eq!(cafe.essid, Ssid::new(b"CoffeeShop"));
+        assert_eq!(cafe.channel().map(|channel| channel.number()), Some(6));
+        assert_eq!(cafe.security.protocol, Protocol::Wpa2);
+        assert_eq!(records[0].fix.map(|fix| fix.latitude), Some(40.7128));
+        let hidden = &records[1];
+        assert!(hidden.network.essid.unwrap().is_hidden());
+        assert_eq!(hidden.network.band(), Some(Band::FiveGhz));
+        assert_eq!(hidden.network.stats, None);
+        assert_eq!(hidden.fix, None);
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        let header = "MAC,SSID,AuthMode,FirstSeen,Channel,RSSI,Type\n";
+        let bad_mac = format!("{}nope,x,[ESS],,1,-50,WIFI\n", header);
+        let err = read(bad_mac.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Parse { .. }));
+        assert!(err.to_string().contains("line 2"));
+        assert!(read(format!("{}00:11:22:33:44:55,x\n", header).as_bytes()).is_err());
+        let bad_time = format!("{}00:11:22:33:44:55,x,[ESS],yesterday,1,-50,WIFI\n", header);
+        assert!(read(bad_time.as_bytes()).is_err());
+        assert!(read("SSID,RSSI\n".as_bytes()).is_err());
+        assert!(read(&b""[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn converts_dates() {
+        for &secs in &[0i64, 951782400, 1714571100, 4107542399, -86400] {
+            let time = if secs >= 0 {
+                UNIX_EPOCH + Duration::from_secs(secs as u64)
+            } else {
+                UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+            };
+            assert_eq!(parse_time(&format_time(time)), Some(time), "{}", format_time(time));
+        }
+        assert_eq!(format_time(UNIX_EPOCH + Duration::from_secs(951782400)), "2000-02-29 00:00:00");
+        assert_eq!(parse_time("2024-13-01 00:00:00"), None);
+        assert!(parse_time("2024-02-29 00:00:00").is_some());
+        assert_eq!(parse_time("2023-02-29 00:00:00"), None);
+        assert_eq!(parse_time("2024-04-31 00:00:00"), None);
+        assert_eq!(parse_time("2024-05-01 -1:00:00"), None);
+        assert_eq!(parse_time("2024-05-01T00:00:00"), None);
+    }
+}
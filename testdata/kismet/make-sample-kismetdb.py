@@ -0,0 +1,89 @@
+#!/usr/bin/env python3
+"""Write sample.kismet, a cut-down kismetdb log for the tests.
+
+The tables have the columns Kismet's own have; the device blobs only carry
+the fields dradis reads, plus a client that should be skipped.
+"""
+
+import json
+import os
+import sqlite3
+
+path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "sample.kismet")
+if os.path.exists(path):
+    os.remove(path)
+
+devices = [
+    ("Wi-Fi AP", "A4:2B:B0:11:22:33", {
+        "kismet.device.base.macaddr": "A4:2B:B0:11:22:33",
+        "kismet.device.base.type": "Wi-Fi AP",
+        "kismet.device.base.phyname": "IEEE802.11",
+        "kismet.device.base.name": "CoffeeShop",
+        "kismet.device.base.commonname": "CoffeeShop",
+        "kismet.device.base.crypt": "WPA2-PSK AES-CCMP",
+        "kismet.device.base.channel": "6",
+        "kismet.device.base.frequency": 2437000,
+        "kismet.device.base.first_time": 1564680007,
+        "kismet.device.base.last_time": 1564680704,
+        "kismet.device.base.signal": {
+            "kismet.common.signal.type": "dbm",
+            "kismet.common.signal.last_signal": -58,
+            "kismet.common.signal.min_signal": -71,
+            "kismet.common.signal.max_signal": -49,
+        },
+        "dot11.device": {
+            "dot11.device.last_beaconed_ssid_record": {
+                "dot11.advertisedssid.ssid": "CoffeeShop",
+                "dot11.advertisedssid.cloaked": 0,
+                "dot11.advertisedssid.maxrate": 54.0,
+            },
+        },
+    }),
+    ("Wi-Fi AP", "00:11:22:AA:BB:CC", {
+        "kismet.device.base.macaddr": "00:11:22:AA:BB:CC",
+        "kismet.device.base.type": "Wi-Fi AP",
+        "kismet.device.base.phyname": "IEEE802.11",
+        "kismet.device.base.crypt": "WPA2-PSK WPA3-SAE AES-CCMP",
+        "kismet.device.base.channel": "36",
+        "kismet.device.base.frequency": 0,
+        "kismet.device.base.first_time": 1564680100,
+        "kismet.device.base.last_time": 1564680100,
+        "kismet.device.base.signal": {
+            "kismet.common.signal.type": "dbm",
+            "kismet.common.signal.last_signal": -67,
+            "kismet.common.signal.min_signal": -67,
+            "kismet.common.signal.max_signal": -67,
+        },
+        "dot11.device": {
+            "dot11.device.advertised_ssid_map": [
+                {"dot11.advertisedssid.ssid": "", "dot11.advertisedssid.cloaked": 1},
+            ],
+        },
+    }),
+    ("Wi-Fi Client", "3C:28:6D:AA:BB:CC", {
+        "kismet.device.base.macaddr": "3C:28:6D:AA:BB:CC",
+        "kismet.device.base.type": "Wi-Fi Client",
+        "kismet.device.base.phyname": "IEEE802.11",
+        "kismet.device.base.channel": "6",
+    }),
+]
+
+db = sqlite3.connect(path)
+db.execute("CREATE TABLE KISMET (kismet_version TEXT, db_version INT, db_module TEXT)")
+db.execute("INSERT INTO KISMET VALUES ('2019-08-GIT', 8, 'kismetlog')")
+db.execute("CREATE TABLE devices (first_time INT, last_time INT, devkey TEXT, phyname TEXT, "
+           "devmac TEXT, strongest_signal INT, min_lat REAL, min_lon REAL, max_lat REAL, "
+           "max_lon REAL, avg_lat REAL, avg_lon REAL, bytes_data INT, type TEXT, device BLOB, "
+           "UNIQUE(phyname, devmac) ON CONFLICT REPLACE)")
+for kind, mac, device in devices:
+    blob = json.dumps(device, sort_keys=True).encode()
+    db.execute("INSERT INTO devices VALUES (?, ?, ?, 'IEEE802.11', ?, ?, 0, 0, 0, 0, 0, 0, 0, ?, ?)",
+               (device.get("kismet.device.base.first_time", 0),
+                device.get("kismet.device.base.last_time", 0),
+                "4202770D00000000_" + mac.replace(":", ""),
+                mac,
+                device.get("kismet.device.base.signal", {}).get("kismet.common.signal.max_signal", 0),
+                kind,
+                blob))
+db.commit()
+db.close()